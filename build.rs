@@ -0,0 +1,4 @@
+// Recompile lorsque le dossier des migrations change, `sqlx::migrate!` les embarquant à la compilation.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
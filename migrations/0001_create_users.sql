@@ -0,0 +1,7 @@
+-- Table des utilisateurs, identique à celle des bases créées avant les migrations.
+CREATE TABLE IF NOT EXISTS users
+(
+    id   INTEGER NOT NULL PRIMARY KEY,
+    uuid TEXT,
+    name TEXT
+);
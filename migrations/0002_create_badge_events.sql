@@ -0,0 +1,15 @@
+-- Historique des passages de badge, horodatés en UTC.
+CREATE TABLE IF NOT EXISTS badge_events
+(
+    id        INTEGER NOT NULL PRIMARY KEY,
+    timestamp TEXT    NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now')),
+    card_uid  TEXT    NOT NULL,
+    user_id   INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    reader    TEXT    NOT NULL,
+    result    TEXT    NOT NULL CHECK (result IN ('granted', 'denied')),
+    reason    TEXT
+);
+
+CREATE INDEX IF NOT EXISTS badge_events_user ON badge_events (user_id, timestamp);
+CREATE INDEX IF NOT EXISTS badge_events_timestamp ON badge_events (timestamp);
+CREATE INDEX IF NOT EXISTS badge_events_reader ON badge_events (reader, timestamp);
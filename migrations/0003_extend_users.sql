@@ -0,0 +1,22 @@
+-- Fiche utilisateur : identité, matricule, service et statut, indépendants de la carte.
+ALTER TABLE users ADD COLUMN first_name TEXT;
+ALTER TABLE users ADD COLUMN last_name TEXT;
+ALTER TABLE users ADD COLUMN employee_number TEXT;
+ALTER TABLE users ADD COLUMN email TEXT;
+ALTER TABLE users ADD COLUMN department TEXT;
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'suspended'));
+ALTER TABLE users ADD COLUMN created_at TEXT;
+ALTER TABLE users ADD COLUMN updated_at TEXT;
+
+UPDATE users SET created_at = strftime('%Y-%m-%d %H:%M:%S', 'now'), updated_at = strftime('%Y-%m-%d %H:%M:%S', 'now');
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_employee_number ON users (employee_number) WHERE employee_number IS NOT NULL;
+
+-- SQLite n'accepte pas de date par défaut sur une colonne ajoutée : elle est renseignée à l'insertion.
+CREATE TRIGGER IF NOT EXISTS users_created_at AFTER INSERT ON users WHEN NEW.created_at IS NULL
+BEGIN
+    UPDATE users
+    SET created_at = strftime('%Y-%m-%d %H:%M:%S', 'now'),
+        updated_at = strftime('%Y-%m-%d %H:%M:%S', 'now')
+    WHERE id = NEW.id;
+END;
@@ -0,0 +1,19 @@
+-- Cartes des utilisateurs : un utilisateur peut avoir plusieurs cartes au fil du temps.
+CREATE TABLE IF NOT EXISTS cards
+(
+    id         INTEGER NOT NULL PRIMARY KEY,
+    uid        TEXT    NOT NULL UNIQUE,
+    user_id    INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    state      TEXT    NOT NULL DEFAULT 'active' CHECK (state IN ('active', 'lost', 'stolen', 'returned', 'expired')),
+    issued_at  TEXT    NOT NULL DEFAULT (date('now', 'localtime')),
+    expires_at TEXT,
+    updated_at TEXT    NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now'))
+);
+
+CREATE INDEX IF NOT EXISTS cards_user ON cards (user_id);
+
+-- Reprise des cartes enregistrées dans users.uuid, colonne désormais inutilisée.
+INSERT OR IGNORE INTO cards (uid, user_id, issued_at)
+SELECT uuid, id, coalesce(date(created_at, 'localtime'), date('now', 'localtime'))
+FROM users
+WHERE uuid IS NOT NULL AND uuid <> '';
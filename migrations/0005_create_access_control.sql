@@ -0,0 +1,39 @@
+-- Zones protégées, portes (lecteurs) qui y donnent accès, groupes d'accès et règles d'autorisation.
+CREATE TABLE IF NOT EXISTS zones
+(
+    id   INTEGER NOT NULL PRIMARY KEY,
+    name TEXT    NOT NULL UNIQUE
+);
+
+-- Une porte associe un lecteur, désigné par son nom PC/SC ou un préfixe de ce nom, à une zone.
+CREATE TABLE IF NOT EXISTS doors
+(
+    id      INTEGER NOT NULL PRIMARY KEY,
+    reader  TEXT    NOT NULL UNIQUE,
+    zone_id INTEGER NOT NULL REFERENCES zones (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS access_groups
+(
+    id   INTEGER NOT NULL PRIMARY KEY,
+    name TEXT    NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS group_members
+(
+    group_id INTEGER NOT NULL REFERENCES access_groups (id) ON DELETE CASCADE,
+    user_id  INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    PRIMARY KEY (group_id, user_id)
+);
+
+-- Une règle autorise les membres d'un groupe à entrer dans une zone.
+CREATE TABLE IF NOT EXISTS access_rules
+(
+    id       INTEGER NOT NULL PRIMARY KEY,
+    group_id INTEGER NOT NULL REFERENCES access_groups (id) ON DELETE CASCADE,
+    zone_id  INTEGER NOT NULL REFERENCES zones (id) ON DELETE CASCADE,
+    UNIQUE (group_id, zone_id)
+);
+
+CREATE INDEX IF NOT EXISTS group_members_user ON group_members (user_id);
+CREATE INDEX IF NOT EXISTS access_rules_zone ON access_rules (zone_id);
@@ -0,0 +1,33 @@
+-- Plages horaires hebdomadaires des groupes d'accès, en minutes depuis minuit, heure du site.
+CREATE TABLE IF NOT EXISTS schedules
+(
+    id           INTEGER NOT NULL PRIMARY KEY,
+    group_id     INTEGER NOT NULL REFERENCES access_groups (id) ON DELETE CASCADE,
+    weekday      INTEGER NOT NULL CHECK (weekday BETWEEN 0 AND 6),
+    start_minute INTEGER NOT NULL CHECK (start_minute BETWEEN 0 AND 1439),
+    end_minute   INTEGER NOT NULL CHECK (end_minute > start_minute AND end_minute <= 1440),
+    UNIQUE (group_id, weekday, start_minute, end_minute)
+);
+
+-- Dates où un groupe est ouvert ou fermé toute la journée, indépendamment de ses plages et des jours fériés.
+CREATE TABLE IF NOT EXISTS schedule_exceptions
+(
+    id       INTEGER NOT NULL PRIMARY KEY,
+    group_id INTEGER NOT NULL REFERENCES access_groups (id) ON DELETE CASCADE,
+    day      TEXT    NOT NULL,
+    open     INTEGER NOT NULL CHECK (open IN (0, 1)),
+    UNIQUE (group_id, day)
+);
+
+-- Jours fériés, regroupés par calendrier importé depuis un fichier iCalendar.
+CREATE TABLE IF NOT EXISTS holidays
+(
+    id       INTEGER NOT NULL PRIMARY KEY,
+    calendar TEXT    NOT NULL,
+    day      TEXT    NOT NULL,
+    summary  TEXT,
+    UNIQUE (calendar, day)
+);
+
+-- Calendrier des jours fériés appliqué à un groupe, aucun par défaut.
+ALTER TABLE access_groups ADD COLUMN calendar TEXT;
@@ -0,0 +1,22 @@
+-- Badges visiteurs : cartes de l'accueil prêtées aux visiteurs le temps d'une visite.
+CREATE TABLE IF NOT EXISTS visitor_badges
+(
+    id       INTEGER NOT NULL PRIMARY KEY,
+    uid      TEXT    NOT NULL UNIQUE,
+    added_at TEXT    NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now'))
+);
+
+-- Visites : un badge du stock attribué à un visiteur jusqu'à une heure locale du site, puis restitué.
+CREATE TABLE IF NOT EXISTS visits
+(
+    id          INTEGER NOT NULL PRIMARY KEY,
+    badge_id    INTEGER NOT NULL REFERENCES visitor_badges (id) ON DELETE CASCADE,
+    visitor     TEXT    NOT NULL,
+    host_id     INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    issued_at   TEXT    NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now')),
+    valid_until TEXT    NOT NULL,
+    returned_at TEXT
+);
+
+-- Un badge n'est prêté qu'à un visiteur à la fois.
+CREATE UNIQUE INDEX IF NOT EXISTS visits_open_badge ON visits (badge_id) WHERE returned_at IS NULL;
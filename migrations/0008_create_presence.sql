@@ -0,0 +1,15 @@
+-- Anti-retour d'une zone : désactivé, souple (passage accordé mais signalé) ou strict (passage refusé),
+-- et délai en minutes après lequel la position d'une carte est oubliée.
+ALTER TABLE zones ADD COLUMN passback TEXT NOT NULL DEFAULT 'off' CHECK (passback IN ('off', 'soft', 'hard'));
+ALTER TABLE zones ADD COLUMN passback_reset INTEGER CHECK (passback_reset > 0);
+
+-- Position de chaque carte dans chaque zone, mise à jour à chaque passage accordé sur un lecteur d'entrée ou de sortie.
+CREATE TABLE IF NOT EXISTS presence
+(
+    card_uid TEXT    NOT NULL,
+    zone_id  INTEGER NOT NULL REFERENCES zones (id) ON DELETE CASCADE,
+    inside   INTEGER NOT NULL CHECK (inside IN (0, 1)),
+    -- Heure locale du site au format AAAA-MM-JJ HH:MM:SS.
+    since    TEXT    NOT NULL,
+    PRIMARY KEY (card_uid, zone_id)
+);
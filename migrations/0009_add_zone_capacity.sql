@@ -0,0 +1,2 @@
+-- Nombre maximal de personnes présentes dans une zone, au-delà duquel les entrées sont refusées ; sans limite par défaut.
+ALTER TABLE zones ADD COLUMN max_occupancy INTEGER CHECK (max_occupancy > 0);
@@ -0,0 +1,13 @@
+-- Empreinte Argon2 du code PIN demandé en second facteur sur les lecteurs à double authentification.
+ALTER TABLE users ADD COLUMN pin_hash TEXT;
+
+-- Échecs de saisie du code PIN, comptés sur une période glissante pour bloquer les tentatives répétées.
+CREATE TABLE IF NOT EXISTS pin_failures
+(
+    id        INTEGER NOT NULL PRIMARY KEY,
+    user_id   INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    reader    TEXT    NOT NULL,
+    failed_at TEXT    NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now'))
+);
+
+CREATE INDEX IF NOT EXISTS pin_failures_user ON pin_failures (user_id, failed_at);
@@ -0,0 +1,25 @@
+-- Journal des actions d'administration. Chaque entrée porte l'empreinte SHA-256 de la précédente ;
+-- la contrainte d'unicité empêche deux entrées de suivre la même.
+CREATE TABLE IF NOT EXISTS audit_log
+(
+    id        INTEGER NOT NULL PRIMARY KEY,
+    at        TEXT    NOT NULL,
+    actor     TEXT    NOT NULL,
+    action    TEXT    NOT NULL,
+    target    TEXT    NOT NULL,
+    before    TEXT,
+    after     TEXT,
+    prev_hash TEXT    NOT NULL UNIQUE,
+    hash      TEXT    NOT NULL
+);
+
+-- Le journal est en ajout seul.
+CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'le journal d''audit ne peut pas être modifié');
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'le journal d''audit ne peut pas être modifié');
+END;
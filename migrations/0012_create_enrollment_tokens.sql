@@ -0,0 +1,9 @@
+-- Jetons à usage unique des liens d'enrôlement servis aux téléphones ; seule leur empreinte SHA-256 est conservée.
+CREATE TABLE IF NOT EXISTS enrollment_tokens
+(
+    id          INTEGER NOT NULL PRIMARY KEY,
+    token_hash  TEXT    NOT NULL UNIQUE,
+    created_at  TEXT    NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now')),
+    expires_at  TEXT    NOT NULL,
+    redeemed_at TEXT
+);
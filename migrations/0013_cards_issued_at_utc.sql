@@ -0,0 +1,27 @@
+-- La date d'émission d'une carte est toujours fournie par l'application, au jour du site : la valeur par défaut
+-- `date('now', 'localtime')` de 0004, qui dépendait du fuseau de la machine, disparaît. SQLite ne modifie pas
+-- la valeur par défaut d'une colonne : la table est reconstruite.
+CREATE TABLE cards_new
+(
+    id         INTEGER NOT NULL PRIMARY KEY,
+    uid        TEXT    NOT NULL UNIQUE,
+    user_id    INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    state      TEXT    NOT NULL DEFAULT 'active' CHECK (state IN ('active', 'lost', 'stolen', 'returned', 'expired')),
+    issued_at  TEXT    NOT NULL,
+    expires_at TEXT,
+    updated_at TEXT    NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now'))
+);
+
+-- Les cartes reprises de users.uuid par 0004 sont datées de la création de l'utilisateur, en UTC comme la base.
+-- users.uuid n'est plus renseignée depuis 0004 : seules ces cartes y correspondent.
+INSERT INTO cards_new (id, uid, user_id, state, issued_at, expires_at, updated_at)
+SELECT c.id, c.uid, c.user_id, c.state,
+       CASE WHEN c.uid = u.uuid THEN coalesce(date(u.created_at), c.issued_at) ELSE c.issued_at END,
+       c.expires_at, c.updated_at
+FROM cards c
+LEFT JOIN users u ON u.id = c.user_id;
+
+DROP TABLE cards;
+ALTER TABLE cards_new RENAME TO cards;
+
+CREATE INDEX IF NOT EXISTS cards_user ON cards (user_id);
@@ -0,0 +1,386 @@
+/// Module `access` décide si une carte présentée sur un lecteur donne accès à la zone desservie par ce lecteur.
+pub mod access {
+    use sqlx::Error;
+    use std::fmt;
+    use crate::config::config::ReaderConfig;
+    use crate::dbo::dbo::{BadgeResult, Card, CardState, DboManager, PassbackMode, Presence, User, UserStatus, Visit, Zone};
+    use crate::schedule::schedule::GroupSchedule;
+    use crate::timesheet::timesheet::{parse_timestamp, Direction};
+
+    /// Raison d'un refus d'accès.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum DenyReason {
+        UnknownCard,
+        CardLost,
+        CardStolen,
+        CardReturned,
+        CardExpired,
+        UserSuspended,
+        /// Badge visiteur du stock, prêté à personne.
+        VisitorBadgeUnassigned,
+        /// La fin de validité du badge visiteur est passée.
+        VisitExpired,
+        /// Aucun groupe de l'utilisateur n'a de règle pour la zone.
+        NotAllowed(String),
+        /// Les groupes autorisés dans la zone n'y ont pas accès à cette heure.
+        OutsideSchedule(String),
+        /// Lecteur à double authentification : le titulaire n'a pas de code PIN.
+        PinNotSet,
+        /// Lecteur à double authentification : le code PIN saisi est faux.
+        WrongPin,
+        /// Lecteur à double authentification : trop d'échecs récents de saisie du code PIN.
+        PinLocked,
+        /// Lecteur à double authentification : le code PIN n'a pas été saisi à temps.
+        PinTimeout,
+        /// La zone a atteint sa capacité maximale.
+        ZoneFull(String),
+        /// Anti-retour : la carte entre dans une zone où elle est déjà entrée sans en sortir.
+        AlreadyInside(String),
+        /// Anti-retour : la carte sort d'une zone d'où elle est déjà sortie sans y entrer.
+        AlreadyOutside(String),
+    }
+
+    impl fmt::Display for DenyReason {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                DenyReason::UnknownCard => write!(f, "carte inconnue"),
+                DenyReason::CardLost => write!(f, "carte déclarée perdue"),
+                DenyReason::CardStolen => write!(f, "carte déclarée volée"),
+                DenyReason::CardReturned => write!(f, "carte restituée"),
+                DenyReason::CardExpired => write!(f, "carte expirée"),
+                DenyReason::UserSuspended => write!(f, "utilisateur suspendu"),
+                DenyReason::VisitorBadgeUnassigned => write!(f, "badge visiteur non attribué"),
+                DenyReason::VisitExpired => write!(f, "badge visiteur expiré"),
+                DenyReason::NotAllowed(zone) => write!(f, "accès non autorisé à la zone {}", zone),
+                DenyReason::OutsideSchedule(zone) => write!(f, "hors des horaires d'accès à la zone {}", zone),
+                DenyReason::PinNotSet => write!(f, "code PIN non défini"),
+                DenyReason::WrongPin => write!(f, "code PIN erroné"),
+                DenyReason::PinLocked => write!(f, "code PIN bloqué après trop d'échecs"),
+                DenyReason::PinTimeout => write!(f, "code PIN non saisi à temps"),
+                DenyReason::ZoneFull(zone) => write!(f, "zone {} complète", zone),
+                DenyReason::AlreadyInside(zone) => write!(f, "anti-retour, carte déjà entrée dans la zone {}", zone),
+                DenyReason::AlreadyOutside(zone) => write!(f, "anti-retour, carte déjà sortie de la zone {}", zone),
+            }
+        }
+    }
+
+    /// Décision prise pour un passage de badge.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Decision {
+        Granted,
+        /// Accès accordé malgré une infraction à l'anti-retour souple, signalée et enregistrée.
+        Tolerated(DenyReason),
+        Denied(DenyReason),
+    }
+
+    impl Decision {
+        /// Indique si la porte s'ouvre.
+        pub fn is_granted(&self) -> bool {
+            !matches!(self, Decision::Denied(_))
+        }
+
+        /// Résultat enregistré dans l'historique des passages.
+        pub fn result(&self) -> BadgeResult {
+            match self {
+                Decision::Granted | Decision::Tolerated(_) => BadgeResult::Granted,
+                Decision::Denied(_) => BadgeResult::Denied,
+            }
+        }
+
+        /// Raison du refus, ou infraction tolérée, telle qu'enregistrée dans l'historique.
+        pub fn reason(&self) -> Option<String> {
+            match self {
+                Decision::Granted => None,
+                Decision::Tolerated(reason) | Decision::Denied(reason) => Some(reason.to_string()),
+            }
+        }
+    }
+
+    /// Éléments nécessaires à la décision, lus dans la base pour une carte et un lecteur.
+    #[derive(Clone, Debug, Default)]
+    pub struct AccessRequest {
+        pub card: Option<Card>,
+        pub user: Option<User>,
+        /// La zone desservie par le lecteur ; un lecteur sans zone ne fait qu'identifier la carte.
+        pub zone: Option<String>,
+        /// Horaires des groupes autorisés dans la zone, ceux de l'hôte pour un visiteur.
+        pub groups: Vec<GroupSchedule>,
+        /// La carte fait partie du stock des badges visiteurs.
+        pub visitor_badge: bool,
+        /// La visite en cours, pour un badge visiteur prêté.
+        pub visit: Option<Visit>,
+        /// Sens du lecteur : entrée dans la zone ou sortie.
+        pub direction: Option<Direction>,
+        /// Mode anti-retour de la zone.
+        pub passback: PassbackMode,
+        /// Délai en minutes après lequel la position de la carte dans la zone est oubliée.
+        pub passback_reset: Option<i64>,
+        /// Dernière position connue de la carte dans la zone.
+        pub presence: Option<Presence>,
+        /// Nombre maximal de personnes présentes dans la zone, sans limite si `None`.
+        pub max_occupancy: Option<i64>,
+        /// Nombre de personnes présentes dans la zone.
+        pub occupancy: i64,
+    }
+
+    impl AccessRequest {
+        /// Lit dans la base la carte ou la visite, son titulaire, la zone du lecteur, les horaires des groupes qui y sont autorisés
+        /// et la position de la carte dans la zone.
+        ///
+        /// # Arguments
+        ///
+        /// * `db` - La base de données.
+        /// * `uid` - L'UID de la carte présentée.
+        /// * `reader` - La configuration du lecteur, dont le rôle donne le sens du passage.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let decision = AccessRequest::load(&db, "A4504FA1", &config.reader("ACS ACR122U 00 00")).await?.decide(clock.now());
+        /// ```
+        pub async fn load(db: &DboManager, uid: &str, reader: &ReaderConfig) -> Result<AccessRequest, Error> {
+            let card = db.find_card(uid).await?;
+            let user = db.find_user_by_uid(uid).await?;
+            let zone = db.zone_of_reader(&reader.name).await?;
+            let visitor_badge = card.is_none() && db.is_visitor_badge(uid).await?;
+            let visit = if visitor_badge { db.open_visit(uid).await? } else { None };
+            // Un visiteur a les accès de son hôte
+            let holder = user.as_ref().map(|user| user.id).or_else(|| visit.as_ref().and_then(|visit| visit.host_id));
+            let groups = match (holder, &zone) {
+                (Some(holder), Some(zone)) => db.schedules_for(holder, zone).await?,
+                _ => Vec::new(),
+            };
+            let direction = reader.role.direction();
+            let (rule, presence) = match (&zone, direction) {
+                (Some(zone), Some(_)) => (db.find_zone(zone).await?.unwrap_or_default(), db.presence(uid, zone).await?),
+                _ => (Zone::default(), None),
+            };
+            Ok(AccessRequest {
+                card,
+                user,
+                zone,
+                groups,
+                visitor_badge,
+                visit,
+                direction,
+                passback: rule.passback,
+                passback_reset: rule.passback_reset,
+                presence,
+                max_occupancy: rule.max_occupancy,
+                occupancy: rule.occupancy,
+            })
+        }
+
+        /// Nom affiché pour la personne qui présente la carte.
+        pub fn holder(&self) -> String {
+            match (&self.user, &self.visit) {
+                (Some(user), _) => user.name.clone(),
+                (None, Some(visit)) => format!("{} (visiteur de {})", visit.visitor, visit.host.as_deref().unwrap_or("-")),
+                (None, None) => String::new(),
+            }
+        }
+
+        /// Décide si l'accès est accordé à une heure locale donnée, fournie par une `Clock`.
+        ///
+        /// La carte doit être active et non expirée, son titulaire actif, et, si le lecteur dessert une zone,
+        /// l'un des groupes du titulaire doit y être autorisé et y avoir accès à cette heure. L'entrée dans une zone
+        /// qui a atteint sa capacité maximale est refusée.
+        ///
+        /// Un badge visiteur n'est accepté que pendant la visite, jusqu'à sa fin de validité.
+        ///
+        /// Dans une zone avec anti-retour, une carte ne peut entrer à nouveau qu'après être sortie, ni sortir
+        /// qu'après être entrée : le passage est refusé en mode strict et toléré, mais signalé, en mode souple.
+        pub fn decide(&self, now: i64) -> Decision {
+            let reason = match (&self.card, &self.user, &self.visit) {
+                (Some(card), Some(user), _) => self.member_reason(card, user, now),
+                (None, _, Some(visit)) if visit.is_expired(now) => Some(DenyReason::VisitExpired),
+                (None, _, Some(_)) => None,
+                _ if self.visitor_badge => Some(DenyReason::VisitorBadgeUnassigned),
+                _ => Some(DenyReason::UnknownCard),
+            };
+            let reason = reason.or_else(|| self.zone_reason(now)).or_else(|| self.capacity_reason(now));
+            match (reason, self.passback_reason(now)) {
+                (Some(reason), _) => Decision::Denied(reason),
+                (None, Some(reason)) if self.passback == PassbackMode::Soft => Decision::Tolerated(reason),
+                (None, Some(reason)) => Decision::Denied(reason),
+                (None, None) => Decision::Granted,
+            }
+        }
+
+        /// Raison d'un refus liée à la carte ou à son titulaire, l'expiration jugée à la date locale de `now`.
+        fn member_reason(&self, card: &Card, user: &User, now: i64) -> Option<DenyReason> {
+            let reason = match card.effective_state(now) {
+                CardState::Active => None,
+                CardState::Lost => Some(DenyReason::CardLost),
+                CardState::Stolen => Some(DenyReason::CardStolen),
+                CardState::Returned => Some(DenyReason::CardReturned),
+                CardState::Expired => Some(DenyReason::CardExpired),
+            };
+            reason.or_else(|| (user.status == UserStatus::Suspended).then_some(DenyReason::UserSuspended))
+        }
+
+        /// Raison d'un refus liée aux règles et aux horaires de la zone du lecteur.
+        fn zone_reason(&self, now: i64) -> Option<DenyReason> {
+            match &self.zone {
+                Some(zone) if self.groups.is_empty() => Some(DenyReason::NotAllowed(zone.clone())),
+                Some(zone) if !self.groups.iter().any(|group| group.is_open(now)) => Some(DenyReason::OutsideSchedule(zone.clone())),
+                _ => None,
+            }
+        }
+
+        /// Position de la carte dans la zone à une heure locale donnée : passé le délai de remise à zéro, elle est
+        /// oubliée, pour l'anti-retour comme pour l'occupation de la zone.
+        fn presence_at(&self, now: i64) -> Option<&Presence> {
+            self.presence.as_ref().filter(|presence| {
+                parse_timestamp(&presence.since).is_some_and(|since| self.passback_reset.is_none_or(|reset| now - since < reset * 60))
+            })
+        }
+
+        /// Refus d'une entrée dans une zone pleine ; une carte déjà comptée dans la zone n'en augmente pas l'occupation.
+        fn capacity_reason(&self, now: i64) -> Option<DenyReason> {
+            let already_inside = self.presence_at(now).is_some_and(|presence| presence.inside);
+            match (&self.zone, self.direction, self.max_occupancy) {
+                (Some(zone), Some(Direction::In), Some(max)) if !already_inside && self.occupancy >= max => Some(DenyReason::ZoneFull(zone.clone())),
+                _ => None,
+            }
+        }
+
+        /// Infraction à l'anti-retour : un passage dans le même sens que le précédent.
+        fn passback_reason(&self, now: i64) -> Option<DenyReason> {
+            let (Some(zone), Some(direction), Some(presence)) = (&self.zone, self.direction, self.presence_at(now)) else {
+                return None;
+            };
+            if self.passback == PassbackMode::Off {
+                return None;
+            }
+            match (direction, presence.inside) {
+                (Direction::In, true) => Some(DenyReason::AlreadyInside(zone.clone())),
+                (Direction::Out, false) => Some(DenyReason::AlreadyOutside(zone.clone())),
+                _ => None,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::schedule::schedule::{Clock, FixedClock, TimeWindow};
+
+        fn request(state: CardState, status: UserStatus, zone: Option<&str>, groups: Vec<GroupSchedule>) -> AccessRequest {
+            AccessRequest {
+                card: Some(Card { uid: String::from("0A0B"), state, ..Card::default() }),
+                user: Some(User { name: String::from("Jean Dupont"), status, ..User::default() }),
+                zone: zone.map(String::from),
+                groups,
+                ..AccessRequest::default()
+            }
+        }
+
+        #[test]
+        fn test_decide() {
+            let now = parse_timestamp("2024-06-14 10:00:00").unwrap();
+            let allowed = || vec![GroupSchedule { group: String::from("Informatique"), ..GroupSchedule::default() }];
+            assert_eq!(AccessRequest::default().decide(now), Decision::Denied(DenyReason::UnknownCard));
+            assert_eq!(request(CardState::Active, UserStatus::Active, None, Vec::new()).decide(now), Decision::Granted);
+            assert_eq!(request(CardState::Active, UserStatus::Active, Some("Serveurs"), allowed()).decide(now), Decision::Granted);
+
+            let denied = request(CardState::Active, UserStatus::Active, Some("Serveurs"), Vec::new()).decide(now);
+            assert_eq!(denied, Decision::Denied(DenyReason::NotAllowed(String::from("Serveurs"))));
+            assert_eq!(denied.result(), BadgeResult::Denied);
+            assert_eq!(denied.reason().as_deref(), Some("accès non autorisé à la zone Serveurs"));
+
+            // L'état de la carte prime sur celui de l'utilisateur et sur les règles
+            assert_eq!(request(CardState::Lost, UserStatus::Suspended, Some("Serveurs"), allowed()).decide(now), Decision::Denied(DenyReason::CardLost));
+            assert_eq!(request(CardState::Active, UserStatus::Suspended, None, Vec::new()).decide(now), Decision::Denied(DenyReason::UserSuspended));
+        }
+
+        #[test]
+        fn test_decide_visitor() {
+            let now = parse_timestamp("2024-06-14 10:00:00").unwrap();
+            let visit = Visit { visitor: String::from("Marie Curie"), host: Some(String::from("Jean Dupont")), valid_until: String::from("2024-06-14 18:00:00"), ..Visit::default() };
+            let request = AccessRequest { visitor_badge: true, visit: Some(visit), ..AccessRequest::default() };
+            assert_eq!(request.decide(now), Decision::Granted);
+            assert_eq!(request.holder(), "Marie Curie (visiteur de Jean Dupont)");
+            assert_eq!(request.decide(parse_timestamp("2024-06-14 18:00:00").unwrap()), Decision::Denied(DenyReason::VisitExpired));
+
+            // Dans une zone, le visiteur a les accès de son hôte
+            let zoned = AccessRequest { zone: Some(String::from("Serveurs")), ..request.clone() };
+            assert_eq!(zoned.decide(now), Decision::Denied(DenyReason::NotAllowed(String::from("Serveurs"))));
+
+            let returned = AccessRequest { visit: None, ..request };
+            assert_eq!(returned.decide(now), Decision::Denied(DenyReason::VisitorBadgeUnassigned));
+        }
+
+        #[test]
+        fn test_decide_passback() {
+            let now = parse_timestamp("2024-06-14 10:00:00").unwrap();
+            let allowed = vec![GroupSchedule { group: String::from("Informatique"), ..GroupSchedule::default() }];
+            let inside = Presence { card_uid: String::from("0A0B"), zone: String::from("Serveurs"), inside: true, since: String::from("2024-06-14 08:00:00"), ..Presence::default() };
+            let entry = AccessRequest {
+                direction: Some(Direction::In),
+                passback: PassbackMode::Hard,
+                presence: Some(inside.clone()),
+                ..request(CardState::Active, UserStatus::Active, Some("Serveurs"), allowed)
+            };
+            let violation = DenyReason::AlreadyInside(String::from("Serveurs"));
+            assert_eq!(entry.decide(now), Decision::Denied(violation.clone()));
+            assert_eq!(AccessRequest { direction: Some(Direction::Out), ..entry.clone() }.decide(now), Decision::Granted);
+            assert_eq!(AccessRequest { presence: None, ..entry.clone() }.decide(now), Decision::Granted);
+            assert_eq!(AccessRequest { passback: PassbackMode::Off, ..entry.clone() }.decide(now), Decision::Granted);
+
+            // En mode souple, le passage est accordé mais l'infraction est enregistrée
+            let soft = AccessRequest { passback: PassbackMode::Soft, ..entry.clone() }.decide(now);
+            assert_eq!(soft, Decision::Tolerated(violation));
+            assert!(soft.is_granted());
+            assert_eq!(soft.result(), BadgeResult::Granted);
+            assert_eq!(soft.reason().as_deref(), Some("anti-retour, carte déjà entrée dans la zone Serveurs"));
+
+            // La position est oubliée après le délai de remise à zéro
+            let timed = AccessRequest { passback_reset: Some(120), ..entry.clone() };
+            assert_eq!(timed.decide(now - 1), Decision::Denied(DenyReason::AlreadyInside(String::from("Serveurs"))));
+            assert_eq!(timed.decide(now), Decision::Granted);
+
+            let outside = Presence { inside: false, ..inside };
+            let exit = AccessRequest { direction: Some(Direction::Out), presence: Some(outside), ..entry };
+            assert_eq!(exit.decide(now), Decision::Denied(DenyReason::AlreadyOutside(String::from("Serveurs"))));
+        }
+
+        #[test]
+        fn test_decide_capacity() {
+            let now = parse_timestamp("2024-06-14 10:00:00").unwrap();
+            let allowed = vec![GroupSchedule { group: String::from("Informatique"), ..GroupSchedule::default() }];
+            let entry = AccessRequest {
+                direction: Some(Direction::In),
+                max_occupancy: Some(2),
+                occupancy: 2,
+                ..request(CardState::Active, UserStatus::Active, Some("Serveurs"), allowed)
+            };
+            assert_eq!(entry.decide(now), Decision::Denied(DenyReason::ZoneFull(String::from("Serveurs"))));
+            assert_eq!(AccessRequest { occupancy: 1, ..entry.clone() }.decide(now), Decision::Granted);
+            assert_eq!(AccessRequest { direction: Some(Direction::Out), ..entry.clone() }.decide(now), Decision::Granted);
+
+            // Une carte déjà comptée dans la zone n'en augmente pas l'occupation
+            let inside = Presence { inside: true, since: String::from("2024-06-14 08:00:00"), ..Presence::default() };
+            assert_eq!(AccessRequest { presence: Some(inside.clone()), ..entry.clone() }.decide(now), Decision::Granted);
+
+            // Une position oubliée après le délai de remise à zéro n'est plus comptée : la carte entre comme une autre
+            let forgotten = AccessRequest { presence: Some(inside), passback_reset: Some(60), ..entry };
+            assert_eq!(forgotten.decide(now), Decision::Denied(DenyReason::ZoneFull(String::from("Serveurs"))));
+        }
+
+        #[test]
+        fn test_decide_with_schedule() {
+            // Le vendredi 14 juin 2024, de 07:00 à 20:00
+            let window = TimeWindow { weekday: 4, start: 7 * 60, end: 20 * 60 };
+            let night = GroupSchedule { group: String::from("Nuit"), windows: vec![TimeWindow { weekday: 4, start: 20 * 60, end: 24 * 60 }], ..GroupSchedule::default() };
+            let day = GroupSchedule { group: String::from("Bureaux"), windows: vec![window], ..GroupSchedule::default() };
+            let request = request(CardState::Active, UserStatus::Active, Some("Bureaux"), vec![night, day]);
+
+            let clock = |timestamp: &str| FixedClock(parse_timestamp(timestamp).unwrap());
+            assert_eq!(request.decide(clock("2024-06-14 07:00:00").now()), Decision::Granted);
+            assert_eq!(request.decide(clock("2024-06-14 21:30:00").now()), Decision::Granted);
+            assert_eq!(request.decide(clock("2024-06-14 06:59:00").now()), Decision::Denied(DenyReason::OutsideSchedule(String::from("Bureaux"))));
+            assert_eq!(request.decide(clock("2024-06-15 10:00:00").now()), Decision::Denied(DenyReason::OutsideSchedule(String::from("Bureaux"))));
+        }
+    }
+}
@@ -0,0 +1,131 @@
+/// Module `audit` chaîne les entrées du journal des actions d'administration : chaque entrée porte l'empreinte
+/// de la précédente, si bien qu'une entrée modifiée ou supprimée rompt la chaîne.
+pub mod audit {
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+    use std::fmt;
+
+    /// Empreinte précédant la première entrée du journal.
+    pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+    /// Entrée du journal d'audit : qui a fait quoi, quand, avec l'état avant et après l'action.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, sqlx::FromRow)]
+    pub struct AuditEntry {
+        pub id: i64,
+        /// Date de l'action, en UTC au format `AAAA-MM-JJ HH:MM:SS`.
+        pub at: String,
+        /// Le compte système de l'administrateur.
+        pub actor: String,
+        /// La commande exécutée, par exemple `zone del`.
+        pub action: String,
+        /// L'objet de l'action : utilisateur, carte, zone, fichier...
+        pub target: String,
+        /// État avant et après l'action, en JSON.
+        pub before: Option<String>,
+        pub after: Option<String>,
+        /// Empreinte de l'entrée précédente, `GENESIS` pour la première.
+        pub prev_hash: String,
+        /// Empreinte SHA-256 de l'entrée, calculée par `digest`.
+        pub hash: String,
+    }
+
+    impl AuditEntry {
+        /// Calcule l'empreinte SHA-256 de l'entrée, en hexadécimal, à partir de tous ses champs et de l'empreinte précédente.
+        ///
+        /// Les champs sont encodés dans un tableau JSON, pour que deux entrées différentes ne puissent pas donner le même texte.
+        pub fn digest(&self) -> String {
+            let content = json!([self.id, self.at, self.actor, self.action, self.target, self.before, self.after, self.prev_hash]);
+            Sha256::digest(content.to_string().as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+        }
+    }
+
+    /// Anomalie relevée par la vérification du journal.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum AuditIssue {
+        /// Le contenu de l'entrée ne correspond plus à son empreinte.
+        Modified(i64),
+        /// L'entrée ne suit pas la précédente : une entrée a été supprimée, insérée ou modifiée avant elle.
+        Unchained(i64),
+    }
+
+    impl fmt::Display for AuditIssue {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                AuditIssue::Modified(id) => write!(f, "entrée {} modifiée", id),
+                AuditIssue::Unchained(id) => write!(f, "chaîne rompue avant l'entrée {} (entrée supprimée ou modifiée)", id),
+            }
+        }
+    }
+
+    /// Vérifie la chaîne des empreintes du journal.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Les entrées du journal, dans l'ordre de leur identifiant.
+    ///
+    /// # Retourne
+    ///
+    /// * `Vec<AuditIssue>` - Les anomalies relevées, vide si le journal est intact. La suppression des dernières entrées
+    ///   ne rompt pas la chaîne : elle se détecte en comparant l'empreinte finale à une copie conservée ailleurs.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// let issues = verify(&db.audit_entries().await?);
+    /// ```
+    pub fn verify(entries: &[AuditEntry]) -> Vec<AuditIssue> {
+        let mut issues = Vec::new();
+        let mut previous = (0, GENESIS);
+        for entry in entries {
+            if entry.prev_hash != previous.1 || entry.id != previous.0 + 1 {
+                issues.push(AuditIssue::Unchained(entry.id));
+            }
+            if entry.digest() != entry.hash {
+                issues.push(AuditIssue::Modified(entry.id));
+            }
+            previous = (entry.id, &entry.hash);
+        }
+        issues
+    }
+
+    /// Compte système de la personne qui exécute le programme, enregistré comme auteur des actions.
+    pub fn current_actor() -> String {
+        std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| String::from("inconnu"))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn chain(actions: &[&str]) -> Vec<AuditEntry> {
+            let mut entries: Vec<AuditEntry> = Vec::new();
+            for (action, id) in actions.iter().zip(1..) {
+                let prev_hash = entries.last().map_or(GENESIS.to_string(), |entry| entry.hash.clone());
+                let mut entry = AuditEntry { id, action: action.to_string(), target: String::from("Serveurs"), prev_hash, ..AuditEntry::default() };
+                entry.hash = entry.digest();
+                entries.push(entry);
+            }
+            entries
+        }
+
+        #[test]
+        fn test_verify() {
+            let entries = chain(&["zone add", "zone capacity", "zone del"]);
+            assert!(verify(&entries).is_empty());
+            assert_eq!(entries[0].hash.len(), 64);
+
+            let mut modified = entries.clone();
+            modified[1].after = Some(String::from("{\"max_occupancy\":100}"));
+            assert_eq!(verify(&modified), [AuditIssue::Modified(2)]);
+
+            // Une empreinte recalculée après modification rompt la chaîne avec l'entrée suivante
+            modified[1].hash = modified[1].digest();
+            assert_eq!(verify(&modified), [AuditIssue::Unchained(3)]);
+
+            let deleted = [entries[0].clone(), entries[2].clone()];
+            assert_eq!(verify(&deleted), [AuditIssue::Unchained(3)]);
+            assert_eq!(verify(&entries[1..]), [AuditIssue::Unchained(2)]);
+            assert_eq!(AuditIssue::Modified(2).to_string(), "entrée 2 modifiée");
+        }
+    }
+}
@@ -0,0 +1,321 @@
+/// Module `badge` surveille les lecteurs et produit un événement pour chaque carte présentée.
+pub mod badge {
+    use pcsc::Error;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::mpsc::Sender;
+    use tokio::task::{self, JoinHandle};
+    use tokio::time::{self, Duration};
+    use crate::card_operations::card_operations::{CardManager, ReaderMonitor};
+    use crate::config::config::{Config, FeedbackConfig, ReaderConfig};
+    use crate::access::access::{AccessRequest, Decision, DenyReason};
+    use crate::dbo::dbo::{DboManager, User};
+    use crate::pin::pin::{verify_pin, PinPad, LOCKOUT_MINUTES, MAX_FAILURES, PIN_TIMEOUT};
+    use crate::schedule::schedule::Clock;
+    use crate::timesheet::timesheet::{format_timestamp, Direction};
+    use crate::transport::transport::{open_trace, TracingTransport, Transport};
+    use crate::utils::utils::decimals_to_hex;
+
+    /// Intervalle entre deux lectures d'un lecteur.
+    pub const TIME: Duration = Duration::from_secs(3);
+
+    /// Délai d'attente d'un branchement ou débranchement de lecteur avant de vérifier l'arrêt du programme.
+    const PNP_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Délais minimal et maximal entre deux tentatives de reconnexion au service PC/SC.
+    const MIN_BACKOFF: Duration = Duration::from_secs(1);
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    /// Événement publié par la surveillance des lecteurs.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum ReaderEvent {
+        /// Une carte a été lue.
+        Badge(BadgeEvent),
+        /// Un lecteur a été branché et est désormais surveillé.
+        Connected(ReaderConfig),
+        /// Un lecteur a été débranché.
+        Disconnected(ReaderConfig),
+    }
+
+    /// Événement émis à chaque lecture d'une carte, étiqueté avec le lecteur d'origine.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct BadgeEvent {
+        pub reader: ReaderConfig,
+        pub uuid: String,
+        pub message: String,
+        pub decision: Decision,
+        /// La zone desservie par le lecteur, si le lecteur est une porte.
+        pub zone: Option<String>,
+    }
+
+    /// Surveille un lecteur et envoie un `BadgeEvent` à chaque lecture de carte.
+    ///
+    /// La LED et le buzzer du lecteur signalent un badge accepté ou refusé à chaque nouvelle carte,
+    /// et chaque nouvelle carte est enregistrée dans l'historique des passages. Un passage accordé sur une porte
+    /// d'entrée ou de sortie met à jour la position de la carte dans la zone, utilisée par l'anti-retour.
+    /// La tâche s'arrête lorsque le récepteur des événements est fermé.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - La configuration du lecteur à surveiller.
+    /// * `db` - La base de données des utilisateurs.
+    /// * `trace` - Le fichier de trace des APDU, si la trace est activée.
+    /// * `clock` - L'horloge du site, pour l'évaluation des horaires d'accès.
+    /// * `feedback` - Les motifs de la LED et du buzzer.
+    /// * `pinpad` - Le clavier de saisie du code PIN, pour un lecteur à double authentification.
+    /// * `tx` - Le canal sur lequel publier les événements.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// tokio::spawn(watch_reader(config.reader(&name), db.clone(), None, Arc::new(config.clock()), config.feedback, PinPad::default(), tx.clone()));
+    /// ```
+    pub async fn watch_reader(reader: ReaderConfig, db: DboManager, trace: Option<Arc<Mutex<File>>>, clock: Arc<dyn Clock>, feedback: FeedbackConfig, pinpad: PinPad, tx: Sender<ReaderEvent>) {
+        let mut interval = time::interval(TIME);
+        let mut buzzer_configured = false;
+        let mut last: Option<BadgeEvent> = None;
+        loop {
+            interval.tick().await;
+            let card = CardManager{card: match CardManager::connect(&reader.name) {
+                Ok(card) => TracingTransport::new(card, &reader.name, trace.clone()),
+                Err(_) => {
+                    last = None;
+                    continue;
+                }
+            }};
+
+            if !buzzer_configured {
+                buzzer_configured = card.detection_buzzer(false) == 1;
+            }
+
+            let event = match badge_card(&card, &db, &reader, clock.as_ref(), &feedback, Some(&pinpad), last.as_ref()).await {
+                Ok(event) => event,
+                // Carte retirée pendant la lecture : elle sera lue au prochain tour si elle est reposée
+                Err(e) => {
+                    eprintln!("Lecture de la carte impossible sur {}: {}", reader.name, e);
+                    continue;
+                }
+            };
+            if last.as_ref().map(|last| &last.uuid) != Some(&event.uuid) {
+                if let Err(e) = db.add_badge_event(&event.uuid, &reader.name, event.decision.result(), event.decision.reason().as_deref()).await {
+                    eprintln!("Impossible d'enregistrer le passage: {}", e);
+                }
+                if let (true, Some(zone), Some(direction)) = (event.decision.is_granted(), &event.zone, reader.role.direction()) {
+                    if let Err(e) = db.set_presence(&event.uuid, zone, direction == Direction::In, &format_timestamp(clock.now())).await {
+                        eprintln!("Impossible d'enregistrer la position de la carte: {}", e);
+                    }
+                }
+            }
+            last = Some(event.clone());
+
+            if tx.send(ReaderEvent::Badge(event)).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Lit l'UID de la carte présente, décide de l'accès à la zone du lecteur et signale la décision sur le lecteur.
+    ///
+    /// Sur un lecteur à double authentification, le code PIN du titulaire est demandé avant d'accorder l'accès,
+    /// une seule fois tant que la carte reste posée.
+    ///
+    /// # Arguments
+    ///
+    /// * `card` - La carte présente sur le lecteur.
+    /// * `db` - La base de données des utilisateurs.
+    /// * `reader` - La configuration du lecteur.
+    /// * `clock` - L'horloge du site, pour l'évaluation des horaires d'accès.
+    /// * `feedback` - Les motifs de la LED et du buzzer joués selon la décision.
+    /// * `pinpad` - Le clavier de saisie du code PIN ; sans clavier, le code n'est jamais saisi.
+    /// * `last` - L'événement du tour précédent ; la LED et le buzzer ne sont déclenchés que pour une nouvelle carte.
+    ///
+    /// # Retourne
+    ///
+    /// * `Result<BadgeEvent, Error>` - L'événement correspondant à la lecture ; une erreur si l'UID n'a pas pu être lu.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// let card = CardManager{card: ReplayTransport::load("badge_client.trace")?};
+    /// let event = badge_card(&card, &db, &reader, &config.clock(), &config.feedback, None, None).await?;
+    /// ```
+    pub async fn badge_card<T: Transport + Sync>(card: &CardManager<T>, db: &DboManager, reader: &ReaderConfig, clock: &dyn Clock, feedback: &FeedbackConfig, pinpad: Option<&PinPad>, last: Option<&BadgeEvent>) -> Result<BadgeEvent, Error> {
+        let carduuid = decimals_to_hex(card.read(0)?);
+        let last = last.filter(|last| last.uuid == carduuid);
+        if let Some(last) = last.filter(|_| reader.two_factor) {
+            return Ok(last.clone());
+        }
+
+        let request = AccessRequest::load(db, &carduuid, reader).await.unwrap_or_else(|e| {
+            eprintln!("Impossible de vérifier l'accès: {}", e);
+            AccessRequest::default()
+        });
+        let mut decision = request.decide(clock.now());
+        if reader.two_factor && decision.is_granted() {
+            let reason = second_factor(db, request.user.as_ref(), reader, pinpad).await.unwrap_or_else(|e| {
+                eprintln!("Impossible de vérifier le code PIN: {}", e);
+                Some(DenyReason::PinNotSet)
+            });
+            if let Some(reason) = reason {
+                decision = Decision::Denied(reason);
+            }
+        }
+        let holder = request.holder();
+        let message = match &decision {
+            Decision::Granted => format!("Bienvenue {} !", holder),
+            Decision::Tolerated(reason) => format!("Bienvenue {} ! ALERTE : {}", holder, reason),
+            // Une carte perdue ou volée qui réapparaît est signalée
+            Decision::Denied(reason @ (DenyReason::CardLost | DenyReason::CardStolen)) => format!("ALERTE : {} présentée (titulaire: {})", reason, holder),
+            Decision::Denied(DenyReason::UnknownCard) => String::from("Carte non configuré"),
+            Decision::Denied(reason) => format!("Accès refusé, {}", reason),
+        };
+
+        if last.is_none() {
+            card.signal(feedback.pattern(decision.is_granted()));
+        }
+
+        Ok(BadgeEvent { reader: reader.clone(), uuid: carduuid, message, decision, zone: request.zone })
+    }
+
+    /// Demande et vérifie le code PIN du titulaire d'une carte ; les échecs sont enregistrés et, trop nombreux, bloquent le code.
+    ///
+    /// # Retourne
+    ///
+    /// * `Result<Option<DenyReason>, sqlx::Error>` - La raison du refus, `None` si le code est correct.
+    async fn second_factor(db: &DboManager, user: Option<&User>, reader: &ReaderConfig, pinpad: Option<&PinPad>) -> Result<Option<DenyReason>, sqlx::Error> {
+        // Un badge visiteur n'a pas de titulaire, donc pas de code PIN
+        let Some(user) = user else {
+            return Ok(Some(DenyReason::PinNotSet));
+        };
+        let Some(hash) = db.pin_hash(user.id).await? else {
+            return Ok(Some(DenyReason::PinNotSet));
+        };
+        if db.pin_failures(user.id, LOCKOUT_MINUTES).await? >= MAX_FAILURES {
+            return Ok(Some(DenyReason::PinLocked));
+        }
+
+        let prompt = format!("[{}] Code PIN de {} ({} s) :", reader.name, user.name, PIN_TIMEOUT.as_secs());
+        let Some(pin) = (match pinpad {
+            Some(pinpad) => pinpad.read(&prompt, PIN_TIMEOUT).await,
+            None => None,
+        }) else {
+            return Ok(Some(DenyReason::PinTimeout));
+        };
+        if verify_pin(pin.trim(), &hash) {
+            db.clear_pin_failures(user.id).await?;
+            Ok(None)
+        } else {
+            db.add_pin_failure(user.id, &reader.name).await?;
+            Ok(Some(DenyReason::WrongPin))
+        }
+    }
+
+    /// Surveille le branchement et le débranchement des lecteurs et lance une tâche `watch_reader` par lecteur.
+    ///
+    /// Chaque changement est publié sous forme de `ReaderEvent::Connected` ou `ReaderEvent::Disconnected`.
+    /// Si le service PC/SC devient indisponible, la surveillance est relancée avec un délai croissant.
+    /// L'attente des notifications PC/SC se fait hors du runtime Tokio pour ne pas bloquer la ligne de commande.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configuration des lecteurs.
+    /// * `db` - La base de données partagée par les tâches des lecteurs.
+    /// * `pinpad` - Le clavier de saisie du code PIN, partagé par les lecteurs à double authentification.
+    /// * `tx` - Le canal sur lequel publier les événements.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// tokio::spawn(monitor_readers(config.clone(), db.clone(), pinpad.clone(), tx));
+    /// ```
+    pub async fn monitor_readers(config: Config, db: DboManager, pinpad: PinPad, tx: Sender<ReaderEvent>) {
+        let mut watchers: HashMap<String, (ReaderConfig, JoinHandle<()>)> = HashMap::new();
+        let mut backoff = MIN_BACKOFF;
+        let trace = config.trace.as_deref().and_then(|path| match open_trace(path) {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                eprintln!("Impossible d'ouvrir la trace {}: {}", path, e);
+                None
+            }
+        });
+        let shared = Shared { db, trace, clock: Arc::new(config.clock()), feedback: config.feedback, pinpad };
+
+        while !tx.is_closed() {
+            let mut monitor = match ReaderMonitor::new() {
+                Ok(monitor) => monitor,
+                Err(e) => {
+                    eprintln!("Service PC/SC indisponible, nouvelle tentative dans {:?}: {}", backoff, e);
+                    sync_watchers(&config, &shared, &mut watchers, &[], &tx).await;
+                    time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            };
+
+            loop {
+                let names = match CardManager::list_readers() {
+                    Ok(names) => names,
+                    Err(Error::NoReadersAvailable) => Vec::new(),
+                    Err(e) => {
+                        eprintln!("Liste des lecteurs indisponible: {}", e);
+                        break;
+                    }
+                };
+                sync_watchers(&config, &shared, &mut watchers, &names, &tx).await;
+
+                let mut changed = Ok(false);
+                while let Ok(false) = changed {
+                    if tx.is_closed() {
+                        return;
+                    }
+                    let waiting = task::spawn_blocking(move || {
+                        let changed = monitor.wait(PNP_TIMEOUT);
+                        (monitor, changed)
+                    });
+                    (monitor, changed) = match waiting.await {
+                        Ok(result) => result,
+                        Err(_) => return,
+                    };
+                }
+                if let Err(e) = changed {
+                    eprintln!("Surveillance des lecteurs interrompue: {}", e);
+                    break;
+                }
+                backoff = MIN_BACKOFF;
+            }
+
+            time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Ressources partagées par les tâches de surveillance des lecteurs.
+    struct Shared {
+        db: DboManager,
+        trace: Option<Arc<Mutex<File>>>,
+        clock: Arc<dyn Clock>,
+        feedback: FeedbackConfig,
+        pinpad: PinPad,
+    }
+
+    /// Lance une tâche pour chaque nouveau lecteur et arrête celles des lecteurs débranchés.
+    async fn sync_watchers(config: &Config, shared: &Shared, watchers: &mut HashMap<String, (ReaderConfig, JoinHandle<()>)>, names: &[String], tx: &Sender<ReaderEvent>) {
+        let removed: Vec<String> = watchers.keys().filter(|name| !names.contains(name)).cloned().collect();
+        for name in removed {
+            if let Some((reader, watcher)) = watchers.remove(&name) {
+                watcher.abort();
+                let _ = tx.send(ReaderEvent::Disconnected(reader)).await;
+            }
+        }
+
+        for name in names {
+            if !watchers.contains_key(name) {
+                let reader = config.reader(name);
+                let watcher = tokio::spawn(watch_reader(reader.clone(), shared.db.clone(), shared.trace.clone(), shared.clock.clone(), shared.feedback, shared.pinpad.clone(), tx.clone()));
+                watchers.insert(name.clone(), (reader.clone(), watcher));
+                let _ = tx.send(ReaderEvent::Connected(reader)).await;
+            }
+        }
+    }
+}
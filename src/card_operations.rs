@@ -2,10 +2,18 @@
 /// à l'aide de la bibliothèque PCSC.
 pub mod card_operations {
     use pcsc::*;
+    use serde::{Deserialize, Serialize};
+    use std::ffi::CString;
+    use std::time::Duration;
+    use crate::emulation::emulation::Type4Tag;
+    use crate::pn532::pn532::Pn532;
+    use crate::transport::transport::Transport;
 
     /// Structure `CardManager` gère les opérations sur une carte.
-    pub struct CardManager {
-        pub card: Card,
+    ///
+    /// Les APDU transitent par un `Transport`, par défaut la connexion PC/SC à la carte.
+    pub struct CardManager<T: Transport = Card> {
+        pub card: T,
     }
 
     impl CardManager {
@@ -21,7 +29,7 @@ pub mod card_operations {
         /// ```
         /// let card = CardManager::loadreader()?;
         /// ```
-
+        #[cfg(test)]
         pub fn loadreader() -> Result<Card, Error> {
             let ctx = Context::establish(Scope::User).expect("Etablissement du context échoué");
 
@@ -45,6 +53,51 @@ pub mod card_operations {
             }
         }
 
+        /// Liste les noms des lecteurs connectés, tels que rapportés par PC/SC.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Vec<String>, Error>` - Les noms des lecteurs disponibles.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let readers = CardManager::list_readers()?;
+        /// ```
+        pub fn list_readers() -> Result<Vec<String>, Error> {
+            let ctx = Context::establish(Scope::User)?;
+
+            let mut readers_buf = [0; 2048];
+            let readers = ctx.list_readers(&mut readers_buf)?;
+            Ok(readers.map(|reader| reader.to_string_lossy().into_owned()).collect())
+        }
+
+        /// Se connecte à la carte posée sur un lecteur donné.
+        ///
+        /// # Arguments
+        ///
+        /// * `reader` - Le nom PC/SC du lecteur.
+        ///
+        /// # Retourne
+        ///
+        /// * `Ok(Card)` - Si la connexion est réussie.
+        /// * `Err(Error)` - Si le lecteur est inconnu ou qu'aucune carte n'est présente.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let card = CardManager::connect("ACS ACR122U PICC Interface 00 00")?;
+        /// ```
+        pub fn connect(reader: &str) -> Result<Card, Error> {
+            let ctx = Context::establish(Scope::User)?;
+            let reader = CString::new(reader).map_err(|_| Error::UnknownReader)?;
+
+            ctx.connect(&reader, ShareMode::Shared, Protocols::ANY)
+        }
+
+    }
+
+    impl<T: Transport> CardManager<T> {
         /// Charge une clé dans la carte.
         ///
         /// # Arguments
@@ -70,22 +123,17 @@ pub mod card_operations {
                 key[0], key[1], key[2], key[3], key[4], key[5] // Key A
             ];
 
-            let mut rapdu = [0; 256];
-             match self.card.transmit(&load_key_apdu, &mut rapdu) {
-                Ok(_v) => {
-                    return 1;
+            match self.card.exchange(&load_key_apdu) {
+                Ok(response) if response.starts_with(&[0x90, 0x00]) => 1,
+                Ok(response) => {
+                    eprintln!("Chargement des clés échouées, code: {:02X?}", response);
+                    0
                 }
                 Err(e) => {
                     println!("Erreur lors de la transmission: {:?}", e);
+                    0
                 }
-             }
-            let status_word = &rapdu[..2];
-            if status_word != [0x90, 0x00] {
-                eprintln!("Chargement des clés échouées, code: {:02X?}", status_word);
-                return 0;
             }
-
-            return 1;
         }
 
         /// Authentifie la carte pour un bloc spécifique.
@@ -96,14 +144,14 @@ pub mod card_operations {
         ///
         /// # Retourne
         ///
-        /// * `u8` - 1 si l'authentification réussit, 0 sinon.
+        /// * `Result<u8, Error>` - 1 si l'authentification réussit, 0 si la carte la refuse ; une erreur si l'échange échoue.
         ///
         /// # Exemples
         ///
         /// ```
-        /// card_manager.auth(4);
+        /// card_manager.auth(4)?;
         /// ```
-        pub fn auth(&self, block: u8) -> u8 {
+        pub fn auth(&self, block: u8) -> Result<u8, Error> {
             let auth_apdu = [
                 0xFF, // Class
                 0x86, // INS: General Authenticate
@@ -116,17 +164,15 @@ pub mod card_operations {
                 0x60, // Key type (A)
                 0x00, // Key number (0 for loaded key)
             ];
-            let mut rapdu = [0; 256];
-            self.card.transmit(&auth_apdu, &mut rapdu).expect("Authentification de la carte échouée");
+            let rapdu = self.card.exchange(&auth_apdu)?;
 
-            let status_word = rapdu[0];
+            let status_word = rapdu.first().copied().unwrap_or_default();
             if status_word != 0x90 {
                 eprintln!("Authentification échouée, code: {:02X?}", status_word);
-                return 0;
+                return Ok(0);
             }
 
-            return 1;
-
+            Ok(1)
         }
 
         /// Lit les données d'un bloc spécifique.
@@ -137,17 +183,17 @@ pub mod card_operations {
         ///
         /// # Retourne
         ///
-        /// * `Vec<u8>` - Les données lues du bloc.
+        /// * `Result<Vec<u8>, Error>` - Les données lues du bloc ; une erreur si la carte est retirée pendant la lecture.
         ///
         /// # Exemples
         ///
         /// ```
-        /// let data = card_manager.read(4);
+        /// let data = card_manager.read(4)?;
         /// ```
-        pub fn read(&self, block: u8) -> Vec<u8> {
+        pub fn read(&self, block: u8) -> Result<Vec<u8>, Error> {
             self.keyload([0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
 
-            self.auth(block);
+            self.auth(block)?;
 
             let read_apdu = [
                 0xFF, // Class
@@ -157,14 +203,10 @@ pub mod card_operations {
                 0x10  // Le: Number of bytes to read (16 bytes for a block)
             ];
 
-            let mut rapdu = [0; 256];
-            self.card.transmit(&read_apdu, &mut rapdu).expect("Failed to transmit read APDU");
+            let mut rapdu = self.card.exchange(&read_apdu)?;
 
-            if rapdu[16] != 0x90 {
-                Vec::from(&rapdu[..16])
-            } else {
-                Vec::from(&rapdu[..16])
-            }
+            rapdu.resize(16, 0);
+            Ok(rapdu)
         }
 
         /// Écrit des données dans un bloc spécifique.
@@ -195,10 +237,9 @@ pub mod card_operations {
                 data[8], data[9], data[10], data[11], data[12], data[13], data[14], data[15]
             ];
 
-            let mut rapdu = [0; 256];
-            self.card.transmit(&write_apdu, &mut rapdu).expect("Failed to transmit write APDU");
+            let rapdu = self.card.exchange(&write_apdu).expect("Failed to transmit write APDU");
 
-            if rapdu[16] != 0x90 {
+            if rapdu.starts_with(&[0x90, 0x00]) {
                 println!("Success")
             } else {
                 println!("Failed")
@@ -214,23 +255,23 @@ pub mod card_operations {
         ///
         /// # Retourne
         ///
-        /// * `Vec<Vec<u8>>` - Les données lues du secteur.
+        /// * `Result<Vec<Vec<u8>>, Error>` - Les données lues du secteur.
         ///
         /// # Exemples
         ///
         /// ```
-        /// let data = card_manager.read_sector(1);
+        /// let data = card_manager.read_sector(1)?;
         /// ```
-        pub fn _read_sectors(&self, sector: u8) -> Vec<Vec<u8>> {
+        pub fn _read_sectors(&self, sector: u8) -> Result<Vec<Vec<u8>>, Error> {
             let mut blocks = Vec::new();
             let start_block = sector * 4;
 
             for block_offset in 0..3 {
                 let block = start_block + block_offset;
-                blocks.push(self.read(block));
+                blocks.push(self.read(block)?);
             }
 
-            blocks
+            Ok(blocks)
         }
 
         /// Écrit des données dans un secteur spécifique.
@@ -254,15 +295,485 @@ pub mod card_operations {
                 self._write(block, *block_data);
             }
         }
+
+        /// Pilote la LED bicolore et le buzzer du lecteur ACR122U.
+        ///
+        /// # Arguments
+        ///
+        /// * `pattern` - Le motif de clignotement et de bip à jouer.
+        ///
+        /// # Retourne
+        ///
+        /// * `u8` - 1 si le lecteur a accepté la commande, 0 sinon.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// card_manager.signal(&LedBuzzerPattern::GRANTED);
+        /// ```
+        pub fn signal(&self, pattern: &LedBuzzerPattern) -> u8 {
+            match self.card.exchange(&pattern.apdu()).as_deref() {
+                Ok([0x90, ..]) => 1,
+                Ok(response) => {
+                    eprintln!("Commande LED/buzzer refusée, code: {:02X?}", response);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Erreur lors de la transmission: {:?}", e);
+                    0
+                }
+            }
+        }
+
+        /// Active ou désactive le bip émis par le lecteur à chaque détection de carte.
+        ///
+        /// # Arguments
+        ///
+        /// * `enabled` - `true` pour garder le bip par défaut, `false` pour le couper.
+        ///
+        /// # Retourne
+        ///
+        /// * `u8` - 1 si le lecteur a accepté la commande, 0 sinon.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// card_manager.detection_buzzer(false);
+        /// ```
+        pub fn detection_buzzer(&self, enabled: bool) -> u8 {
+            let buzzer_apdu = [
+                0xFF, // Class
+                0x00, // INS
+                0x52, // P1: Set buzzer output during card detection
+                if enabled { 0xFF } else { 0x00 }, // P2: Buzzer on/off
+                0x00, // Le
+            ];
+
+            match self.card.exchange(&buzzer_apdu).as_deref() {
+                Ok([0x90, 0x00, ..]) => 1,
+                Ok(response) => {
+                    eprintln!("Configuration du buzzer échouée, code: {:02X?}", response);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Erreur lors de la transmission: {:?}", e);
+                    0
+                }
+            }
+        }
+    }
+
+    /// Octets de l'UID annoncé par le lecteur lorsqu'il émule une étiquette.
+    const EMULATION_NFCID: [u8; 3] = [0x42, 0x41, 0x44];
+
+    impl<T: Transport> CardManager<T> {
+        /// Fait passer le lecteur pour une étiquette NFC de type 4 le temps d'une session.
+        ///
+        /// Le PN532 attend qu'un téléphone s'approche, puis chaque APDU reçue est traitée par `tag`
+        /// jusqu'à ce que le téléphone quitte le champ. Le transport doit pouvoir joindre le PN532
+        /// sans carte posée, par exemple un `ReaderManager`.
+        ///
+        /// # Arguments
+        ///
+        /// * `tag` - L'étiquette émulée et son message NDEF.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<bool, Error>` - `true` si le message NDEF a été lu en entier.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let card = CardManager{card: ReaderManager{reader: ReaderManager::loadreader()?}};
+        /// card.emulate(&mut Type4Tag::new(&message))?;
+        /// ```
+        pub fn emulate(&self, tag: &mut Type4Tag) -> Result<bool, Error> {
+            let pn532 = Pn532::new(&self.card);
+            pn532.tg_init_as_target(EMULATION_NFCID)?;
+
+            loop {
+                let capdu = match pn532.tg_get_data() {
+                    Ok(capdu) => capdu,
+                    Err(Error::RemovedCard) => break,
+                    Err(e) => return Err(e),
+                };
+                match pn532.tg_set_data(&tag.process(&capdu)) {
+                    Ok(()) => {}
+                    Err(Error::RemovedCard) => break,
+                    Err(e) => return Err(e),
+                }
+            }
+
+            Ok(tag.ndef_read())
+        }
+    }
+
+    /// Délai laissé à un téléphone pour s'approcher en émulation, en unités de 5 secondes.
+    pub const EMULATION_TIMEOUT: u8 = 12;
+
+    /// Délai d'attente du lecteur rétabli après l'émulation : attente de la réponse du PN532 sans limite.
+    /// Le lecteur ne permet pas de relire le délai en place.
+    const WAIT_FOREVER: u8 = 0xFF;
+
+    impl CardManager<ReaderManager> {
+        /// Émule une étiquette NFC le temps d'une session, avec un délai d'attente du téléphone.
+        ///
+        /// Le polling automatique, qui empêche le PN532 de passer en mode cible, est suspendu pendant la session.
+        /// Les paramètres PICC et le délai d'attente du lecteur sont rétablis dans tous les cas, y compris lorsque
+        /// aucun téléphone ne s'approche avant la fin du délai.
+        ///
+        /// # Arguments
+        ///
+        /// * `tag` - L'étiquette émulée et son message NDEF.
+        /// * `timeout` - Le délai d'attente, en unités de 5 secondes.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<bool, Error>` - `true` si le message NDEF a été lu en entier, une erreur si le délai expire.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let card = CardManager{card: ReaderManager{reader: ReaderManager::loadreader(&name)?}};
+        /// card.emulate_for(&mut Type4Tag::new(&message), EMULATION_TIMEOUT)?;
+        /// ```
+        pub fn emulate_for(&self, tag: &mut Type4Tag, timeout: u8) -> Result<bool, Error> {
+            let parameters = self.card.picc_parameters()?;
+            let result = self.card
+                .set_picc_parameters(PiccParameters { auto_polling: false, ..parameters })
+                .and_then(|_| self.card.set_timeout(timeout))
+                .and_then(|()| self.emulate(tag));
+
+            let restored = self.card.set_timeout(WAIT_FOREVER).and(self.card.set_picc_parameters(parameters));
+            if let Err(e) = &restored {
+                eprintln!("Impossible de rétablir les paramètres du lecteur: {}", e);
+            }
+            result
+        }
+    }
+
+    /// Code de contrôle de l'IOCTL "escape" de l'ACR122U, utilisé pour parler au lecteur sans carte.
+    const IOCTL_CCID_ESCAPE: u32 = 3500;
+
+    /// Structure `ReaderManager` gère les opérations propres au lecteur, indépendamment de toute carte.
+    pub struct ReaderManager {
+        pub reader: Card,
+    }
+
+    impl ReaderManager {
+        /// Se connecte à un lecteur en mode direct, sans exiger la présence d'une carte.
+        ///
+        /// # Arguments
+        ///
+        /// * `reader` - Le nom PC/SC du lecteur.
+        ///
+        /// # Retourne
+        ///
+        /// * `Ok(Card)` - La connexion directe au lecteur.
+        /// * `Err(Error)` - Si le lecteur est inconnu ou si la connexion échoue.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let reader = ReaderManager{reader: ReaderManager::loadreader("ACS ACR122U PICC Interface 00 00")?};
+        /// ```
+        pub fn loadreader(reader: &str) -> Result<Card, Error> {
+            let ctx = Context::establish(Scope::User)?;
+            let reader = CString::new(reader).map_err(|_| Error::UnknownReader)?;
+
+            ctx.connect(&reader, ShareMode::Direct, Protocols::UNDEFINED)
+        }
+
+        /// Envoie une pseudo-APDU au lecteur via l'IOCTL "escape".
+        ///
+        /// # Arguments
+        ///
+        /// * `apdu` - La pseudo-APDU à transmettre.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Vec<u8>, Error>` - La réponse brute du lecteur.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let response = reader_manager.escape(&[0xFF, 0x00, 0x48, 0x00, 0x00])?;
+        /// ```
+        pub fn escape(&self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+            let mut rapdu = [0; 256];
+            let response = self.reader.control(ctl_code(IOCTL_CCID_ESCAPE.into()), apdu, &mut rapdu)?;
+            Ok(response.to_vec())
+        }
+
+        /// Envoie une pseudo-APDU et vérifie que le lecteur répond par le statut `90`.
+        fn command(&self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+            let response = self.escape(apdu)?;
+            match response.as_slice() {
+                [.., 0x90, _] => Ok(response),
+                _ => {
+                    eprintln!("Commande lecteur refusée, code: {:02X?}", response);
+                    Err(Error::UnknownError)
+                }
+            }
+        }
+
+        /// Lit la version du firmware du lecteur (par exemple `ACR122U215`).
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<String, Error>` - La version du firmware.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let version = reader_manager.firmware_version()?;
+        /// ```
+        pub fn firmware_version(&self) -> Result<String, Error> {
+            let response = self.escape(&[0xFF, 0x00, 0x48, 0x00, 0x00])?;
+            Ok(String::from_utf8_lossy(&response).trim_end_matches('\0').to_string())
+        }
+
+        /// Lit les paramètres de fonctionnement PICC du lecteur.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<PiccParameters, Error>` - Les paramètres actuellement appliqués.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let parameters = reader_manager.picc_parameters()?;
+        /// ```
+        pub fn picc_parameters(&self) -> Result<PiccParameters, Error> {
+            let response = self.command(&[0xFF, 0x00, 0x50, 0x00, 0x00])?;
+            Ok(PiccParameters::from_byte(response[response.len() - 1]))
+        }
+
+        /// Modifie les paramètres de fonctionnement PICC du lecteur.
+        ///
+        /// # Arguments
+        ///
+        /// * `parameters` - Les nouveaux paramètres (polling automatique, types de cartes, intervalle).
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<PiccParameters, Error>` - Les paramètres effectivement appliqués par le lecteur.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// reader_manager.set_picc_parameters(PiccParameters::default())?;
+        /// ```
+        pub fn set_picc_parameters(&self, parameters: PiccParameters) -> Result<PiccParameters, Error> {
+            let response = self.command(&[0xFF, 0x00, 0x51, parameters.to_byte(), 0x00])?;
+            Ok(PiccParameters::from_byte(response[response.len() - 1]))
+        }
+
+        /// Allume ou éteint l'antenne du lecteur (commande PN532 RFConfiguration).
+        ///
+        /// # Arguments
+        ///
+        /// * `on` - `true` pour allumer le champ RF, `false` pour le couper.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// reader_manager.antenna(false)?;
+        /// ```
+        pub fn antenna(&self, on: bool) -> Result<(), Error> {
+            Pn532::new(self).rf_configuration(0x01, &[on as u8])
+        }
+
+        /// Règle le délai d'attente de réponse du lecteur.
+        ///
+        /// # Arguments
+        ///
+        /// * `timeout` - Le délai en unités de 5 secondes, `0x00` pour aucun contrôle et `0xFF` pour attendre indéfiniment.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// reader_manager.set_timeout(0x01)?;
+        /// ```
+        pub fn set_timeout(&self, timeout: u8) -> Result<(), Error> {
+            self.command(&[0xFF, 0x00, 0x41, timeout, 0x00])?;
+            Ok(())
+        }
+    }
+
+    impl Transport for ReaderManager {
+        /// Les APDU passent par l'IOCTL "escape", ce qui permet de piloter le PN532 sans carte posée.
+        fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+            self.escape(apdu)
+        }
+    }
+
+    /// Structure `ReaderMonitor` détecte le branchement et le débranchement des lecteurs
+    /// grâce au lecteur spécial PC/SC `\\?PnP?\Notification`.
+    pub struct ReaderMonitor {
+        ctx: Context,
+        pnp: [ReaderState; 1],
+    }
+
+    impl ReaderMonitor {
+        /// Établit le contexte PC/SC utilisé pour la surveillance.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<ReaderMonitor, Error>` - Le moniteur, ou une erreur si le service PC/SC est indisponible.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let mut monitor = ReaderMonitor::new()?;
+        /// ```
+        pub fn new() -> Result<ReaderMonitor, Error> {
+            Ok(ReaderMonitor {
+                ctx: Context::establish(Scope::User)?,
+                pnp: [ReaderState::new(PNP_NOTIFICATION(), State::UNAWARE)],
+            })
+        }
+
+        /// Attend un changement dans la liste des lecteurs.
+        ///
+        /// # Arguments
+        ///
+        /// * `timeout` - Le délai d'attente maximal.
+        ///
+        /// # Retourne
+        ///
+        /// * `Ok(true)` - Si un lecteur a été branché ou débranché.
+        /// * `Ok(false)` - Si le délai a expiré sans changement.
+        /// * `Err(Error)` - Si le service PC/SC ne répond plus.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// if monitor.wait(Duration::from_secs(1))? { /* relister les lecteurs */ }
+        /// ```
+        pub fn wait(&mut self, timeout: Duration) -> Result<bool, Error> {
+            match self.ctx.get_status_change(timeout, &mut self.pnp) {
+                Ok(()) => {
+                    self.pnp[0].sync_current_state();
+                    Ok(true)
+                }
+                Err(Error::Timeout) => Ok(false),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    /// Paramètres de fonctionnement PICC de l'ACR122U (octet de la commande `FF 00 51`).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct PiccParameters {
+        /// Détection automatique des cartes par le lecteur.
+        pub auto_polling: bool,
+        /// Envoi automatique de la requête ATS aux cartes ISO 14443-4.
+        pub auto_ats: bool,
+        /// Intervalle de polling : 250 ms si `true`, 500 ms sinon.
+        pub short_polling_interval: bool,
+        pub felica_424: bool,
+        pub felica_212: bool,
+        pub topaz: bool,
+        pub iso14443_b: bool,
+        pub iso14443_a: bool,
+    }
+
+    impl PiccParameters {
+        /// Décode l'octet de paramètres renvoyé par le lecteur.
+        pub fn from_byte(byte: u8) -> PiccParameters {
+            PiccParameters {
+                auto_polling: byte & 0x80 != 0,
+                auto_ats: byte & 0x40 != 0,
+                short_polling_interval: byte & 0x20 != 0,
+                felica_424: byte & 0x10 != 0,
+                felica_212: byte & 0x08 != 0,
+                topaz: byte & 0x04 != 0,
+                iso14443_b: byte & 0x02 != 0,
+                iso14443_a: byte & 0x01 != 0,
+            }
+        }
+
+        /// Encode les paramètres dans l'octet attendu par le lecteur.
+        pub fn to_byte(self) -> u8 {
+            (self.auto_polling as u8) << 7
+                | (self.auto_ats as u8) << 6
+                | (self.short_polling_interval as u8) << 5
+                | (self.felica_424 as u8) << 4
+                | (self.felica_212 as u8) << 3
+                | (self.topaz as u8) << 2
+                | (self.iso14443_b as u8) << 1
+                | self.iso14443_a as u8
+        }
+    }
+
+    impl Default for PiccParameters {
+        /// Valeur d'usine de l'ACR122U (`0xFF`).
+        fn default() -> Self {
+            PiccParameters::from_byte(0xFF)
+        }
+    }
+
+    /// Motif joué par la LED bicolore et le buzzer de l'ACR122U.
+    ///
+    /// Les durées `t1` et `t2` sont exprimées en unités de 100 ms, `t1` correspondant à l'état
+    /// initial du clignotement et `t2` à l'état inverse.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct LedBuzzerPattern {
+        /// Octet P2 de contrôle des LED (masques et états rouge/vert).
+        pub led_state: u8,
+        /// Durée de l'état initial, en unités de 100 ms.
+        pub t1: u8,
+        /// Durée de l'état inverse, en unités de 100 ms.
+        pub t2: u8,
+        /// Nombre de répétitions du cycle.
+        pub repetitions: u8,
+        /// Liaison du buzzer : 0 muet, 1 pendant `t1`, 2 pendant `t2`, 3 pendant les deux.
+        pub buzzer: u8,
+    }
+
+    impl LedBuzzerPattern {
+        /// Badge accepté : clignotement vert et bip court.
+        pub const GRANTED: LedBuzzerPattern = LedBuzzerPattern {
+            led_state: 0xA8, // Clignotement vert, LED verte éteinte à la fin
+            t1: 2,
+            t2: 1,
+            repetitions: 1,
+            buzzer: 0x01,
+        };
+
+        /// Carte inconnue : clignotement rouge et bip long.
+        pub const DENIED: LedBuzzerPattern = LedBuzzerPattern {
+            led_state: 0x55, // Clignotement rouge, LED rouge allumée à la fin
+            t1: 10,
+            t2: 1,
+            repetitions: 1,
+            buzzer: 0x01,
+        };
+
+        /// Construit la pseudo-APDU "Bi-Color LED and Buzzer Control" correspondant au motif.
+        pub fn apdu(&self) -> [u8; 9] {
+            [
+                0xFF, // Class
+                0x00, // INS
+                0x40, // P1: LED and buzzer control
+                self.led_state, // P2: LED state control
+                0x04, // Lc
+                self.t1,
+                self.t2,
+                self.repetitions,
+                self.buzzer,
+            ]
+        }
     }
 
     #[cfg(test)]
     mod test {
         use super::*;
+        use crate::transport::transport::ReplayTransport;
 
         fn create_card() -> CardManager{
-        let card = CardManager{card: CardManager::loadreader().unwrap()};
-        card
+        CardManager{card: CardManager::loadreader().unwrap()}
         }
 
 
@@ -280,6 +791,52 @@ pub mod card_operations {
             let res = card.keyload([0x0f, 0xff, 0xef, 0xef, 0xaf, 0xff]);
             assert_eq!(res, 0);
         }
+
+        #[test]
+        fn test_picc_parameters_roundtrip() {
+            let parameters = PiccParameters::from_byte(0x8F);
+            assert!(parameters.auto_polling);
+            assert!(!parameters.auto_ats);
+            assert!(!parameters.short_polling_interval);
+            assert!(parameters.iso14443_a);
+            assert!(!parameters.felica_424);
+            assert_eq!(parameters.to_byte(), 0x8F);
+        }
+
+        #[test]
+        fn test_led_buzzer_apdu() {
+            let pattern = LedBuzzerPattern { led_state: 0x0F, t1: 3, t2: 4, repetitions: 2, buzzer: 0x03 };
+            assert_eq!(pattern.apdu(), [0xFF, 0x00, 0x40, 0x0F, 0x04, 0x03, 0x04, 0x02, 0x03]);
+        }
+
+        #[test]
+        fn test_read_replayed_trace() {
+            let trace = "\
+                1718000000000\tACS ACR122U PICC Interface 00\t>\tFF82000006FFFFFFFFFFFF\n\
+                1718000000004\tACS ACR122U PICC Interface 00\t<\t9000\n\
+                1718000000005\tACS ACR122U PICC Interface 00\t>\tFF860000050100006000\n\
+                1718000000012\tACS ACR122U PICC Interface 00\t<\t9000\n\
+                1718000000013\tACS ACR122U PICC Interface 00\t>\tFFB0000010\n\
+                1718000000020\tACS ACR122U PICC Interface 00\t<\tA4504FA11A08040062636465666768699000\n";
+            let card = CardManager{card: ReplayTransport::parse(trace).unwrap()};
+            assert_eq!(card.read(0).unwrap(), [0xA4, 0x50, 0x4F, 0xA1, 0x1A, 0x08, 0x04, 0x00, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69]);
+            assert_eq!(card.card.remaining(), 0);
+        }
+
+        #[test]
+        fn test_read_removed_card() {
+            // La carte est retirée pendant la lecture : l'erreur PC/SC est renvoyée au lieu d'arrêter le programme
+            let trace = "\
+                1718000000000\tACS ACR122U PICC Interface 00\t>\tFF82000006FFFFFFFFFFFF\n\
+                1718000000004\tACS ACR122U PICC Interface 00\t<\t9000\n\
+                1718000000005\tACS ACR122U PICC Interface 00\t>\tFF860000050100006000\n\
+                1718000000012\tACS ACR122U PICC Interface 00\t<\t9000\n\
+                1718000000013\tACS ACR122U PICC Interface 00\t>\tFFB0000010\n\
+                1718000000020\tACS ACR122U PICC Interface 00\t!\tRemovedCard\n";
+            let card = CardManager{card: ReplayTransport::parse(trace).unwrap()};
+            assert_eq!(card.read(0), Err(Error::RemovedCard));
+            assert_eq!(card.card.remaining(), 0);
+        }
     }
 
 }
@@ -0,0 +1,331 @@
+/// Module `config` charge la configuration du badgeage depuis un fichier JSON.
+pub mod config {
+    use chrono_tz::Tz;
+    use serde::{Deserialize, Serialize};
+    use std::fmt;
+    use std::fs;
+    use std::io::{Error, ErrorKind};
+    use crate::card_operations::card_operations::LedBuzzerPattern;
+    use crate::dbo::dbo::DB_PATH;
+    use crate::schedule::schedule::SystemClock;
+    use crate::timesheet::timesheet::Direction;
+
+    /// Emplacement par défaut du fichier de configuration.
+    pub const CONFIG_PATH: &str = "config.json";
+    /// Variable d'environnement donnant le chemin de la base de données.
+    pub const DATABASE_ENV: &str = "BADGEAGE_DB";
+
+    /// Rôle d'un lecteur sur le site.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum ReaderRole {
+        /// Lecteur d'entrée.
+        #[default]
+        Entry,
+        /// Lecteur de sortie.
+        Exit,
+        /// Poste d'enrôlement utilisé par les commandes `add` et `reset`.
+        Enrollment,
+    }
+
+    impl ReaderRole {
+        /// Sens des passages sur un lecteur de ce rôle ; le poste d'enrôlement n'en a pas.
+        pub fn direction(&self) -> Option<Direction> {
+            match self {
+                ReaderRole::Entry => Some(Direction::In),
+                ReaderRole::Exit => Some(Direction::Out),
+                ReaderRole::Enrollment => None,
+            }
+        }
+    }
+
+    impl fmt::Display for ReaderRole {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ReaderRole::Entry => write!(f, "entrée"),
+                ReaderRole::Exit => write!(f, "sortie"),
+                ReaderRole::Enrollment => write!(f, "enrôlement"),
+            }
+        }
+    }
+
+    /// Configuration d'un lecteur, identifié par son nom PC/SC.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct ReaderConfig {
+        /// Nom PC/SC du lecteur, ou préfixe de ce nom.
+        pub name: String,
+        #[serde(default)]
+        pub role: ReaderRole,
+        #[serde(default)]
+        pub location: String,
+        /// Le lecteur demande le code PIN du titulaire après la lecture de la carte.
+        #[serde(default)]
+        pub two_factor: bool,
+    }
+
+    impl ReaderConfig {
+        /// Indique si cette configuration s'applique au lecteur PC/SC donné.
+        pub fn matches(&self, reader: &str) -> bool {
+            reader == self.name || reader.starts_with(&self.name)
+        }
+
+        /// Indique si un lecteur connecté est désigné par le nom saisi dans une commande.
+        ///
+        /// # Arguments
+        ///
+        /// * `selector` - Le nom PC/SC du lecteur ou un préfixe de ce nom, son emplacement ou son rôle
+        ///   (`entrée`, `sortie`, `enrôlement`), sans tenir compte de la casse.
+        pub fn selected_by(&self, selector: &str) -> bool {
+            let selector = selector.to_lowercase();
+            self.name.to_lowercase().starts_with(&selector)
+                || self.location.to_lowercase() == selector
+                || self.role.to_string() == selector
+        }
+    }
+
+    /// Configuration du mode émulation, où le lecteur se présente comme une étiquette NFC.
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct EmulationConfig {
+        /// Lien servi aux téléphones ; `{token}` est remplacé par un jeton aléatoire à usage unique.
+        pub url: String,
+        /// Durée de validité du jeton, en minutes.
+        pub token_minutes: u32,
+    }
+
+    impl Default for EmulationConfig {
+        fn default() -> Self {
+            EmulationConfig { url: String::from("https://badgeage.local/enroll?token={token}"), token_minutes: 10 }
+        }
+    }
+
+    impl EmulationConfig {
+        /// Construit le lien servi pour une session, avec un nouveau jeton.
+        ///
+        /// # Arguments
+        ///
+        /// * `token` - Le jeton à usage unique de la session.
+        pub fn url_for(&self, token: &str) -> String {
+            self.url.replace("{token}", token)
+        }
+    }
+
+    /// Motifs de la LED et du buzzer joués sur les lecteurs après chaque décision.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct FeedbackConfig {
+        /// Motif d'un badge accepté.
+        pub granted: LedBuzzerPattern,
+        /// Motif d'un badge refusé.
+        pub denied: LedBuzzerPattern,
+    }
+
+    impl Default for FeedbackConfig {
+        fn default() -> Self {
+            FeedbackConfig { granted: LedBuzzerPattern::GRANTED, denied: LedBuzzerPattern::DENIED }
+        }
+    }
+
+    impl FeedbackConfig {
+        /// Motif à jouer pour une décision.
+        ///
+        /// # Arguments
+        ///
+        /// * `granted` - Indique si l'accès est accordé.
+        pub fn pattern(&self, granted: bool) -> &LedBuzzerPattern {
+            if granted { &self.granted } else { &self.denied }
+        }
+    }
+
+    /// Règles de calcul des feuilles de temps.
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct TimesheetConfig {
+        /// Arrondi des heures d'entrée et de sortie, en minutes (0 pour ne pas arrondir).
+        pub rounding_minutes: u32,
+        /// Durée au-delà de laquelle une session sans sortie est considérée comme un oubli de badge.
+        pub max_session_hours: u32,
+        /// Durée d'une journée de travail en minutes, au-delà de laquelle les heures sont supplémentaires.
+        pub daily_minutes: u32,
+    }
+
+    impl Default for TimesheetConfig {
+        fn default() -> Self {
+            TimesheetConfig { rounding_minutes: 0, max_session_hours: 12, daily_minutes: 7 * 60 }
+        }
+    }
+
+    /// Structure `Config` regroupe la configuration du site.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Config {
+        #[serde(default)]
+        pub readers: Vec<ReaderConfig>,
+        #[serde(default)]
+        pub emulation: EmulationConfig,
+        #[serde(default)]
+        pub timesheet: TimesheetConfig,
+        #[serde(default)]
+        pub feedback: FeedbackConfig,
+        /// Chemin du fichier SQLite, créé au premier lancement s'il n'existe pas.
+        #[serde(default)]
+        pub database: Option<String>,
+        /// Fichier dans lequel tracer les APDU échangées avec les cartes, si renseigné.
+        #[serde(default)]
+        pub trace: Option<String>,
+        /// Fuseau horaire du site (par exemple `Europe/Paris`) dans lequel sont évalués les horaires d'accès ;
+        /// celui de la machine à défaut.
+        #[serde(default)]
+        pub timezone: Option<Tz>,
+        /// Heure locale (`HH:MM`) à laquelle afficher chaque jour les badges visiteurs non restitués.
+        #[serde(default)]
+        pub visitor_report: Option<String>,
+        /// Fichier contenant la clé de chiffrement de la base de données, en hexadécimal ; la base est en clair si absent.
+        #[serde(default)]
+        pub keystore: Option<String>,
+    }
+
+    impl Config {
+        /// Charge la configuration depuis un fichier JSON.
+        ///
+        /// # Arguments
+        ///
+        /// * `path` - Le chemin du fichier de configuration.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Config, Error>` - La configuration lue, ou la configuration par défaut si le fichier n'existe pas.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let config = Config::load(CONFIG_PATH)?;
+        /// ```
+        pub fn load(path: &str) -> Result<Config, Error> {
+            match fs::read_to_string(path) {
+                Ok(content) => Ok(serde_json::from_str(&content)?),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+                Err(e) => Err(e),
+            }
+        }
+
+        /// Associe un lecteur PC/SC à sa configuration.
+        ///
+        /// # Arguments
+        ///
+        /// * `reader` - Le nom PC/SC du lecteur.
+        ///
+        /// # Retourne
+        ///
+        /// * `ReaderConfig` - La configuration correspondante, portant le nom PC/SC exact.
+        ///   Un lecteur absent de la configuration est considéré comme un lecteur d'entrée sans emplacement.
+        pub fn reader(&self, reader: &str) -> ReaderConfig {
+            let config = self.readers.iter().find(|config| config.matches(reader)).cloned().unwrap_or_default();
+            ReaderConfig { name: reader.to_string(), ..config }
+        }
+
+        /// Horloge du site, dans le fuseau horaire configuré.
+        pub fn clock(&self) -> SystemClock {
+            SystemClock { timezone: self.timezone }
+        }
+
+        /// Détermine le chemin de la base de données.
+        ///
+        /// # Arguments
+        ///
+        /// * `flag` - Le chemin passé par l'option `--db`, prioritaire.
+        /// * `env` - La valeur de la variable `BADGEAGE_DB`, utilisée à défaut d'option.
+        ///
+        /// # Retourne
+        ///
+        /// * `String` - Le chemin retenu : l'option, puis la variable d'environnement, puis le champ
+        ///   `database` de la configuration, et enfin `sqlite3.db` dans le dossier courant.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let path = config.database_path(None, env::var(DATABASE_ENV).ok());
+        /// ```
+        pub fn database_path(&self, flag: Option<String>, env: Option<String>) -> String {
+            flag.or(env).or_else(|| self.database.clone()).unwrap_or_else(|| DB_PATH.to_string())
+        }
+
+        /// Indique si un poste d'enrôlement est configuré.
+        pub fn has_enrollment_reader(&self) -> bool {
+            self.readers.iter().any(|reader| reader.role == ReaderRole::Enrollment)
+        }
+
+        /// Indique si un lecteur de sortie est configuré.
+        pub fn has_exit_reader(&self) -> bool {
+            self.readers.iter().any(|reader| reader.role == ReaderRole::Exit)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_reader_matching() {
+            let config: Config = serde_json::from_str(r#"{"readers": [
+                {"name": "ACS ACR122U PICC Interface 00", "role": "exit", "location": "Hall", "two_factor": true},
+                {"name": "ACS ACR122U PICC Interface 01", "role": "enrollment"}
+            ]}"#).unwrap();
+
+            let exit = config.reader("ACS ACR122U PICC Interface 00 00");
+            assert_eq!(exit.name, "ACS ACR122U PICC Interface 00 00");
+            assert_eq!(exit.role, ReaderRole::Exit);
+            assert_eq!(exit.location, "Hall");
+
+            assert!(exit.two_factor);
+            assert_eq!(config.reader("ACS ACR122U PICC Interface 01 00").role, ReaderRole::Enrollment);
+            assert!(!config.reader("ACS ACR122U PICC Interface 01 00").two_factor);
+            assert_eq!(config.reader("Autre lecteur").role, ReaderRole::Entry);
+            assert!(config.has_enrollment_reader());
+
+            assert!(exit.selected_by("hall"));
+            assert!(exit.selected_by("sortie"));
+            assert!(exit.selected_by("ACS ACR122U PICC Interface 00"));
+            assert!(!exit.selected_by("ACS ACR122U PICC Interface 01"));
+            assert!(!exit.selected_by("entrée"));
+        }
+
+        #[test]
+        fn test_emulation_url() {
+            let config: Config = serde_json::from_str(r#"{"emulation": {"url": "https://site/enroll?t={token}"}}"#).unwrap();
+            assert_eq!(config.emulation.url_for("abc"), "https://site/enroll?t=abc");
+            assert_eq!(config.emulation.token_minutes, 10);
+            assert!(Config::default().emulation.url_for("abc").ends_with("token=abc"));
+        }
+
+        #[test]
+        fn test_database_path_priority() {
+            let config: Config = serde_json::from_str(r#"{"database": "/var/lib/badgeage/badgeage.db"}"#).unwrap();
+            assert_eq!(config.database_path(Some("cli.db".into()), Some("env.db".into())), "cli.db");
+            assert_eq!(config.database_path(None, Some("env.db".into())), "env.db");
+            assert_eq!(config.database_path(None, None), "/var/lib/badgeage/badgeage.db");
+            assert_eq!(Config::default().database_path(None, None), "sqlite3.db");
+        }
+
+        #[test]
+        fn test_feedback_patterns() {
+            let config: Config = serde_json::from_str(r#"{"feedback": {"denied": {"led_state": 85, "t1": 5, "t2": 5, "repetitions": 3, "buzzer": 3}}}"#).unwrap();
+            assert_eq!(config.feedback.pattern(true), &LedBuzzerPattern::GRANTED);
+            assert_eq!(config.feedback.pattern(false), &LedBuzzerPattern { led_state: 0x55, t1: 5, t2: 5, repetitions: 3, buzzer: 3 });
+            assert_eq!(Config::default().feedback.pattern(false), &LedBuzzerPattern::DENIED);
+        }
+
+        #[test]
+        fn test_timezone() {
+            let config: Config = serde_json::from_str(r#"{"timezone": "Europe/Paris"}"#).unwrap();
+            assert_eq!(config.clock().local(0), 3600);
+            assert!(Config::default().timezone.is_none());
+            assert!(serde_json::from_str::<Config>(r#"{"timezone": "Europe/Atlantide"}"#).is_err());
+        }
+
+        #[test]
+        fn test_load_missing_file() {
+            let config = Config::load("config_inexistante.json").unwrap();
+            assert!(config.readers.is_empty());
+        }
+    }
+}
@@ -1,203 +1,2535 @@
 
 /// Module `dbo` fournit des fonctions pour gérer les opérations de base de données
 /// en utilisant SQLx avec SQLite.
+///
+/// Toutes les valeurs sont passées en paramètres liés, jamais par concaténation, et les lignes sont lues
+/// dans des types du domaine. Les requêtes sont vérifiées à la compilation par les macros `query!`, `query_as!`
+/// et `query_scalar!` contre le schéma des migrations ; leurs descriptions sont enregistrées dans le dossier
+/// `.sqlx` pour compiler sans base (voir « Requêtes vérifiées à la compilation » dans le README). Seules les
+/// instructions propres à SQLCipher de `encrypt`, que SQLite ne sait pas décrire, restent des chaînes.
+///
+/// Les horodatages sont enregistrés en UTC et convertis à l'heure du site par l'horloge du gestionnaire,
+/// celle des décisions d'accès, jamais par le fuseau de la machine (`'localtime'` de SQLite).
 pub mod dbo {
-    const DB_URL: &str = "sqlite://sqlite3.db";
-    use sqlx::{Error, Pool, Row, Sqlite, SqlitePool};
-    use serde_json::json;
-    use std::fs::File;
+    /// Emplacement par défaut de la base de données, relatif au dossier courant.
+    pub const DB_PATH: &str = "sqlite3.db";
+    use sqlx::migrate::{Migrate, Migrator};
+    use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqlitePoolOptions};
+    use sqlx::{Error, FromRow, SqlitePool};
+    use serde::Serialize;
+    use sha2::{Digest, Sha256};
+    use std::collections::{HashMap, HashSet};
+    use std::fmt;
+    use std::fs::{self, File};
     use std::io::Write;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use crate::audit::audit::{AuditEntry, GENESIS};
+    use crate::import::import::{normalize_uid, ImportIssue, ImportedUser};
+    use crate::keystore::keystore::pragma_value;
+    use crate::schedule::schedule::{Clock, GroupSchedule, Holiday, SystemClock, TimeWindow};
+    use crate::timesheet::timesheet::{format_day, format_timestamp, parse_day, parse_timestamp, SECONDS_PER_DAY};
+
+    /// Délai d'attente lorsqu'une autre connexion verrouille la base.
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+    /// Nombre maximal de connexions ouvertes simultanément.
+    const MAX_CONNECTIONS: u32 = 5;
+    /// Migrations du schéma, lues dans le dossier `migrations` et embarquées à la compilation.
+    static MIGRATOR: Migrator = sqlx::migrate!();
+
+    /// État d'une migration embarquée dans le programme.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct MigrationStatus {
+        pub version: i64,
+        pub description: String,
+        pub applied: bool,
+    }
+
+    /// Résultat d'un passage de badge, enregistré dans la colonne `result`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, sqlx::Type)]
+    #[sqlx(rename_all = "lowercase")]
+    pub enum BadgeResult {
+        Granted,
+        Denied,
+    }
+
+    impl fmt::Display for BadgeResult {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                BadgeResult::Granted => write!(f, "accepté"),
+                BadgeResult::Denied => write!(f, "refusé"),
+            }
+        }
+    }
+
+    /// Passage de badge enregistré dans l'historique.
+    #[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+    pub struct BadgeRecord {
+        pub id: i64,
+        /// Date et heure locales, au format `AAAA-MM-JJ HH:MM:SS`.
+        pub timestamp: String,
+        pub card_uid: String,
+        pub user_id: Option<i64>,
+        pub user_name: Option<String>,
+        pub reader: String,
+        pub result: BadgeResult,
+        pub reason: Option<String>,
+    }
+
+    /// Statut d'un utilisateur ; les badges d'un utilisateur suspendu sont refusés.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, sqlx::Type)]
+    #[sqlx(rename_all = "lowercase")]
+    pub enum UserStatus {
+        #[default]
+        Active,
+        Suspended,
+    }
+
+    impl UserStatus {
+        /// Valeur enregistrée dans la colonne `status`.
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                UserStatus::Active => "active",
+                UserStatus::Suspended => "suspended",
+            }
+        }
+    }
+
+    impl FromStr for UserStatus {
+        type Err = String;
+
+        fn from_str(status: &str) -> Result<Self, Self::Err> {
+            match status {
+                "active" | "actif" => Ok(UserStatus::Active),
+                "suspended" | "suspendu" => Ok(UserStatus::Suspended),
+                _ => Err(format!("Statut inconnu: {} (active ou suspended)", status)),
+            }
+        }
+    }
+
+    impl fmt::Display for UserStatus {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                UserStatus::Active => write!(f, "actif"),
+                UserStatus::Suspended => write!(f, "suspendu"),
+            }
+        }
+    }
+
+    /// Fiche d'un utilisateur, identifié par `id` indépendamment de sa carte.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, FromRow)]
+    pub struct User {
+        pub id: i64,
+        /// UID de la carte active la plus récente.
+        pub uuid: Option<String>,
+        /// Nom affiché et utilisé par les commandes.
+        pub name: String,
+        pub first_name: Option<String>,
+        pub last_name: Option<String>,
+        /// Matricule, unique.
+        pub employee_number: Option<String>,
+        pub email: Option<String>,
+        pub department: Option<String>,
+        pub status: UserStatus,
+        /// Dates de création et de dernière modification, en heure locale.
+        pub created_at: Option<String>,
+        pub updated_at: Option<String>,
+    }
+
+    impl User {
+        /// Champs modifiables par `set`.
+        pub const FIELDS: [&'static str; 7] = ["name", "first_name", "last_name", "employee_number", "email", "department", "status"];
+
+        /// Modifie un champ de la fiche ; une valeur vide efface un champ facultatif.
+        ///
+        /// # Arguments
+        ///
+        /// * `field` - Le nom du champ, parmi `User::FIELDS`.
+        /// * `value` - La nouvelle valeur.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<(), String>` - Une erreur si le champ est inconnu ou la valeur invalide.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// user.set("department", "Comptabilité")?;
+        /// ```
+        pub fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+            let value = value.trim();
+            let optional = (!value.is_empty()).then(|| value.to_string());
+            match field {
+                "name" if value.is_empty() => return Err(String::from("Le nom ne peut pas être vide")),
+                "name" => self.name = value.to_string(),
+                "first_name" => self.first_name = optional,
+                "last_name" => self.last_name = optional,
+                "employee_number" => self.employee_number = optional,
+                "email" => {
+                    if let Some(email) = &optional {
+                        let valid = email.split_once('@').is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.') && !email.contains(' '));
+                        if !valid {
+                            return Err(format!("Adresse e-mail invalide: {}", email));
+                        }
+                    }
+                    self.email = optional;
+                }
+                "department" => self.department = optional,
+                "status" => self.status = value.parse()?,
+                _ => return Err(format!("Champ inconnu: {} ({})", field, User::FIELDS.join(", "))),
+            }
+            Ok(())
+        }
+
+        /// Valeur d'un champ modifiable par `set`, `None` s'il est vide ou inconnu.
+        pub fn get(&self, field: &str) -> Option<String> {
+            match field {
+                "name" => Some(self.name.clone()),
+                "first_name" => self.first_name.clone(),
+                "last_name" => self.last_name.clone(),
+                "employee_number" => self.employee_number.clone(),
+                "email" => self.email.clone(),
+                "department" => self.department.clone(),
+                "status" => Some(self.status.as_str().to_string()),
+                _ => None,
+            }
+        }
+    }
+
+    /// État d'une carte dans son cycle de vie ; seule une carte active donne accès.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, sqlx::Type)]
+    #[sqlx(rename_all = "lowercase")]
+    pub enum CardState {
+        #[default]
+        Active,
+        Lost,
+        Stolen,
+        Returned,
+        Expired,
+    }
+
+    impl CardState {
+        /// Valeur enregistrée dans la colonne `state`.
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                CardState::Active => "active",
+                CardState::Lost => "lost",
+                CardState::Stolen => "stolen",
+                CardState::Returned => "returned",
+                CardState::Expired => "expired",
+            }
+        }
+    }
+
+    impl FromStr for CardState {
+        type Err = String;
+
+        fn from_str(state: &str) -> Result<Self, Self::Err> {
+            match state {
+                "active" => Ok(CardState::Active),
+                "lost" => Ok(CardState::Lost),
+                "stolen" => Ok(CardState::Stolen),
+                "returned" => Ok(CardState::Returned),
+                "expired" => Ok(CardState::Expired),
+                _ => Err(format!("État de carte inconnu: {}", state)),
+            }
+        }
+    }
+
+    impl fmt::Display for CardState {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                CardState::Active => write!(f, "active"),
+                CardState::Lost => write!(f, "perdue"),
+                CardState::Stolen => write!(f, "volée"),
+                CardState::Returned => write!(f, "restituée"),
+                CardState::Expired => write!(f, "expirée"),
+            }
+        }
+    }
+
+    /// Carte attribuée à un utilisateur.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, FromRow)]
+    pub struct Card {
+        pub id: i64,
+        pub uid: String,
+        pub user_id: i64,
+        pub state: CardState,
+        /// Dates d'émission et d'expiration, à l'heure du site au format `AAAA-MM-JJ`.
+        pub issued_at: String,
+        pub expires_at: Option<String>,
+    }
+
+    impl Card {
+        /// État effectif de la carte : une carte active dont la date d'expiration est dépassée est expirée.
+        ///
+        /// # Arguments
+        ///
+        /// * `now` - L'heure locale du site, comme `Clock::now`.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let state = card.effective_state(config.clock().now());
+        /// ```
+        pub fn effective_state(&self, now: i64) -> CardState {
+            let today = format_day(now.div_euclid(SECONDS_PER_DAY));
+            match self.state {
+                CardState::Active if self.expires_at.as_ref().is_some_and(|expires_at| *expires_at < today) => CardState::Expired,
+                state => state,
+            }
+        }
+    }
+
+    /// Visite en cours : un badge visiteur prêté jusqu'à une heure donnée.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, FromRow)]
+    pub struct Visit {
+        pub id: i64,
+        pub badge_uid: String,
+        pub visitor: String,
+        /// L'utilisateur qui reçoit le visiteur, dont le visiteur partage les accès.
+        pub host_id: Option<i64>,
+        pub host: Option<String>,
+        pub issued_at: String,
+        /// Fin de validité du badge, heure locale du site au format `AAAA-MM-JJ HH:MM:SS`.
+        pub valid_until: String,
+    }
+
+    impl Visit {
+        /// Indique si la fin de validité est atteinte à une heure locale donnée ; une date illisible vaut expiration.
+        pub fn is_expired(&self, now: i64) -> bool {
+            parse_timestamp(&self.valid_until).is_none_or(|until| now >= until)
+        }
+    }
+
+    /// Mode anti-retour d'une zone : une carte entrée doit sortir avant de pouvoir entrer à nouveau.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, sqlx::Type)]
+    #[sqlx(rename_all = "lowercase")]
+    pub enum PassbackMode {
+        #[default]
+        Off,
+        /// Le passage est accordé mais l'infraction est signalée et enregistrée.
+        Soft,
+        /// Le passage est refusé.
+        Hard,
+    }
+
+    impl PassbackMode {
+        /// Valeur enregistrée dans la colonne `passback`.
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                PassbackMode::Off => "off",
+                PassbackMode::Soft => "soft",
+                PassbackMode::Hard => "hard",
+            }
+        }
+    }
+
+    impl FromStr for PassbackMode {
+        type Err = String;
+
+        fn from_str(mode: &str) -> Result<Self, Self::Err> {
+            match mode {
+                "off" => Ok(PassbackMode::Off),
+                "soft" => Ok(PassbackMode::Soft),
+                "hard" => Ok(PassbackMode::Hard),
+                _ => Err(format!("Mode anti-retour inconnu: {}", mode)),
+            }
+        }
+    }
+
+    impl fmt::Display for PassbackMode {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                PassbackMode::Off => write!(f, "désactivé"),
+                PassbackMode::Soft => write!(f, "souple"),
+                PassbackMode::Hard => write!(f, "strict"),
+            }
+        }
+    }
+
+    /// Zone protégée, avec les lecteurs qui y donnent accès et sa règle anti-retour.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct Zone {
+        pub name: String,
+        pub doors: Vec<String>,
+        pub passback: PassbackMode,
+        /// Délai en minutes après lequel la position d'une carte dans la zone est oubliée.
+        pub passback_reset: Option<i64>,
+        /// Nombre maximal de personnes présentes, sans limite si `None`.
+        pub max_occupancy: Option<i64>,
+        /// Nombre de cartes entrées dans la zone et non ressorties, hors positions oubliées après le délai de remise à zéro.
+        pub occupancy: i64,
+    }
+
+    /// Zone telle que lue dans la base, ses portes réunies par `group_concat`.
+    struct ZoneRow {
+        name: String,
+        doors: Option<String>,
+        passback: PassbackMode,
+        passback_reset: Option<i64>,
+        max_occupancy: Option<i64>,
+        occupancy: i64,
+    }
+
+    impl From<ZoneRow> for Zone {
+        fn from(row: ZoneRow) -> Zone {
+            Zone {
+                name: row.name,
+                doors: split_list(row.doors),
+                passback: row.passback,
+                passback_reset: row.passback_reset,
+                max_occupancy: row.max_occupancy,
+                occupancy: row.occupancy,
+            }
+        }
+    }
+
+    impl Zone {
+        /// Indique si la zone a atteint sa capacité maximale.
+        pub fn is_full(&self) -> bool {
+            self.max_occupancy.is_some_and(|max| self.occupancy >= max)
+        }
+    }
+
+    /// Dernière position connue d'une carte dans une zone.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, FromRow)]
+    pub struct Presence {
+        pub card_uid: String,
+        pub zone: String,
+        pub inside: bool,
+        /// Heure locale du site du dernier passage, au format `AAAA-MM-JJ HH:MM:SS`.
+        pub since: String,
+        /// Le titulaire de la carte, `None` pour une carte supprimée ou un badge visiteur restitué.
+        pub holder: Option<String>,
+    }
+
+    /// Groupe d'accès, avec ses membres et les zones auxquelles il donne accès.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct AccessGroup {
+        pub name: String,
+        pub members: Vec<String>,
+        pub zones: Vec<String>,
+    }
+
+    /// Groupe d'accès tel que lu dans la base, ses membres et ses zones réunis par `group_concat`.
+    struct AccessGroupRow {
+        name: String,
+        members: Option<String>,
+        zones: Option<String>,
+    }
+
+    impl From<AccessGroupRow> for AccessGroup {
+        fn from(row: AccessGroupRow) -> AccessGroup {
+            AccessGroup { name: row.name, members: split_list(row.members), zones: split_list(row.zones) }
+        }
+    }
+
+    /// Badge du stock des badges visiteurs, avec le visiteur auquel il est prêté.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, FromRow)]
+    pub struct VisitorBadge {
+        pub uid: String,
+        pub visitor: Option<String>,
+    }
+
+    /// Calendrier de jours fériés, avec son nombre de jours.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, FromRow)]
+    pub struct HolidayCalendar {
+        pub calendar: String,
+        pub days: i64,
+    }
+
+    /// Carte active et son titulaire, telle qu'exportée par `export_users_to_json`.
+    #[derive(Serialize)]
+    struct ExportedUser {
+        name: String,
+        uuid: String,
+    }
+
+    /// Groupe d'accès tel que lu pour charger ses horaires.
+    struct GroupRow {
+        id: i64,
+        name: String,
+        calendar: Option<String>,
+    }
+
+    /// Empreinte SHA-256 d'un jeton d'enrôlement, en hexadécimal.
+    fn token_hash(token: &str) -> String {
+        Sha256::digest(token.trim().to_lowercase().as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Découpe une liste produite par `group_concat(..., char(10))`.
+    fn split_list(list: Option<String>) -> Vec<String> {
+        list.map(|list| list.lines().map(String::from).collect()).unwrap_or_default()
+    }
+
+    /// Crée un utilisateur et sa première carte, émise le jour `issued_at` (`AAAA-MM-JJ`).
+    async fn insert_user_with_card(connection: &mut SqliteConnection, uid: &str, name: &str, issued_at: &str) -> Result<i64, Error> {
+        let user_id = sqlx::query!("INSERT INTO users (name) VALUES (?)", name).execute(&mut *connection).await?.last_insert_rowid();
+        sqlx::query!("INSERT INTO cards (uid, user_id, issued_at) VALUES (?, ?, ?)", uid, user_id, issued_at)
+            .execute(&mut *connection)
+            .await?;
+        Ok(user_id)
+    }
+
+    /// Résultat de la vérification, et le cas échéant de l'écriture, d'un import d'utilisateurs.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct ImportReport {
+        /// Utilisateurs ajoutés, ou à ajouter lors d'un essai.
+        pub added: Vec<ImportedUser>,
+        /// Cartes supplémentaires d'un utilisateur déjà connu, par exemple la deuxième carte active d'un export.
+        pub cards: Vec<ImportedUser>,
+        /// Utilisateurs déjà présents avec la même carte, ignorés.
+        pub unchanged: usize,
+        /// Problèmes détectés ; l'import n'est pas écrit s'il y en a.
+        pub issues: Vec<ImportIssue>,
+    }
 
     /// Structure `DboManager` gère les opérations sur la base de données.
+    ///
+    /// Elle possède un pool de connexions ouvert une seule fois et partagé par la tâche de badgeage
+    /// et les commandes ; la cloner ne fait que partager ce pool.
+    #[derive(Clone)]
+    pub struct DboManager {
+        pub dboconnector: SqlitePool,
+        /// Horloge du site, qui convertit les horodatages UTC de la base en heure locale.
+        clock: SystemClock,
+    }
+
+    impl DboManager {
+        /// Ouvre la base de données et lui applique les migrations en attente.
+        ///
+        /// Le fichier est créé s'il n'existe pas.
+        ///
+        /// # Arguments
+        ///
+        /// * `path` - Le chemin du fichier SQLite.
+        /// * `key` - La clé de chiffrement de la base, en hexadécimal, `None` pour une base en clair.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<DboManager, Error>` - Le gestionnaire de base de données.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let db = DboManager::new(DB_PATH, None).await?;
+        /// ```
+        pub async fn new(path: &str, key: Option<&str>) -> Result<DboManager, Error> {
+            let db = Self::open(path, key).await?;
+            db.migrate_up().await?;
+            Ok(db)
+        }
+
+        /// Ouvre le pool de connexions à la base de données sans appliquer les migrations, en mode WAL
+        /// pour que les lectures du badgeage ne bloquent pas les écritures des commandes.
+        ///
+        /// # Arguments
+        ///
+        /// * `path` - Le chemin du fichier SQLite, créé s'il n'existe pas.
+        /// * `key` - La clé SQLCipher de la base, en hexadécimal ; une base créée avec une clé est chiffrée.
+        ///   Une base ouverte avec une autre clé, ou sans clé alors qu'elle est chiffrée, est refusée.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let db = DboManager::open(DB_PATH, Some(&load_key("/etc/badgeage/db.key")?)).await?;
+        /// ```
+        pub async fn open(path: &str, key: Option<&str>) -> Result<DboManager, Error> {
+            let mut options = SqliteConnectOptions::new()
+                .filename(path)
+                .create_if_missing(true)
+                .journal_mode(SqliteJournalMode::Wal)
+                .busy_timeout(BUSY_TIMEOUT);
+            if let Some(key) = key {
+                options = options.pragma("key", pragma_value(key));
+            }
+            let dboconnector = SqlitePoolOptions::new()
+                .max_connections(MAX_CONNECTIONS)
+                .connect_with(options)
+                .await?;
+            Ok(DboManager { dboconnector, clock: SystemClock::default() })
+        }
+
+        /// Convertit les dates lues et écrites à l'heure du site, celle des décisions d'accès, plutôt qu'à
+        /// l'heure de la machine.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let db = DboManager::new(DB_PATH, None).await?.with_clock(config.clock());
+        /// ```
+        pub fn with_clock(self, clock: SystemClock) -> DboManager {
+            DboManager { clock, ..self }
+        }
+
+        /// Date du jour à l'heure du site, au format `AAAA-MM-JJ`.
+        fn today(&self) -> String {
+            format_day(self.clock.now().div_euclid(SECONDS_PER_DAY))
+        }
+
+        /// Convertit un horodatage UTC de la base en heure locale du site ; une valeur illisible est conservée.
+        fn local_timestamp(&self, utc: &mut String) {
+            if let Some(time) = parse_timestamp(utc) {
+                *utc = format_timestamp(self.clock.local(time));
+            }
+        }
+
+        /// Bornes UTC, la fin exclue, des journées locales `from` à `to` incluses.
+        fn day_bounds(&self, from: &str, to: &str) -> Result<(String, String), Error> {
+            let day = |day: &str| parse_day(day).ok_or_else(|| Error::Protocol(format!("Date invalide (AAAA-MM-JJ): {}", day)));
+            let (from, to) = (day(from)?, day(to)? + 1);
+            Ok((format_timestamp(self.clock.utc(from * SECONDS_PER_DAY)), format_timestamp(self.clock.utc(to * SECONDS_PER_DAY))))
+        }
+
+        /// Convertit à l'heure du site les dates de création et de modification d'une fiche utilisateur.
+        fn local_user(&self, mut user: User) -> User {
+            for date in [&mut user.created_at, &mut user.updated_at].into_iter().flatten() {
+                self.local_timestamp(date);
+            }
+            user
+        }
+
+        /// Convertit à l'heure du site les horodatages des passages.
+        fn local_events(&self, mut events: Vec<BadgeRecord>) -> Vec<BadgeRecord> {
+            for event in &mut events {
+                self.local_timestamp(&mut event.timestamp);
+            }
+            events
+        }
+
+        /// Convertit à l'heure du site la date d'attribution d'une visite.
+        fn local_visit(&self, mut visit: Visit) -> Visit {
+            self.local_timestamp(&mut visit.issued_at);
+            visit
+        }
+
+        /// Liste les migrations embarquées et indique celles déjà appliquées à la base.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Vec<MigrationStatus>, Error>` - Les migrations, par ordre de version.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let pending = db.migration_status().await?.iter().filter(|m| !m.applied).count();
+        /// ```
+        pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>, Error> {
+            let mut connection = self.dboconnector.acquire().await?;
+            connection.ensure_migrations_table().await?;
+            let applied = connection.list_applied_migrations().await?;
+
+            Ok(MIGRATOR.iter().map(|migration| MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                applied: applied.iter().any(|done| done.version == migration.version),
+            }).collect())
+        }
+
+        /// Applique les migrations en attente, chacune dans sa propre transaction.
+        ///
+        /// Une migration déjà appliquée dont le contenu a changé, ou une base migrée par une version
+        /// plus récente du programme, provoque une erreur.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<usize, Error>` - Le nombre de migrations appliquées.
+        pub async fn migrate_up(&self) -> Result<usize, Error> {
+            let pending = self.migration_status().await?.iter().filter(|migration| !migration.applied).count();
+            MIGRATOR.run(&self.dboconnector).await?;
+            Ok(pending)
+        }
+
+        /// Chiffre en place une base en clair : elle est copiée dans une base chiffrée qui la remplace.
+        ///
+        /// Aucun autre programme ne doit utiliser la base pendant l'opération.
+        ///
+        /// # Arguments
+        ///
+        /// * `path` - Le chemin de la base en clair.
+        /// * `key` - La clé de chiffrement, en hexadécimal.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<(), Error>` - Une erreur si la base est déjà chiffrée ou illisible ; elle est alors laissée intacte.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// DboManager::encrypt(DB_PATH, &create_key("/etc/badgeage/db.key")?).await?;
+        /// ```
+        pub async fn encrypt(path: &str, key: &str) -> Result<(), Error> {
+            let encrypted = format!("{}.chiffrement", path);
+            let _ = fs::remove_file(&encrypted);
+            let db = Self::open(path, None).await?;
+            let mut connection = db.dboconnector.acquire().await?;
+            // Instructions propres à SQLCipher, que les macros vérifiées ne savent pas décrire.
+            // Les écritures du journal WAL sont reportées dans le fichier avant la copie
+            sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&mut *connection).await?;
+            sqlx::query("ATTACH DATABASE ? AS encrypted KEY ?").bind(&encrypted).bind(format!("x'{}'", key)).execute(&mut *connection).await?;
+            let exported = sqlx::query("SELECT sqlcipher_export('encrypted')").execute(&mut *connection).await;
+            sqlx::query("DETACH DATABASE encrypted").execute(&mut *connection).await?;
+            drop(connection);
+            db.close().await;
+            if let Err(e) = exported {
+                let _ = fs::remove_file(&encrypted);
+                return Err(e);
+            }
+            fs::rename(&encrypted, path)?;
+            for suffix in ["-wal", "-shm"] {
+                let _ = fs::remove_file(format!("{}{}", path, suffix));
+            }
+            Ok(())
+        }
+
+        /// Ferme le pool en attendant la fin des requêtes en cours.
+        pub async fn close(&self) {
+            self.dboconnector.close().await;
+        }
+
+        /// Ajoute un utilisateur à la table des utilisateurs, avec sa carte.
+        ///
+        /// # Arguments
+        ///
+        /// * `uuid` - Une chaîne représentant l'UUID de l'utilisateur.
+        /// * `username` - Une référence à une chaîne représentant le nom de l'utilisateur.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<u64, Error>` - Le nombre de lignes affectées par l'insertion.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let rows_affected = db.adduser("some-uuid".to_string(), "username").await;
+        /// ```
+        pub async fn adduser(&self, uuid: String, username: &str) -> Result<u64, Error> {
+            let mut transaction = self.dboconnector.begin().await?;
+            insert_user_with_card(&mut transaction, &uuid, username, &self.today()).await?;
+            transaction.commit().await?;
+            Ok(1)
+        }
+
+        /// Liste les utilisateurs par ordre alphabétique.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let users = db.list_users().await?;
+        /// ```
+        pub async fn list_users(&self) -> Result<Vec<User>, Error> {
+            let users = sqlx::query_as!(
+                User,
+                r#"SELECT id, (SELECT uid FROM cards WHERE user_id = users.id AND state = 'active'
+                    ORDER BY issued_at DESC, id DESC LIMIT 1) AS "uuid?: String", name AS "name!", first_name, last_name,
+                    employee_number, email, department, status AS "status: UserStatus", created_at, updated_at
+                FROM users ORDER BY name COLLATE NOCASE, id"#
+            )
+            .fetch_all(&self.dboconnector)
+            .await?;
+            Ok(users.into_iter().map(|user| self.local_user(user)).collect())
+        }
+
+        /// Recherche un utilisateur par identifiant, matricule ou nom, dans cet ordre de priorité.
+        ///
+        /// # Arguments
+        ///
+        /// * `key` - L'identifiant, le matricule ou le nom de l'utilisateur.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let user = db.find_user("E1234").await?;
+        /// ```
+        pub async fn find_user(&self, key: &str) -> Result<Option<User>, Error> {
+            let user = sqlx::query_as!(
+                User,
+                r#"SELECT id, (SELECT uid FROM cards WHERE user_id = users.id AND state = 'active'
+                    ORDER BY issued_at DESC, id DESC LIMIT 1) AS "uuid?: String", name AS "name!", first_name, last_name,
+                    employee_number, email, department, status AS "status: UserStatus", created_at, updated_at
+                FROM users WHERE CAST(id AS TEXT) = ?1 OR employee_number = ?1 OR name = ?1
+                ORDER BY CAST(id AS TEXT) = ?1 DESC, employee_number = ?1 DESC, id LIMIT 1"#,
+                key
+            )
+            .fetch_optional(&self.dboconnector)
+            .await?;
+            Ok(user.map(|user| self.local_user(user)))
+        }
+
+        /// Recherche l'utilisateur titulaire d'une carte, quel que soit l'état de la carte.
+        ///
+        /// # Arguments
+        ///
+        /// * `uuid` - L'UID de la carte.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Option<User>, Error>` - Le titulaire, ou `None` si la carte n'est attribuée à personne.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// if let Some(owner) = db.find_user_by_uid(&event.uuid).await? {
+        ///     eprintln!("La carte est déjà attribuée à {}", owner.name);
+        /// }
+        /// ```
+        pub async fn find_user_by_uid(&self, uuid: &str) -> Result<Option<User>, Error> {
+            let user = sqlx::query_as!(
+                User,
+                r#"SELECT id, (SELECT uid FROM cards WHERE user_id = users.id AND state = 'active'
+                    ORDER BY issued_at DESC, id DESC LIMIT 1) AS "uuid?: String", name AS "name!", first_name, last_name,
+                    employee_number, email, department, status AS "status: UserStatus", created_at, updated_at
+                FROM users WHERE id = (SELECT user_id FROM cards WHERE uid = ?)"#,
+                uuid
+            )
+            .fetch_optional(&self.dboconnector)
+            .await?;
+            Ok(user.map(|user| self.local_user(user)))
+        }
+
+        /// Recherche une carte par son UID, quel que soit son état.
+        ///
+        /// # Arguments
+        ///
+        /// * `uid` - L'UID de la carte.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let card = db.find_card("A4504FA1").await?;
+        /// ```
+        pub async fn find_card(&self, uid: &str) -> Result<Option<Card>, Error> {
+            sqlx::query_as!(Card, r#"SELECT id, uid, user_id, state AS "state: CardState", issued_at, expires_at FROM cards WHERE uid = ?"#, uid)
+                .fetch_optional(&self.dboconnector)
+                .await
+        }
+
+        /// Liste les cartes d'un utilisateur, de la plus ancienne à la plus récente.
+        pub async fn cards_of_user(&self, user_id: i64) -> Result<Vec<Card>, Error> {
+            sqlx::query_as!(
+                Card,
+                r#"SELECT id, uid, user_id, state AS "state: CardState", issued_at, expires_at FROM cards WHERE user_id = ? ORDER BY issued_at, id"#,
+                user_id
+            )
+            .fetch_all(&self.dboconnector)
+            .await
+        }
+
+        /// Attribue une nouvelle carte à un utilisateur.
+        ///
+        /// # Arguments
+        ///
+        /// * `user_id` - L'identifiant de l'utilisateur.
+        /// * `uid` - L'UID de la carte.
+        /// * `expires_at` - La date d'expiration, au format `AAAA-MM-JJ`.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<i64, Error>` - L'identifiant de la carte ; une erreur si l'UID est déjà enregistré.
+        pub async fn add_card(&self, user_id: i64, uid: &str, expires_at: Option<&str>) -> Result<i64, Error> {
+            let today = self.today();
+            let result = sqlx::query!("INSERT INTO cards (uid, user_id, issued_at, expires_at) VALUES (?, ?, ?, ?)", uid, user_id, today, expires_at)
+                .execute(&self.dboconnector)
+                .await?;
+            Ok(result.last_insert_rowid())
+        }
+
+        /// Change l'état d'une carte ; une carte perdue ou volée est refusée dès la lecture suivante.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<u64, Error>` - Le nombre de cartes modifiées, 0 si l'UID est inconnu.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// db.set_card_state("A4504FA1", CardState::Lost).await?;
+        /// ```
+        pub async fn set_card_state(&self, uid: &str, state: CardState) -> Result<u64, Error> {
+            let result = sqlx::query!("UPDATE cards SET state = ?, updated_at = strftime('%Y-%m-%d %H:%M:%S', 'now') WHERE uid = ?", state, uid)
+                .execute(&self.dboconnector)
+                .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Modifie la date d'expiration d'une carte, ou la supprime avec `None`.
+        pub async fn set_card_expiry(&self, uid: &str, expires_at: Option<&str>) -> Result<u64, Error> {
+            let result = sqlx::query!("UPDATE cards SET expires_at = ?, updated_at = strftime('%Y-%m-%d %H:%M:%S', 'now') WHERE uid = ?", expires_at, uid)
+                .execute(&self.dboconnector)
+                .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Enregistre les champs modifiables d'une fiche utilisateur et met à jour sa date de modification.
+        ///
+        /// # Arguments
+        ///
+        /// * `user` - La fiche modifiée, identifiée par `id`.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<u64, Error>` - Le nombre de lignes modifiées ; une erreur si le matricule est déjà utilisé.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// user.set("status", "suspended")?;
+        /// db.update_user(&user).await?;
+        /// ```
+        pub async fn update_user(&self, user: &User) -> Result<u64, Error> {
+            let result = sqlx::query!(
+                "UPDATE users SET name = ?, first_name = ?, last_name = ?, employee_number = ?, email = ?, \
+                department = ?, status = ?, updated_at = strftime('%Y-%m-%d %H:%M:%S', 'now') WHERE id = ?",
+                user.name,
+                user.first_name,
+                user.last_name,
+                user.employee_number,
+                user.email,
+                user.department,
+                user.status,
+                user.id
+            )
+            .execute(&self.dboconnector)
+            .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Supprime une carte, et son utilisateur s'il n'a plus d'autre carte.
+        ///
+        /// # Arguments
+        ///
+        /// * `uuid` - Une chaîne représentant l'UUID de l'utilisateur à supprimer.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<u64, Error>` - Le nombre de lignes affectées par la suppression.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let rows_affected = db.deluser("some-uuid".to_string()).await;
+        /// ```
+        pub async fn deluser(&self, uuid: String) -> Result<u64, Error> {
+            let mut transaction = self.dboconnector.begin().await?;
+            let user_id = sqlx::query_scalar!("SELECT user_id FROM cards WHERE uid = ?", uuid).fetch_optional(&mut *transaction).await?;
+            let result = sqlx::query!("DELETE FROM cards WHERE uid = ?", uuid).execute(&mut *transaction).await?;
+            sqlx::query!("DELETE FROM presence WHERE card_uid = ?", uuid).execute(&mut *transaction).await?;
+            sqlx::query!("DELETE FROM users WHERE id = ? AND NOT EXISTS (SELECT 1 FROM cards WHERE user_id = users.id)", user_id)
+                .execute(&mut *transaction)
+                .await?;
+            transaction.commit().await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Enregistre l'empreinte du code PIN d'un utilisateur, ou la retire avec `None`.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<u64, Error>` - 1 si l'utilisateur est modifié, 0 s'il n'existe pas.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// db.set_pin(user.id, Some(&hash_pin("4321")?)).await?;
+        /// ```
+        pub async fn set_pin(&self, user_id: i64, hash: Option<&str>) -> Result<u64, Error> {
+            let result = sqlx::query!("UPDATE users SET pin_hash = ? WHERE id = ?", hash, user_id).execute(&self.dboconnector).await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Renvoie l'empreinte du code PIN d'un utilisateur, `None` s'il n'en a pas.
+        pub async fn pin_hash(&self, user_id: i64) -> Result<Option<String>, Error> {
+            let hash = sqlx::query_scalar!("SELECT pin_hash FROM users WHERE id = ?", user_id).fetch_optional(&self.dboconnector).await?;
+            Ok(hash.flatten())
+        }
+
+        /// Enregistre un échec de saisie du code PIN d'un utilisateur sur un lecteur.
+        pub async fn add_pin_failure(&self, user_id: i64, reader: &str) -> Result<i64, Error> {
+            let result = sqlx::query!("INSERT INTO pin_failures (user_id, reader) VALUES (?, ?)", user_id, reader).execute(&self.dboconnector).await?;
+            Ok(result.last_insert_rowid())
+        }
+
+        /// Compte les échecs de saisie du code PIN d'un utilisateur au cours des dernières minutes.
+        pub async fn pin_failures(&self, user_id: i64, minutes: i64) -> Result<i64, Error> {
+            sqlx::query_scalar!(
+                r#"SELECT count(*) AS "failures!: i64" FROM pin_failures WHERE user_id = ?
+                AND failed_at >= strftime('%Y-%m-%d %H:%M:%S', 'now', '-' || ? || ' minutes')"#,
+                user_id,
+                minutes
+            )
+            .fetch_one(&self.dboconnector)
+            .await
+        }
+
+        /// Efface les échecs de saisie du code PIN d'un utilisateur, après une saisie réussie ou pour le débloquer.
+        pub async fn clear_pin_failures(&self, user_id: i64) -> Result<u64, Error> {
+            let result = sqlx::query!("DELETE FROM pin_failures WHERE user_id = ?", user_id).execute(&self.dboconnector).await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Enregistre un jeton d'enrôlement à usage unique, servi aux téléphones par `emulate`.
+        ///
+        /// Seule l'empreinte SHA-256 du jeton est conservée.
+        ///
+        /// # Arguments
+        ///
+        /// * `token` - Le jeton aléatoire, en hexadécimal.
+        /// * `minutes` - La durée de validité du jeton.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// db.add_enrollment_token(&rngtoken(), config.emulation.token_minutes).await?;
+        /// ```
+        pub async fn add_enrollment_token(&self, token: &str, minutes: u32) -> Result<i64, Error> {
+            let hash = token_hash(token);
+            let result = sqlx::query!(
+                "INSERT INTO enrollment_tokens (token_hash, expires_at) VALUES (?, strftime('%Y-%m-%d %H:%M:%S', 'now', '+' || ? || ' minutes'))",
+                hash,
+                minutes
+            )
+            .execute(&self.dboconnector)
+            .await?;
+            Ok(result.last_insert_rowid())
+        }
+
+        /// Consomme un jeton d'enrôlement : il n'est accepté qu'une fois, avant son expiration.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<bool, Error>` - `true` si le jeton était valide ; il ne l'est plus ensuite.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// if db.redeem_enrollment_token(token).await? { ... }
+        /// ```
+        pub async fn redeem_enrollment_token(&self, token: &str) -> Result<bool, Error> {
+            let hash = token_hash(token);
+            let result = sqlx::query!(
+                "UPDATE enrollment_tokens SET redeemed_at = strftime('%Y-%m-%d %H:%M:%S', 'now') \
+                WHERE token_hash = ? AND redeemed_at IS NULL AND expires_at > strftime('%Y-%m-%d %H:%M:%S', 'now')",
+                hash
+            )
+            .execute(&self.dboconnector)
+            .await?;
+            Ok(result.rows_affected() == 1)
+        }
+
+        /// Ajoute une entrée au journal d'audit, chaînée à la dernière par son empreinte.
+        ///
+        /// # Arguments
+        ///
+        /// * `actor` - Le compte de l'administrateur.
+        /// * `action` - La commande exécutée.
+        /// * `target` - L'objet de l'action.
+        /// * `before` - L'état avant l'action, en JSON, s'il y en a un.
+        /// * `after` - L'état après l'action, en JSON, s'il y en a un.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<AuditEntry, Error>` - L'entrée enregistrée. Deux ajouts simultanés ne peuvent pas suivre la même
+        ///   entrée : l'empreinte précédente est unique dans la table.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// db.add_audit_entry("admin", "zone del", "Serveurs", Some(r#"{"doors":["Baie"]}"#), None).await?;
+        /// ```
+        pub async fn add_audit_entry(&self, actor: &str, action: &str, target: &str, before: Option<&str>, after: Option<&str>) -> Result<AuditEntry, Error> {
+            let mut transaction = self.dboconnector.begin().await?;
+            let last = sqlx::query!("SELECT id, hash FROM audit_log ORDER BY id DESC LIMIT 1").fetch_optional(&mut *transaction).await?;
+            let (id, prev_hash) = last.map_or((1, GENESIS.to_string()), |last| (last.id + 1, last.hash));
+            let at = sqlx::query_scalar!(r#"SELECT strftime('%Y-%m-%d %H:%M:%S', 'now') AS "at!: String""#).fetch_one(&mut *transaction).await?;
+            let mut entry = AuditEntry {
+                id,
+                at,
+                actor: actor.to_string(),
+                action: action.to_string(),
+                target: target.to_string(),
+                before: before.map(str::to_string),
+                after: after.map(str::to_string),
+                prev_hash,
+                hash: String::new(),
+            };
+            entry.hash = entry.digest();
+            sqlx::query!(
+                "INSERT INTO audit_log (id, at, actor, action, target, before, after, prev_hash, hash) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                entry.id,
+                entry.at,
+                entry.actor,
+                entry.action,
+                entry.target,
+                entry.before,
+                entry.after,
+                entry.prev_hash,
+                entry.hash
+            )
+            .execute(&mut *transaction)
+            .await?;
+            transaction.commit().await?;
+            Ok(entry)
+        }
+
+        /// Renvoie le journal d'audit dans l'ordre des entrées.
+        pub async fn audit_entries(&self) -> Result<Vec<AuditEntry>, Error> {
+            sqlx::query_as!(AuditEntry, "SELECT id, at, actor, action, target, before, after, prev_hash, hash FROM audit_log ORDER BY id")
+                .fetch_all(&self.dboconnector)
+                .await
+        }
+
+        /// Exporte les utilisateurs au format JSON.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<String, Error>` - Les utilisateurs sous forme de chaîne JSON en cas de succès, sinon une erreur.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let json_data = db.export_users_to_json(file_path).await;
+        /// ```
+        pub async fn export_users_to_json(&self, file_path: &str) -> Result<(), Error>  {
+            let users = sqlx::query_as!(
+                ExportedUser,
+                r#"SELECT u.name AS "name!", c.uid AS uuid FROM cards c JOIN users u ON u.id = c.user_id WHERE c.state = 'active' ORDER BY u.id, c.id"#
+            )
+            .fetch_all(&self.dboconnector)
+            .await?;
+
+            let json_data = serde_json::to_string(&users).map_err(std::io::Error::from)?;
+
+            let mut file = File::create(file_path)?;
+            file.write_all(json_data.as_bytes())?;
+
+            Ok(())
+        }
+
+        /// Importe des utilisateurs dans une seule transaction.
+        ///
+        /// Les UID sont normalisés en majuscules. Un utilisateur déjà présent avec la même carte est ignoré.
+        /// Si un UID est invalide ou déjà attribué à un autre nom, ou si un nom est déjà associé à une autre
+        /// carte, aucun utilisateur n'est ajouté et tous les problèmes sont signalés.
+        ///
+        /// # Arguments
+        ///
+        /// * `users` - Les utilisateurs lus dans le fichier d'import.
+        /// * `dry_run` - Vérifie l'import sans rien écrire.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<ImportReport, Error>` - Le compte rendu de l'import.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let report = db.import_users(&read_users("users.json")?, true).await?;
+        /// ```
+        pub async fn import_users(&self, users: &[ImportedUser], dry_run: bool) -> Result<ImportReport, Error> {
+            let mut transaction = self.dboconnector.begin().await?;
+            let rows = sqlx::query!(r#"SELECT u.id, c.uid AS "uuid?", u.name FROM users u LEFT JOIN cards c ON c.user_id = u.id"#)
+                .fetch_all(&mut *transaction)
+                .await?;
+
+            // Cartes connues et nombre d'utilisateurs portant chaque nom, enrichis au fil du fichier
+            // pour détecter les doublons internes
+            let mut by_uid: HashMap<String, String> = HashMap::new();
+            let mut by_name: HashMap<String, HashSet<i64>> = HashMap::new();
+            for row in rows {
+                let Some(name) = row.name else {
+                    continue;
+                };
+                if let Some(uuid) = row.uuid {
+                    by_uid.insert(uuid, name.clone());
+                }
+                by_name.entry(name).or_default().insert(row.id);
+            }
+
+            let mut report = ImportReport::default();
+            for user in users {
+                let Some(uuid) = normalize_uid(&user.uuid) else {
+                    report.issues.push(ImportIssue::InvalidUid { line: user.line, uuid: user.uuid.clone() });
+                    continue;
+                };
+                if user.name.is_empty() {
+                    report.issues.push(ImportIssue::EmptyName { line: user.line });
+                    continue;
+                }
+
+                let imported = report.added.iter().chain(&report.cards).any(|added| added.uuid == uuid);
+                match (by_uid.get(&uuid), by_name.get(&user.name).map(HashSet::len)) {
+                    (Some(name), _) if *name == user.name && !imported => report.unchanged += 1,
+                    (Some(existing), _) => report.issues.push(ImportIssue::DuplicateUid { line: user.line, uuid, existing: existing.clone() }),
+                    (None, Some(users)) if users > 1 => report.issues.push(ImportIssue::DuplicateName { line: user.line, name: user.name.clone() }),
+                    // Un nom déjà connu reçoit la carte en plus des siennes, comme un export d'un utilisateur à plusieurs cartes
+                    (None, Some(_)) => {
+                        by_uid.insert(uuid.clone(), user.name.clone());
+                        report.cards.push(ImportedUser { uuid, ..user.clone() });
+                    }
+                    (None, None) => {
+                        by_uid.insert(uuid.clone(), user.name.clone());
+                        // 0 : utilisateur créé par cet import
+                        by_name.entry(user.name.clone()).or_default().insert(0);
+                        report.added.push(ImportedUser { uuid, ..user.clone() });
+                    }
+                }
+            }
+
+            if dry_run || !report.issues.is_empty() {
+                transaction.rollback().await?;
+                return Ok(report);
+            }
+
+            let today = self.today();
+            for user in &report.added {
+                insert_user_with_card(&mut transaction, &user.uuid, &user.name, &today).await?;
+            }
+            for card in &report.cards {
+                sqlx::query!("INSERT INTO cards (uid, user_id, issued_at) SELECT ?, id, ? FROM users WHERE name = ?", card.uuid, today, card.name)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+            transaction.commit().await?;
+            Ok(report)
+        }
+
+        /// Crée une zone.
+        pub async fn add_zone(&self, name: &str) -> Result<i64, Error> {
+            let result = sqlx::query!("INSERT INTO zones (name) VALUES (?)", name).execute(&self.dboconnector).await?;
+            Ok(result.last_insert_rowid())
+        }
+
+        /// Supprime une zone, ses portes et les règles qui la concernent.
+        pub async fn delete_zone(&self, name: &str) -> Result<u64, Error> {
+            let result = sqlx::query!("DELETE FROM zones WHERE name = ?", name).execute(&self.dboconnector).await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Liste les zones, par ordre alphabétique, avec leurs portes.
+        ///
+        /// L'occupation est comptée à l'heure du site : comme pour l'anti-retour, une position plus ancienne que le délai
+        /// de remise à zéro de la zone n'est pas comptée.
+        pub async fn list_zones(&self) -> Result<Vec<Zone>, Error> {
+            let now = format_timestamp(self.clock.now());
+            let zones = sqlx::query_as!(
+                ZoneRow,
+                r#"SELECT name, passback AS "passback: PassbackMode", passback_reset, max_occupancy,
+                    (SELECT group_concat(reader, char(10)) FROM doors WHERE zone_id = zones.id) AS "doors?: String",
+                    (SELECT count(*) FROM presence WHERE zone_id = zones.id AND inside = 1 AND (zones.passback_reset IS NULL
+                        OR since > datetime(?1, '-' || zones.passback_reset || ' minutes'))) AS "occupancy!: i64"
+                FROM zones ORDER BY name"#,
+                now
+            )
+            .fetch_all(&self.dboconnector)
+            .await?;
+            Ok(zones.into_iter().map(Zone::from).collect())
+        }
+
+        /// Recherche une zone par son nom.
+        pub async fn find_zone(&self, name: &str) -> Result<Option<Zone>, Error> {
+            let now = format_timestamp(self.clock.now());
+            let zone = sqlx::query_as!(
+                ZoneRow,
+                r#"SELECT name, passback AS "passback: PassbackMode", passback_reset, max_occupancy,
+                    (SELECT group_concat(reader, char(10)) FROM doors WHERE zone_id = zones.id) AS "doors?: String",
+                    (SELECT count(*) FROM presence WHERE zone_id = zones.id AND inside = 1 AND (zones.passback_reset IS NULL
+                        OR since > datetime(?1, '-' || zones.passback_reset || ' minutes'))) AS "occupancy!: i64"
+                FROM zones WHERE name = ?2"#,
+                now,
+                name
+            )
+            .fetch_optional(&self.dboconnector)
+            .await?;
+            Ok(zone.map(Zone::from))
+        }
+
+        /// Règle le mode anti-retour d'une zone.
+        ///
+        /// # Arguments
+        ///
+        /// * `zone` - Le nom de la zone.
+        /// * `mode` - Le mode anti-retour.
+        /// * `reset` - Le délai en minutes après lequel la position d'une carte est oubliée, jamais si `None`.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<u64, Error>` - 1 si la zone est modifiée, 0 si elle n'existe pas.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// db.set_passback("Serveurs", PassbackMode::Hard, Some(12 * 60)).await?;
+        /// ```
+        pub async fn set_passback(&self, zone: &str, mode: PassbackMode, reset: Option<i64>) -> Result<u64, Error> {
+            let result = sqlx::query!("UPDATE zones SET passback = ?, passback_reset = ? WHERE name = ?", mode, reset, zone)
+                .execute(&self.dboconnector)
+                .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Fixe le nombre maximal de personnes présentes dans une zone, ou retire la limite.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<u64, Error>` - 1 si la zone est modifiée, 0 si elle n'existe pas.
+        pub async fn set_max_occupancy(&self, zone: &str, max: Option<i64>) -> Result<u64, Error> {
+            let result = sqlx::query!("UPDATE zones SET max_occupancy = ? WHERE name = ?", max, zone).execute(&self.dboconnector).await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Liste les cartes présentes, dans toutes les zones ou dans une seule, par zone puis par titulaire ; les positions
+        /// oubliées après le délai de remise à zéro de la zone ne sont pas listées.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let present = db.occupants(Some("Bâtiment")).await?;
+        /// ```
+        pub async fn occupants(&self, zone: Option<&str>) -> Result<Vec<Presence>, Error> {
+            let now = format_timestamp(self.clock.now());
+            // `holder`, la cinquième colonne, est l'utilisateur de la carte ou le visiteur à qui le badge est prêté
+            sqlx::query_as!(
+                Presence,
+                r#"SELECT p.card_uid, z.name AS zone, p.inside AS "inside: bool", p.since,
+                    COALESCE(u.name, v.visitor || ' (visiteur de ' || COALESCE(h.name, '-') || ')') AS "holder?: String"
+                FROM presence p JOIN zones z ON z.id = p.zone_id
+                    LEFT JOIN cards c ON c.uid = p.card_uid LEFT JOIN users u ON u.id = c.user_id
+                    LEFT JOIN visitor_badges b ON b.uid = p.card_uid LEFT JOIN visits v ON v.badge_id = b.id AND v.returned_at IS NULL
+                    LEFT JOIN users h ON h.id = v.host_id
+                WHERE p.inside = 1 AND (z.passback_reset IS NULL OR p.since > datetime(?1, '-' || z.passback_reset || ' minutes'))
+                    AND (?2 IS NULL OR z.name = ?2)
+                ORDER BY z.name, 5, p.card_uid"#,
+                now,
+                zone
+            )
+            .fetch_all(&self.dboconnector)
+            .await
+        }
+
+        /// Renvoie la dernière position connue d'une carte dans une zone.
+        pub async fn presence(&self, uid: &str, zone: &str) -> Result<Option<Presence>, Error> {
+            sqlx::query_as!(
+                Presence,
+                r#"SELECT p.card_uid, z.name AS zone, p.inside AS "inside: bool", p.since,
+                    COALESCE(u.name, v.visitor || ' (visiteur de ' || COALESCE(h.name, '-') || ')') AS "holder?: String"
+                FROM presence p JOIN zones z ON z.id = p.zone_id
+                    LEFT JOIN cards c ON c.uid = p.card_uid LEFT JOIN users u ON u.id = c.user_id
+                    LEFT JOIN visitor_badges b ON b.uid = p.card_uid LEFT JOIN visits v ON v.badge_id = b.id AND v.returned_at IS NULL
+                    LEFT JOIN users h ON h.id = v.host_id
+                WHERE p.card_uid = ? AND z.name = ?"#,
+                uid,
+                zone
+            )
+            .fetch_optional(&self.dboconnector)
+            .await
+        }
+
+        /// Enregistre l'entrée ou la sortie d'une carte dans une zone.
+        ///
+        /// # Arguments
+        ///
+        /// * `uid` - L'UID de la carte.
+        /// * `zone` - Le nom de la zone.
+        /// * `inside` - `true` pour une entrée, `false` pour une sortie.
+        /// * `since` - L'heure locale du passage, au format `AAAA-MM-JJ HH:MM:SS`.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// db.set_presence("A4504FA1", "Serveurs", true, "2024-06-14 08:00:00").await?;
+        /// ```
+        pub async fn set_presence(&self, uid: &str, zone: &str, inside: bool, since: &str) -> Result<u64, Error> {
+            let result = sqlx::query!(
+                "INSERT INTO presence (card_uid, zone_id, inside, since) SELECT ?, id, ?, ? FROM zones WHERE name = ? \
+                ON CONFLICT (card_uid, zone_id) DO UPDATE SET inside = excluded.inside, since = excluded.since",
+                uid,
+                inside,
+                since,
+                zone
+            )
+            .execute(&self.dboconnector)
+            .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Oublie la position d'une carte, dans une zone ou dans toutes, pour lever un blocage anti-retour.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<u64, Error>` - Le nombre de positions oubliées.
+        pub async fn clear_presence(&self, uid: &str, zone: Option<&str>) -> Result<u64, Error> {
+            let result = sqlx::query!("DELETE FROM presence WHERE card_uid = ?1 AND (?2 IS NULL OR zone_id = (SELECT id FROM zones WHERE name = ?2))", uid, zone)
+                .execute(&self.dboconnector)
+                .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Fait d'un lecteur une porte de la zone donnée, en remplaçant son éventuelle zone précédente.
+        ///
+        /// # Arguments
+        ///
+        /// * `reader` - Le nom PC/SC du lecteur, ou un préfixe de ce nom.
+        /// * `zone` - Le nom de la zone.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<u64, Error>` - 1 si la porte est enregistrée, 0 si la zone n'existe pas.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// db.set_door("ACS ACR122U", "Serveurs").await?;
+        /// ```
+        pub async fn set_door(&self, reader: &str, zone: &str) -> Result<u64, Error> {
+            let result = sqlx::query!(
+                "INSERT INTO doors (reader, zone_id) SELECT ?, id FROM zones WHERE name = ? ON CONFLICT (reader) DO UPDATE SET zone_id = excluded.zone_id",
+                reader,
+                zone
+            )
+            .execute(&self.dboconnector)
+            .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Retire un lecteur de sa zone.
+        pub async fn delete_door(&self, reader: &str) -> Result<u64, Error> {
+            let result = sqlx::query!("DELETE FROM doors WHERE reader = ?", reader).execute(&self.dboconnector).await?;
+            Ok(result.rows_affected())
+        }
 
-    pub struct DboManager {
-        pub dboconnector: SqlitePool,
-    }
+        /// Renvoie la zone desservie par un lecteur ; la porte la plus précise l'emporte si plusieurs préfixes correspondent.
+        pub async fn zone_of_reader(&self, reader: &str) -> Result<Option<String>, Error> {
+            sqlx::query_scalar!(
+                "SELECT z.name FROM doors d JOIN zones z ON z.id = d.zone_id \
+                WHERE d.reader = substr(?, 1, length(d.reader)) ORDER BY length(d.reader) DESC LIMIT 1",
+                reader
+            )
+            .fetch_optional(&self.dboconnector)
+            .await
+        }
 
+        /// Crée un groupe d'accès.
+        pub async fn add_group(&self, name: &str) -> Result<i64, Error> {
+            let result = sqlx::query!("INSERT INTO access_groups (name) VALUES (?)", name).execute(&self.dboconnector).await?;
+            Ok(result.last_insert_rowid())
+        }
 
-    /// Établit une connexion à la base de données.
-    ///
-    /// # Retourne
-    ///
-    /// * `Pool<Sqlite>` - La connexion à la base de données.
-    ///
-    /// # Exemples
-    ///
-    /// ```
-    /// let db = DboManager::dbconnection().await;
-    /// ```
+        /// Supprime un groupe d'accès, ses adhésions et ses règles.
+        pub async fn delete_group(&self, name: &str) -> Result<u64, Error> {
+            let result = sqlx::query!("DELETE FROM access_groups WHERE name = ?", name).execute(&self.dboconnector).await?;
+            Ok(result.rows_affected())
+        }
 
-    impl DboManager {
-        async fn dbconnection() -> Pool<Sqlite> {
-            let connection = SqlitePool::connect(DB_URL).await.unwrap();
-            connection
+        /// Liste les groupes d'accès, par ordre alphabétique, avec leurs membres et leurs zones.
+        pub async fn list_groups(&self) -> Result<Vec<AccessGroup>, Error> {
+            let groups = sqlx::query_as!(
+                AccessGroupRow,
+                r#"SELECT g.name,
+                    (SELECT group_concat(u.name, char(10)) FROM group_members m JOIN users u ON u.id = m.user_id WHERE m.group_id = g.id) AS "members?: String",
+                    (SELECT group_concat(z.name, char(10)) FROM access_rules r JOIN zones z ON z.id = r.zone_id WHERE r.group_id = g.id) AS "zones?: String"
+                FROM access_groups g ORDER BY g.name"#
+            )
+            .fetch_all(&self.dboconnector)
+            .await?;
+            Ok(groups.into_iter().map(AccessGroup::from).collect())
         }
 
-        /// Vérifie si un UUID existe dans la table des utilisateurs.
+        /// Ajoute un utilisateur à un groupe d'accès.
         ///
-        /// # Arguments
+        /// # Retourne
         ///
-        /// * `uuid` - Une référence à une chaîne représentant l'UUID à vérifier.
+        /// * `Result<u64, Error>` - 1 si l'utilisateur est ajouté, 0 si le groupe n'existe pas ; une erreur s'il en est déjà membre.
+        pub async fn add_group_member(&self, group: &str, user_id: i64) -> Result<u64, Error> {
+            let result = sqlx::query!("INSERT INTO group_members (group_id, user_id) SELECT id, ? FROM access_groups WHERE name = ?", user_id, group)
+                .execute(&self.dboconnector)
+                .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Retire un utilisateur d'un groupe d'accès.
+        pub async fn remove_group_member(&self, group: &str, user_id: i64) -> Result<u64, Error> {
+            let result = sqlx::query!("DELETE FROM group_members WHERE user_id = ? AND group_id = (SELECT id FROM access_groups WHERE name = ?)", user_id, group)
+                .execute(&self.dboconnector)
+                .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Autorise les membres d'un groupe à entrer dans une zone.
         ///
         /// # Retourne
         ///
-        /// * `Result<String, Error>` - Le nom de l'utilisateur associé à l'UUID s'il existe, sinon une erreur.
+        /// * `Result<u64, Error>` - 1 si la règle est créée, 0 si le groupe ou la zone n'existe pas ; une erreur si elle existe déjà.
         ///
         /// # Exemples
         ///
         /// ```
-        /// let uuid_exists = DboManager::uuid_exist("some-uuid").await;
+        /// db.allow_zone("Informatique", "Serveurs").await?;
         /// ```
+        pub async fn allow_zone(&self, group: &str, zone: &str) -> Result<u64, Error> {
+            let result = sqlx::query!(
+                "INSERT INTO access_rules (group_id, zone_id) SELECT g.id, z.id FROM access_groups g, zones z WHERE g.name = ? AND z.name = ?",
+                group,
+                zone
+            )
+            .execute(&self.dboconnector)
+            .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Supprime la règle autorisant un groupe à entrer dans une zone.
+        pub async fn revoke_zone(&self, group: &str, zone: &str) -> Result<u64, Error> {
+            let result = sqlx::query!(
+                "DELETE FROM access_rules WHERE group_id = (SELECT id FROM access_groups WHERE name = ?) \
+                AND zone_id = (SELECT id FROM zones WHERE name = ?)",
+                group,
+                zone
+            )
+            .execute(&self.dboconnector)
+            .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Renvoie les horaires des groupes d'un utilisateur qui ont une règle pour la zone donnée.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Vec<GroupSchedule>, Error>` - Vide si aucun groupe de l'utilisateur n'est autorisé dans la zone.
+        pub async fn schedules_for(&self, user_id: i64, zone: &str) -> Result<Vec<GroupSchedule>, Error> {
+            let groups = sqlx::query_as!(
+                GroupRow,
+                "SELECT g.id, g.name, g.calendar FROM access_groups g JOIN access_rules r ON r.group_id = g.id \
+                JOIN zones z ON z.id = r.zone_id JOIN group_members m ON m.group_id = g.id WHERE z.name = ? AND m.user_id = ? ORDER BY g.name",
+                zone,
+                user_id
+            )
+            .fetch_all(&self.dboconnector)
+            .await?;
+            let mut schedules = Vec::new();
+            for group in groups {
+                schedules.push(self.load_schedule(group).await?);
+            }
+            Ok(schedules)
+        }
 
-        pub async fn uuid_exist(uuid: &str) -> Result<String, Error> {
-            let db = Self::dbconnection().await;
-            let query = format!("SELECT * FROM users where uuid = '{}' LIMIT 1", uuid);
-            match sqlx::query(&query).fetch_optional(&db).await {
-                Ok(Some(row)) => {
-                    let uuid: String = row.get("name");
-                    db.close().await;
-                    Ok(uuid)
-                },
-                Ok(None) => Err(Error::RowNotFound),
-                Err(e) => Err(e),
+        /// Renvoie les horaires d'un groupe d'accès.
+        pub async fn group_schedule(&self, group: &str) -> Result<Option<GroupSchedule>, Error> {
+            let row = sqlx::query_as!(GroupRow, "SELECT id, name, calendar FROM access_groups WHERE name = ?", group).fetch_optional(&self.dboconnector).await?;
+            match row {
+                Some(group) => Ok(Some(self.load_schedule(group).await?)),
+                None => Ok(None),
             }
         }
 
-        /// Ajoute un utilisateur à la table des utilisateurs.
+        /// Lit les plages horaires, les exceptions et les jours fériés d'un groupe.
+        async fn load_schedule(&self, group: GroupRow) -> Result<GroupSchedule, Error> {
+            let windows = sqlx::query!("SELECT weekday, start_minute, end_minute FROM schedules WHERE group_id = ? ORDER BY weekday, start_minute", group.id)
+                .fetch_all(&self.dboconnector)
+                .await?;
+            let exceptions = sqlx::query!(r#"SELECT day, open AS "open: bool" FROM schedule_exceptions WHERE group_id = ? ORDER BY day"#, group.id)
+                .fetch_all(&self.dboconnector)
+                .await?;
+            let holidays = sqlx::query_scalar!("SELECT day FROM holidays WHERE calendar = ? ORDER BY day", group.calendar).fetch_all(&self.dboconnector).await?;
+            Ok(GroupSchedule {
+                group: group.name,
+                windows: windows.into_iter().map(|window| TimeWindow { weekday: window.weekday as usize, start: window.start_minute, end: window.end_minute }).collect(),
+                calendar: group.calendar,
+                exceptions: exceptions.into_iter().map(|exception| (exception.day, exception.open)).collect(),
+                holidays,
+            })
+        }
+
+        /// Ajoute une plage horaire à un groupe pour chacun des jours donnés.
         ///
         /// # Arguments
         ///
-        /// * `uuid` - Une chaîne représentant l'UUID de l'utilisateur.
-        /// * `username` - Une référence à une chaîne représentant le nom de l'utilisateur.
+        /// * `group` - Le nom du groupe.
+        /// * `weekdays` - Les jours, de 0 pour lundi à 6 pour dimanche.
+        /// * `start`, `end` - Le début et la fin (exclue) de la plage, en minutes depuis minuit.
         ///
         /// # Retourne
         ///
-        /// * `Result<u64, Error>` - Le nombre de lignes affectées par l'insertion.
+        /// * `Result<u64, Error>` - Le nombre de plages ajoutées, 0 si le groupe n'existe pas.
         ///
         /// # Exemples
         ///
         /// ```
-        /// let rows_affected = DboManager::adduser("some-uuid".to_string(), "username").await;
+        /// db.add_schedule("Bureaux", &[0, 1, 2, 3, 4], 7 * 60, 20 * 60).await?;
         /// ```
+        pub async fn add_schedule(&self, group: &str, weekdays: &[usize], start: i64, end: i64) -> Result<u64, Error> {
+            let mut transaction = self.dboconnector.begin().await?;
+            let mut added = 0;
+            for weekday in weekdays {
+                let weekday = *weekday as i64;
+                let result = sqlx::query!(
+                    "INSERT OR IGNORE INTO schedules (group_id, weekday, start_minute, end_minute) SELECT id, ?, ?, ? FROM access_groups WHERE name = ?",
+                    weekday,
+                    start,
+                    end,
+                    group
+                )
+                .execute(&mut *transaction)
+                .await?;
+                added += result.rows_affected();
+            }
+            transaction.commit().await?;
+            Ok(added)
+        }
 
-        pub async fn adduser(uuid: String, username: &str) -> Result<u64, Error> {
-            let db = Self::dbconnection().await;
-            let query = "INSERT INTO users (uuid, name) VALUES (?, ?)".to_string();
-            let result = sqlx::query(&query).bind(uuid).bind(username).execute(&db).await?;
+        /// Supprime les plages horaires d'un groupe, qui a alors accès à toute heure.
+        pub async fn clear_schedule(&self, group: &str) -> Result<u64, Error> {
+            let result = sqlx::query!("DELETE FROM schedules WHERE group_id = (SELECT id FROM access_groups WHERE name = ?)", group)
+                .execute(&self.dboconnector)
+                .await?;
             Ok(result.rows_affected())
         }
 
-        /// Supprime un utilisateur de la table des utilisateurs.
+        /// Ouvre (`Some(true)`) ou ferme (`Some(false)`) un groupe toute une journée, ou supprime l'exception avec `None`.
         ///
         /// # Arguments
         ///
-        /// * `uuid` - Une chaîne représentant l'UUID de l'utilisateur à supprimer.
+        /// * `group` - Le nom du groupe.
+        /// * `day` - La date, au format `AAAA-MM-JJ`.
+        /// * `open` - L'exception à appliquer.
+        pub async fn set_schedule_exception(&self, group: &str, day: &str, open: Option<bool>) -> Result<u64, Error> {
+            let result = match open {
+                Some(open) => {
+                    sqlx::query!(
+                        "INSERT INTO schedule_exceptions (group_id, day, open) SELECT id, ?, ? FROM access_groups WHERE name = ? \
+                        ON CONFLICT (group_id, day) DO UPDATE SET open = excluded.open",
+                        day,
+                        open,
+                        group
+                    )
+                    .execute(&self.dboconnector)
+                    .await?
+                }
+                None => {
+                    sqlx::query!("DELETE FROM schedule_exceptions WHERE day = ? AND group_id = (SELECT id FROM access_groups WHERE name = ?)", day, group)
+                        .execute(&self.dboconnector)
+                        .await?
+                }
+            };
+            Ok(result.rows_affected())
+        }
+
+        /// Applique un calendrier de jours fériés à un groupe, ou le retire avec `None`.
+        pub async fn set_group_calendar(&self, group: &str, calendar: Option<&str>) -> Result<u64, Error> {
+            let result = sqlx::query!("UPDATE access_groups SET calendar = ? WHERE name = ?", calendar, group).execute(&self.dboconnector).await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Enregistre les jours fériés d'un calendrier, en mettant à jour le libellé des jours déjà connus.
         ///
         /// # Retourne
         ///
-        /// * `Result<u64, Error>` - Le nombre de lignes affectées par la suppression.
+        /// * `Result<u64, Error>` - Le nombre de jours enregistrés.
+        pub async fn import_holidays(&self, calendar: &str, holidays: &[Holiday]) -> Result<u64, Error> {
+            let mut transaction = self.dboconnector.begin().await?;
+            for holiday in holidays {
+                sqlx::query!(
+                    "INSERT INTO holidays (calendar, day, summary) VALUES (?, ?, ?) ON CONFLICT (calendar, day) DO UPDATE SET summary = excluded.summary",
+                    calendar,
+                    holiday.day,
+                    holiday.summary
+                )
+                .execute(&mut *transaction)
+                .await?;
+            }
+            transaction.commit().await?;
+            Ok(holidays.len() as u64)
+        }
+
+        /// Liste les jours fériés d'un calendrier, par date.
+        pub async fn list_holidays(&self, calendar: &str) -> Result<Vec<Holiday>, Error> {
+            sqlx::query_as!(Holiday, "SELECT day, summary FROM holidays WHERE calendar = ? ORDER BY day", calendar)
+                .fetch_all(&self.dboconnector)
+                .await
+        }
+
+        /// Liste les calendriers de jours fériés avec leur nombre de jours.
+        pub async fn list_calendars(&self) -> Result<Vec<HolidayCalendar>, Error> {
+            sqlx::query_as!(HolidayCalendar, r#"SELECT calendar, count(*) AS "days!: i64" FROM holidays GROUP BY calendar ORDER BY calendar"#)
+                .fetch_all(&self.dboconnector)
+                .await
+        }
+
+        /// Ajoute un badge au stock des badges visiteurs.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<i64, Error>` - L'identifiant du badge ; une erreur s'il est déjà dans le stock.
+        pub async fn add_visitor_badge(&self, uid: &str) -> Result<i64, Error> {
+            let result = sqlx::query!("INSERT INTO visitor_badges (uid) VALUES (?)", uid).execute(&self.dboconnector).await?;
+            Ok(result.last_insert_rowid())
+        }
+
+        /// Retire un badge du stock, s'il n'est pas prêté.
+        pub async fn delete_visitor_badge(&self, uid: &str) -> Result<u64, Error> {
+            let result = sqlx::query!(
+                "DELETE FROM visitor_badges WHERE uid = ? AND NOT EXISTS (SELECT 1 FROM visits WHERE badge_id = visitor_badges.id AND returned_at IS NULL)",
+                uid
+            )
+            .execute(&self.dboconnector)
+            .await?;
+            Ok(result.rows_affected())
+        }
+
+        /// Liste les badges du stock avec le visiteur auquel chacun est prêté.
+        pub async fn list_visitor_badges(&self) -> Result<Vec<VisitorBadge>, Error> {
+            sqlx::query_as!(
+                VisitorBadge,
+                r#"SELECT b.uid, v.visitor AS "visitor?" FROM visitor_badges b LEFT JOIN visits v ON v.badge_id = b.id AND v.returned_at IS NULL ORDER BY b.id"#
+            )
+            .fetch_all(&self.dboconnector)
+            .await
+        }
+
+        /// Indique si une carte fait partie du stock des badges visiteurs.
+        pub async fn is_visitor_badge(&self, uid: &str) -> Result<bool, Error> {
+            sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM visitor_badges WHERE uid = ?) AS "exists!: bool""#, uid)
+                .fetch_one(&self.dboconnector)
+                .await
+        }
+
+        /// Prête le premier badge libre du stock à un visiteur.
+        ///
+        /// # Arguments
+        ///
+        /// * `visitor` - Le nom du visiteur.
+        /// * `host_id` - L'utilisateur qui le reçoit.
+        /// * `valid_until` - La fin de validité, heure locale du site au format `AAAA-MM-JJ HH:MM:SS`.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Option<Visit>, Error>` - La visite, ou `None` si tous les badges sont prêtés.
         ///
         /// # Exemples
         ///
         /// ```
-        /// let rows_affected = DboManager::deluser("some-uuid".to_string()).await;
+        /// let visit = db.start_visit("Marie Curie", host.id, "2024-06-14 18:00:00").await?;
         /// ```
-        pub async fn deluser(uuid: String) -> Result<u64, Error> {
-            let db = Self::dbconnection().await;
-            let query = "DELETE FROM users WHERE uuid = ?".to_string();
-            let result = sqlx::query(&query).bind(uuid).execute(&db).await?;
+        pub async fn start_visit(&self, visitor: &str, host_id: i64, valid_until: &str) -> Result<Option<Visit>, Error> {
+            let mut transaction = self.dboconnector.begin().await?;
+            let badge_id = sqlx::query_scalar!(
+                "SELECT id FROM visitor_badges b WHERE NOT EXISTS (SELECT 1 FROM visits WHERE badge_id = b.id AND returned_at IS NULL) ORDER BY id LIMIT 1"
+            )
+            .fetch_optional(&mut *transaction)
+            .await?;
+            let Some(badge_id) = badge_id else {
+                return Ok(None);
+            };
+            let result = sqlx::query!("INSERT INTO visits (badge_id, visitor, host_id, valid_until) VALUES (?, ?, ?, ?)", badge_id, visitor, host_id, valid_until)
+                .execute(&mut *transaction)
+                .await?;
+            let id = result.last_insert_rowid();
+            let visit = sqlx::query_as!(
+                Visit,
+                r#"SELECT v.id, b.uid AS badge_uid, v.visitor, v.host_id, u.name AS "host?", v.issued_at, v.valid_until
+                FROM visits v JOIN visitor_badges b ON b.id = v.badge_id LEFT JOIN users u ON u.id = v.host_id WHERE v.id = ?"#,
+                id
+            )
+            .fetch_one(&mut *transaction)
+            .await?;
+            transaction.commit().await?;
+            Ok(Some(self.local_visit(visit)))
+        }
+
+        /// Renvoie la visite en cours pour un badge visiteur.
+        pub async fn open_visit(&self, uid: &str) -> Result<Option<Visit>, Error> {
+            let visit = sqlx::query_as!(
+                Visit,
+                r#"SELECT v.id, b.uid AS badge_uid, v.visitor, v.host_id, u.name AS "host?", v.issued_at, v.valid_until
+                FROM visits v JOIN visitor_badges b ON b.id = v.badge_id LEFT JOIN users u ON u.id = v.host_id
+                WHERE b.uid = ? AND v.returned_at IS NULL"#,
+                uid
+            )
+            .fetch_optional(&self.dboconnector)
+            .await?;
+            Ok(visit.map(|visit| self.local_visit(visit)))
+        }
+
+        /// Liste les visites en cours, c'est-à-dire les badges visiteurs non restitués, par date d'attribution.
+        pub async fn open_visits(&self) -> Result<Vec<Visit>, Error> {
+            let visits = sqlx::query_as!(
+                Visit,
+                r#"SELECT v.id, b.uid AS badge_uid, v.visitor, v.host_id, u.name AS "host?", v.issued_at, v.valid_until
+                FROM visits v JOIN visitor_badges b ON b.id = v.badge_id LEFT JOIN users u ON u.id = v.host_id
+                WHERE v.returned_at IS NULL ORDER BY v.issued_at, v.id"#
+            )
+            .fetch_all(&self.dboconnector)
+            .await?;
+            Ok(visits.into_iter().map(|visit| self.local_visit(visit)).collect())
+        }
+
+        /// Clôt la visite en cours d'un badge, qui retourne dans le stock.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<u64, Error>` - 1 si le badge était prêté, 0 sinon.
+        pub async fn end_visit(&self, uid: &str) -> Result<u64, Error> {
+            let mut transaction = self.dboconnector.begin().await?;
+            let result = sqlx::query!(
+                "UPDATE visits SET returned_at = strftime('%Y-%m-%d %H:%M:%S', 'now') \
+                WHERE returned_at IS NULL AND badge_id = (SELECT id FROM visitor_badges WHERE uid = ?)",
+                uid
+            )
+            .execute(&mut *transaction)
+            .await?;
+            // Le badge restitué est repris à l'accueil : le prochain visiteur part de l'extérieur des zones
+            sqlx::query!("DELETE FROM presence WHERE card_uid = ?", uid).execute(&mut *transaction).await?;
+            transaction.commit().await?;
             Ok(result.rows_affected())
         }
 
-        /// Exporte les utilisateurs au format JSON.
+        /// Enregistre un passage de badge dans l'historique, rattaché à l'utilisateur de la carte s'il existe.
+        ///
+        /// # Arguments
+        ///
+        /// * `card_uid` - L'UID de la carte lue.
+        /// * `reader` - Le nom du lecteur.
+        /// * `result` - Le badge a été accepté ou refusé.
+        /// * `reason` - La raison d'un refus.
         ///
         /// # Retourne
         ///
-        /// * `Result<String, Error>` - Les utilisateurs sous forme de chaîne JSON en cas de succès, sinon une erreur.
+        /// * `Result<i64, Error>` - L'identifiant de l'événement enregistré.
         ///
         /// # Exemples
         ///
         /// ```
-        /// let json_data = DboManager::export_users_to_json(file_path).await;
+        /// db.add_badge_event("A4504FA1", &reader.name, BadgeResult::Denied, Some("carte inconnue")).await?;
         /// ```
+        pub async fn add_badge_event(&self, card_uid: &str, reader: &str, result: BadgeResult, reason: Option<&str>) -> Result<i64, Error> {
+            let result = sqlx::query!(
+                "INSERT INTO badge_events (card_uid, user_id, reader, result, reason) VALUES (?1, (SELECT user_id FROM cards WHERE uid = ?1), ?2, ?3, ?4)",
+                card_uid,
+                reader,
+                result,
+                reason
+            )
+            .execute(&self.dboconnector)
+            .await?;
+            Ok(result.last_insert_rowid())
+        }
 
-        pub async fn export_users_to_json(file_path: &str) -> Result<(), Error>  {
-            let db = Self::dbconnection().await;
-            let query = "SELECT * FROM users";
-            let rows = sqlx::query(query).fetch_all(&db).await?;
-
-            let users: Vec<_> = rows.iter().map(|row| {
-                let uuid: String = row.get("uuid");
-                let name: String = row.get("name");
-                json!({"uuid": uuid, "name": name})
-            }).collect();
+        /// Renvoie l'historique des passages d'un utilisateur, du plus ancien au plus récent.
+        ///
+        /// # Arguments
+        ///
+        /// * `name` - Le nom de l'utilisateur.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let events = db.events_by_user("tonton").await?;
+        /// ```
+        pub async fn events_by_user(&self, name: &str) -> Result<Vec<BadgeRecord>, Error> {
+            let events = sqlx::query_as!(
+                BadgeRecord,
+                r#"SELECT e.id, e.timestamp, e.card_uid, e.user_id, u.name AS user_name, e.reader, e.result AS "result: BadgeResult", e.reason
+                FROM badge_events e LEFT JOIN users u ON u.id = e.user_id WHERE u.name = ? ORDER BY e.timestamp, e.id"#,
+                name
+            )
+            .fetch_all(&self.dboconnector)
+            .await?;
+            Ok(self.local_events(events))
+        }
 
-            let json_data = json!(users).to_string();
+        /// Renvoie les passages d'un utilisateur entre deux journées incluses, en heure locale.
+        ///
+        /// # Arguments
+        ///
+        /// * `name` - Le nom de l'utilisateur.
+        /// * `from` - Le premier jour, au format `AAAA-MM-JJ`.
+        /// * `to` - Le dernier jour, au format `AAAA-MM-JJ`.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let events = db.events_by_user_between("tonton", "2024-06-10", "2024-06-16").await?;
+        /// ```
+        pub async fn events_by_user_between(&self, name: &str, from: &str, to: &str) -> Result<Vec<BadgeRecord>, Error> {
+            let (start, end) = self.day_bounds(from, to)?;
+            let events = sqlx::query_as!(
+                BadgeRecord,
+                r#"SELECT e.id, e.timestamp, e.card_uid, e.user_id, u.name AS user_name, e.reader, e.result AS "result: BadgeResult", e.reason
+                FROM badge_events e LEFT JOIN users u ON u.id = e.user_id WHERE u.name = ? AND e.timestamp >= ? AND e.timestamp < ?
+                ORDER BY e.timestamp, e.id"#,
+                name,
+                start,
+                end
+            )
+            .fetch_all(&self.dboconnector)
+            .await?;
+            Ok(self.local_events(events))
+        }
 
-            db.close().await;
+        /// Renvoie les passages d'une journée, en heure locale.
+        ///
+        /// # Arguments
+        ///
+        /// * `day` - Le jour au format `AAAA-MM-JJ`.
+        pub async fn events_by_day(&self, day: &str) -> Result<Vec<BadgeRecord>, Error> {
+            self.events_between(day, day).await
+        }
 
-            let mut file = File::create(file_path)?;
-            file.write_all(json_data.as_bytes())?;
+        /// Renvoie les passages de tous les utilisateurs entre deux journées incluses, en heure locale.
+        ///
+        /// # Arguments
+        ///
+        /// * `from` - Le premier jour, au format `AAAA-MM-JJ`.
+        /// * `to` - Le dernier jour, au format `AAAA-MM-JJ`.
+        pub async fn events_between(&self, from: &str, to: &str) -> Result<Vec<BadgeRecord>, Error> {
+            let (start, end) = self.day_bounds(from, to)?;
+            let events = sqlx::query_as!(
+                BadgeRecord,
+                r#"SELECT e.id, e.timestamp, e.card_uid, e.user_id, u.name AS user_name, e.reader, e.result AS "result: BadgeResult", e.reason
+                FROM badge_events e LEFT JOIN users u ON u.id = e.user_id WHERE e.timestamp >= ? AND e.timestamp < ? ORDER BY e.timestamp, e.id"#,
+                start,
+                end
+            )
+            .fetch_all(&self.dboconnector)
+            .await?;
+            Ok(self.local_events(events))
+        }
 
-            Ok(())
+        /// Renvoie les passages enregistrés sur un lecteur.
+        ///
+        /// # Arguments
+        ///
+        /// * `reader` - Le nom PC/SC du lecteur.
+        pub async fn events_by_reader(&self, reader: &str) -> Result<Vec<BadgeRecord>, Error> {
+            let events = sqlx::query_as!(
+                BadgeRecord,
+                r#"SELECT e.id, e.timestamp, e.card_uid, e.user_id, u.name AS user_name, e.reader, e.result AS "result: BadgeResult", e.reason
+                FROM badge_events e LEFT JOIN users u ON u.id = e.user_id WHERE e.reader = ? ORDER BY e.timestamp, e.id"#,
+                reader
+            )
+            .fetch_all(&self.dboconnector)
+            .await?;
+            Ok(self.local_events(events))
         }
     }
     #[cfg(test)]
     mod tests {
         use super::*;
+        use crate::audit::audit::{verify, AuditIssue};
         use std::fs;
 
+        /// Base de test propre à un test, créée dans le dossier temporaire du système.
+        ///
+        /// La base et ses fichiers WAL sont supprimés à la fin du test, y compris lorsqu'une assertion échoue.
+        struct TempDb {
+            path: String,
+        }
+
+        impl TempDb {
+            /// Réserve le chemin de la base ; `name` distingue les tests exécutés en parallèle.
+            fn new(name: &str) -> TempDb {
+                let path = std::env::temp_dir().join(format!("badgeage_{}_{}.db", name, std::process::id()));
+                let temp = TempDb { path: path.to_string_lossy().into_owned() };
+                temp.remove();
+                temp
+            }
+
+            /// Crée la base et applique les migrations.
+            async fn db(&self) -> DboManager {
+                DboManager::new(&self.path, None).await.unwrap()
+            }
+
+            fn remove(&self) {
+                for suffix in ["", "-wal", "-shm"] {
+                    let _ = fs::remove_file(format!("{}{}", self.path, suffix));
+                }
+            }
+        }
+
+        impl Drop for TempDb {
+            fn drop(&mut self) {
+                self.remove();
+            }
+        }
+
+        /// Crée une base de test migrée contenant les deux utilisateurs du jeu d'essai.
+        async fn create_db(name: &str) -> (TempDb, DboManager) {
+            let temp = TempDb::new(name);
+            let db = temp.db().await;
+            db.adduser("A4504FA11A8406263646566676869".to_string(), "tonton").await.unwrap();
+            db.adduser("D458D1A0FD8406263646566676869".to_string(), "Yu").await.unwrap();
+            (temp, db)
+        }
 
         #[tokio::test]
         async fn test_dbconnection() {
-            let result = super::DboManager::dbconnection().await;
-            assert!(!result.is_closed());
+            let (_temp, db) = create_db("connection").await;
+            assert!(!db.dboconnector.is_closed());
+
+            let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode").fetch_one(&db.dboconnector).await.unwrap();
+            assert_eq!(journal_mode, "wal");
+            let busy_timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout").fetch_one(&db.dboconnector).await.unwrap();
+            assert_eq!(busy_timeout, 5000);
+
+            db.close().await;
+            assert!(db.dboconnector.is_closed());
+        }
+
+        #[tokio::test]
+        async fn test_create_missing_database() {
+            let temp = TempDb::new("schema");
+            let path = temp.path.as_str();
+            let db = DboManager::new(path, None).await.unwrap();
+            assert!(db.migration_status().await.unwrap().iter().all(|migration| migration.applied));
+            assert_eq!(db.adduser("0102".to_string(), "Alice").await.unwrap(), 1);
+            assert_eq!(db.find_user_by_uid("0102").await.unwrap().unwrap().name, "Alice");
+            db.close().await;
+
+            let db = DboManager::new(path, None).await.unwrap();
+            assert_eq!(db.find_user_by_uid("0102").await.unwrap().unwrap().name, "Alice");
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_migrate_up() {
+            let temp = TempDb::new("migrate");
+            let path = temp.path.as_str();
+            let db = DboManager::open(path, None).await.unwrap();
+            let status = db.migration_status().await.unwrap();
+            assert_eq!(status[0], MigrationStatus { version: 1, description: "create users".to_string(), applied: false });
+
+            assert_eq!(db.migrate_up().await.unwrap(), status.len());
+            assert!(db.migration_status().await.unwrap().iter().all(|migration| migration.applied));
+            assert_eq!(db.migrate_up().await.unwrap(), 0);
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_migrate_legacy_cards() {
+            let temp = TempDb::new("migrate_cards");
+            let db = DboManager::open(&temp.path, None).await.unwrap();
+            // Base antérieure aux cartes : la carte est encore dans users.uuid
+            let mut connection = db.dboconnector.acquire().await.unwrap();
+            connection.ensure_migrations_table().await.unwrap();
+            for migration in MIGRATOR.iter().take(3) {
+                connection.apply(migration).await.unwrap();
+            }
+            sqlx::query("INSERT INTO users (uuid, name, created_at) VALUES ('0A0B', 'Alice', '2024-03-01 23:30:00')").execute(&mut *connection).await.unwrap();
+            drop(connection);
+
+            db.migrate_up().await.unwrap();
+            // La date d'émission reprise est le jour UTC de la création, quel que soit le fuseau de la machine
+            assert_eq!(db.find_card("0A0B").await.unwrap().unwrap().issued_at, "2024-03-01");
+            // Sans valeur par défaut, une carte doit recevoir sa date d'émission de l'application
+            let user_id = db.find_user("Alice").await.unwrap().unwrap().id;
+            assert!(sqlx::query("INSERT INTO cards (uid, user_id) VALUES ('0C0D', ?)").bind(user_id).execute(&db.dboconnector).await.is_err());
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_badge_events() {
+            let temp = TempDb::new("events");
+            let db = temp.db().await;
+            db.adduser("0A0B".to_string(), "Alice").await.unwrap();
+
+            db.add_badge_event("0A0B", "Lecteur 00", BadgeResult::Granted, None).await.unwrap();
+            db.add_badge_event("FFFF", "Lecteur 01", BadgeResult::Denied, Some("carte inconnue")).await.unwrap();
+            db.add_badge_event("0A0B", "Lecteur 01", BadgeResult::Granted, None).await.unwrap();
+
+            let alice = db.events_by_user("Alice").await.unwrap();
+            assert_eq!(alice.len(), 2);
+            assert_eq!(alice[0].reader, "Lecteur 00");
+            assert_eq!(alice[0].user_name.as_deref(), Some("Alice"));
+
+            let reader = db.events_by_reader("Lecteur 01").await.unwrap();
+            assert_eq!(reader.len(), 2);
+            assert_eq!(reader[0].result, BadgeResult::Denied);
+            assert_eq!(reader[0].user_id, None);
+            assert_eq!(reader[0].reason.as_deref(), Some("carte inconnue"));
+
+            let today = db.today();
+            assert_eq!(db.events_by_day(&today).await.unwrap().len(), 3);
+            assert_eq!(db.events_by_user_between("Alice", &today, &today).await.unwrap().len(), 2);
+            assert!(db.events_by_user_between("Alice", "2000-01-01", "2000-01-07").await.unwrap().is_empty());
+            assert_eq!(db.events_between(&today, &today).await.unwrap().len(), 3);
+            assert!(db.events_by_day("2000-01-01").await.unwrap().is_empty());
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_site_timezone() {
+            let temp = TempDb::new("timezone");
+            let db = temp.db().await.with_clock(SystemClock { timezone: Some(chrono_tz::Europe::Paris) });
+            db.adduser("0A0B".to_string(), "Alice").await.unwrap();
+            let user = db.find_user("Alice").await.unwrap().unwrap();
+            // 23:30 UTC le 14 juin est 01:30 le 15 juin à Paris, quel que soit le fuseau de la machine
+            sqlx::query("INSERT INTO badge_events (timestamp, card_uid, user_id, reader, result) VALUES ('2024-06-14 23:30:00', '0A0B', ?, 'Entrée', 'granted')")
+                .bind(user.id)
+                .execute(&db.dboconnector)
+                .await
+                .unwrap();
+
+            let events = db.events_by_day("2024-06-15").await.unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].timestamp, "2024-06-15 01:30:00");
+            assert!(db.events_by_day("2024-06-14").await.unwrap().is_empty());
+            assert_eq!(db.events_by_user_between("Alice", "2024-06-10", "2024-06-15").await.unwrap().len(), 1);
+            assert!(db.events_between("2024-06-10", "2024-06-14").await.unwrap().is_empty());
+            assert!(db.events_by_day("2024-06-31").await.is_err());
+
+            sqlx::query("UPDATE users SET created_at = '2024-01-15 10:00:00' WHERE id = ?").bind(user.id).execute(&db.dboconnector).await.unwrap();
+            assert_eq!(db.find_user("Alice").await.unwrap().unwrap().created_at.as_deref(), Some("2024-01-15 11:00:00"));
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_import_users() {
+            let temp = TempDb::new("import");
+            let db = temp.db().await;
+            db.adduser("0A0B".to_string(), "Alice").await.unwrap();
+
+            let user = |line: usize, uuid: &str, name: &str| ImportedUser { line, uuid: uuid.to_string(), name: name.to_string() };
+            let users = [user(1, "0a0b", "Alice"), user(2, "0c0d", "Bob"), user(3, "0e0f", "Carol")];
+
+            let report = db.import_users(&users, true).await.unwrap();
+            assert_eq!((report.added.len(), report.unchanged, report.issues.len()), (2, 1, 0));
+            assert!(db.find_user_by_uid("0C0D").await.unwrap().is_none());
+
+            let report = db.import_users(&users, false).await.unwrap();
+            assert_eq!(report.added[0].uuid, "0C0D");
+            assert_eq!(db.find_user_by_uid("0C0D").await.unwrap().unwrap().name, "Bob");
+
+            db.adduser("3333".to_string(), "Homonyme").await.unwrap();
+            db.adduser("4444".to_string(), "Homonyme").await.unwrap();
+            let conflicts = [user(1, "1111", "Dave"), user(2, "0C0D", "Eve"), user(3, "2222", "Homonyme"), user(4, "1111", "Frank"), user(5, "zz", "Gina")];
+            let report = db.import_users(&conflicts, false).await.unwrap();
+            assert_eq!(report.issues, [
+                ImportIssue::DuplicateUid { line: 2, uuid: String::from("0C0D"), existing: String::from("Bob") },
+                ImportIssue::DuplicateName { line: 3, name: String::from("Homonyme") },
+                ImportIssue::DuplicateUid { line: 4, uuid: String::from("1111"), existing: String::from("Dave") },
+                ImportIssue::InvalidUid { line: 5, uuid: String::from("zz") },
+            ]);
+            assert!(db.find_user_by_uid("1111").await.unwrap().is_none());
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_import_export_round_trip() {
+            let temp = TempDb::new("round_trip");
+            let db = temp.db().await;
+            db.adduser("0A0B".to_string(), "Alice").await.unwrap();
+            let alice = db.find_user("Alice").await.unwrap().unwrap();
+            db.add_card(alice.id, "0C0D", None).await.unwrap();
+            let file_path = format!("{}.json", temp.path);
+            db.export_users_to_json(&file_path).await.unwrap();
+            let users = crate::import::import::read_users(&file_path).unwrap();
+            fs::remove_file(&file_path).unwrap();
+
+            // Réimporté dans la même base, l'export est déjà présent
+            let report = db.import_users(&users, false).await.unwrap();
+            assert_eq!((report.added.len(), report.cards.len(), report.unchanged, report.issues.len()), (0, 0, 2, 0));
+
+            // Dans une base vide, l'utilisateur retrouve ses deux cartes
+            let other = TempDb::new("round_trip_empty");
+            let empty = other.db().await;
+            let report = empty.import_users(&users, false).await.unwrap();
+            assert!(report.issues.is_empty());
+            assert_eq!((report.added.len(), report.cards.len()), (1, 1));
+            let alice = empty.find_user("Alice").await.unwrap().unwrap();
+            let cards: Vec<String> = empty.cards_of_user(alice.id).await.unwrap().into_iter().map(|card| card.uid).collect();
+            assert_eq!(cards, ["0A0B", "0C0D"]);
+            db.close().await;
+            empty.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_user_crud() {
+            let temp = TempDb::new("users");
+            let db = temp.db().await;
+            db.adduser("0A0B".to_string(), "Jean Dupont").await.unwrap();
+            db.adduser("0C0D".to_string(), "Alice").await.unwrap();
+
+            let mut user = db.find_user("Jean Dupont").await.unwrap().unwrap();
+            assert_eq!(user.status, UserStatus::Active);
+            assert!(user.created_at.is_some());
+
+            user.set("employee_number", "E1234").unwrap();
+            user.set("email", "jean.dupont@example.com").unwrap();
+            user.set("status", "suspended").unwrap();
+            assert!(user.set("email", "jean").is_err());
+            assert!(user.set("badge", "x").is_err());
+            assert_eq!(db.update_user(&user).await.unwrap(), 1);
+
+            let found = db.find_user("E1234").await.unwrap().unwrap();
+            assert_eq!(found.id, user.id);
+            assert_eq!(found.status, UserStatus::Suspended);
+            assert_eq!(db.find_user(&user.id.to_string()).await.unwrap().unwrap().name, "Jean Dupont");
+            assert_eq!(db.find_user_by_uid("0C0D").await.unwrap().unwrap().name, "Alice");
+            assert!(db.find_user("Inconnu").await.unwrap().is_none());
+
+            let mut alice = db.find_user("Alice").await.unwrap().unwrap();
+            alice.set("employee_number", "E1234").unwrap();
+            assert!(db.update_user(&alice).await.is_err());
+
+            let names: Vec<String> = db.list_users().await.unwrap().into_iter().map(|user| user.name).collect();
+            assert_eq!(names, ["Alice", "Jean Dupont"]);
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_card_lifecycle() {
+            let temp = TempDb::new("cards");
+            let db = temp.db().await;
+            db.adduser("0A0B".to_string(), "Jean Dupont").await.unwrap();
+            let user = db.find_user("Jean Dupont").await.unwrap().unwrap();
+            assert_eq!(user.uuid.as_deref(), Some("0A0B"));
+
+            // Carte perdue : refusée, puis remplacée
+            assert_eq!(db.set_card_state("0A0B", CardState::Lost).await.unwrap(), 1);
+            assert_eq!(db.set_card_state("FFFF", CardState::Lost).await.unwrap(), 0);
+            let now = parse_timestamp("2024-06-14 10:00:00").unwrap();
+            assert_eq!(db.find_card("0A0B").await.unwrap().unwrap().effective_state(now), CardState::Lost);
+            assert!(db.find_user("Jean Dupont").await.unwrap().unwrap().uuid.is_none());
+            db.add_card(user.id, "0C0D", Some("2000-01-01")).await.unwrap();
+            assert!(db.add_card(user.id, "0C0D", None).await.is_err());
+
+            let cards = db.cards_of_user(user.id).await.unwrap();
+            assert_eq!(cards.iter().map(|card| card.uid.as_str()).collect::<Vec<_>>(), ["0A0B", "0C0D"]);
+            assert_eq!(cards[1].state, CardState::Active);
+            assert_eq!(cards[1].issued_at, db.today());
+            assert_eq!(cards[1].effective_state(now), CardState::Expired);
+            // La carte reste valable jusqu'à la fin de son jour d'expiration, à l'heure du site
+            db.set_card_expiry("0C0D", Some("2024-06-14")).await.unwrap();
+            let card = db.find_card("0C0D").await.unwrap().unwrap();
+            assert_eq!(card.effective_state(parse_timestamp("2024-06-14 23:59:59").unwrap()), CardState::Active);
+            assert_eq!(card.effective_state(parse_timestamp("2024-06-15 00:00:00").unwrap()), CardState::Expired);
+            db.set_card_expiry("0C0D", None).await.unwrap();
+            assert_eq!(db.find_card("0C0D").await.unwrap().unwrap().effective_state(now), CardState::Active);
+
+            // Les passages restent rattachés à l'utilisateur, quelle que soit la carte
+            db.add_badge_event("0A0B", "Entrée", BadgeResult::Denied, Some("carte déclarée perdue")).await.unwrap();
+            assert_eq!(db.find_user_by_uid("0A0B").await.unwrap().unwrap().id, user.id);
+            assert_eq!(db.find_user_by_uid("0A0B").await.unwrap().unwrap().name, "Jean Dupont");
+
+            // L'utilisateur n'est supprimé qu'avec sa dernière carte
+            db.deluser("0A0B".to_string()).await.unwrap();
+            assert!(db.find_user("Jean Dupont").await.unwrap().is_some());
+            db.deluser("0C0D".to_string()).await.unwrap();
+            assert!(db.find_user("Jean Dupont").await.unwrap().is_none());
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_access_rules() {
+            let temp = TempDb::new("access");
+            let db = temp.db().await;
+            db.adduser("0A0B".to_string(), "Jean Dupont").await.unwrap();
+            let user = db.find_user("Jean Dupont").await.unwrap().unwrap();
+
+            db.add_zone("Serveurs").await.unwrap();
+            assert!(db.add_zone("Serveurs").await.is_err());
+            assert_eq!(db.set_door("ACS ACR122U", "Serveurs").await.unwrap(), 1);
+            assert_eq!(db.set_door("ACS", "Inconnue").await.unwrap(), 0);
+            assert_eq!(db.zone_of_reader("ACS ACR122U PICC Interface 00 00").await.unwrap().as_deref(), Some("Serveurs"));
+            assert!(db.zone_of_reader("Identiv uTrust").await.unwrap().is_none());
+
+            db.add_group("Informatique").await.unwrap();
+            assert!(db.schedules_for(user.id, "Serveurs").await.unwrap().is_empty());
+            assert_eq!(db.add_group_member("Informatique", user.id).await.unwrap(), 1);
+            assert!(db.add_group_member("Informatique", user.id).await.is_err());
+            assert_eq!(db.allow_zone("Informatique", "Serveurs").await.unwrap(), 1);
+            assert_eq!(db.allow_zone("Informatique", "Inconnue").await.unwrap(), 0);
+            assert_eq!(db.schedules_for(user.id, "Serveurs").await.unwrap()[0].group, "Informatique");
+
+            let groups = db.list_groups().await.unwrap();
+            assert_eq!(groups, [AccessGroup { name: String::from("Informatique"), members: vec![user.name.clone()], zones: vec![String::from("Serveurs")] }]);
+            assert_eq!(db.list_zones().await.unwrap()[0].doors, ["ACS ACR122U"]);
+
+            assert_eq!(db.revoke_zone("Informatique", "Serveurs").await.unwrap(), 1);
+            assert!(db.schedules_for(user.id, "Serveurs").await.unwrap().is_empty());
+            assert_eq!(db.remove_group_member("Informatique", user.id).await.unwrap(), 1);
+            assert_eq!(db.delete_door("ACS ACR122U").await.unwrap(), 1);
+            assert_eq!(db.delete_group("Informatique").await.unwrap(), 1);
+            assert_eq!(db.delete_zone("Serveurs").await.unwrap(), 1);
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_schedules() {
+            let temp = TempDb::new("schedules");
+            let db = temp.db().await;
+            db.adduser("0A0B".to_string(), "Jean Dupont").await.unwrap();
+            let user = db.find_user("Jean Dupont").await.unwrap().unwrap();
+            db.add_zone("Bureaux").await.unwrap();
+            db.add_group("Personnel").await.unwrap();
+            db.add_group_member("Personnel", user.id).await.unwrap();
+            db.allow_zone("Personnel", "Bureaux").await.unwrap();
+
+            assert_eq!(db.add_schedule("Personnel", &[0, 1, 2, 3, 4], 7 * 60, 20 * 60).await.unwrap(), 5);
+            assert_eq!(db.add_schedule("Personnel", &[0], 7 * 60, 20 * 60).await.unwrap(), 0);
+            assert_eq!(db.add_schedule("Inconnu", &[0], 7 * 60, 20 * 60).await.unwrap(), 0);
+            assert_eq!(db.set_schedule_exception("Personnel", "2025-12-26", Some(false)).await.unwrap(), 1);
+            assert_eq!(db.set_schedule_exception("Personnel", "2025-12-26", Some(true)).await.unwrap(), 1);
+            let holidays = [Holiday { day: String::from("2025-12-25"), summary: Some(String::from("Noël")) }];
+            assert_eq!(db.import_holidays("France", &holidays).await.unwrap(), 1);
+            assert_eq!(db.import_holidays("France", &holidays).await.unwrap(), 1);
+            assert_eq!(db.set_group_calendar("Personnel", Some("France")).await.unwrap(), 1);
+            assert_eq!(db.list_calendars().await.unwrap(), [HolidayCalendar { calendar: String::from("France"), days: 1 }]);
+            assert_eq!(db.list_holidays("France").await.unwrap(), holidays);
+
+            let schedule = db.schedules_for(user.id, "Bureaux").await.unwrap().remove(0);
+            assert_eq!(schedule, db.group_schedule("Personnel").await.unwrap().unwrap());
+            assert_eq!(schedule.windows.len(), 5);
+            assert_eq!(schedule.windows[4], TimeWindow { weekday: 4, start: 420, end: 1200 });
+            assert_eq!(schedule.exceptions, [(String::from("2025-12-26"), true)]);
+            assert_eq!(schedule.holidays, ["2025-12-25"]);
+
+            assert_eq!(db.set_schedule_exception("Personnel", "2025-12-26", None).await.unwrap(), 1);
+            assert_eq!(db.clear_schedule("Personnel").await.unwrap(), 5);
+            assert!(db.group_schedule("Inconnu").await.unwrap().is_none());
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_presence() {
+            let temp = TempDb::new("presence");
+            let db = temp.db().await;
+            db.adduser("0A0B".to_string(), "Jean Dupont").await.unwrap();
+            db.add_zone("Serveurs").await.unwrap();
+            assert_eq!(db.find_zone("Serveurs").await.unwrap().unwrap().passback, PassbackMode::Off);
+            assert_eq!(db.set_passback("Serveurs", PassbackMode::Hard, Some(720)).await.unwrap(), 1);
+            assert_eq!(db.set_passback("Inconnue", PassbackMode::Hard, None).await.unwrap(), 0);
+            let zone = db.find_zone("Serveurs").await.unwrap().unwrap();
+            assert_eq!((zone.passback, zone.passback_reset), (PassbackMode::Hard, Some(720)));
+            assert!(db.find_zone("Inconnue").await.unwrap().is_none());
+
+            assert_eq!(db.set_presence("0A0B", "Serveurs", true, "2024-06-14 08:00:00").await.unwrap(), 1);
+            assert_eq!(db.set_presence("0A0B", "Serveurs", false, "2024-06-14 12:00:00").await.unwrap(), 1);
+            assert_eq!(db.set_presence("0A0B", "Inconnue", true, "2024-06-14 12:00:00").await.unwrap(), 0);
+            let presence = db.presence("0A0B", "Serveurs").await.unwrap().unwrap();
+            assert_eq!((presence.inside, presence.since.as_str()), (false, "2024-06-14 12:00:00"));
+            assert_eq!(presence.holder.as_deref(), Some("Jean Dupont"));
+            assert!(db.occupants(None).await.unwrap().is_empty());
+
+            // Un visiteur est présenté avec son hôte ; la zone est pleine dès qu'elle atteint sa capacité
+            let host = db.find_user("Jean Dupont").await.unwrap().unwrap();
+            db.add_visitor_badge("V001").await.unwrap();
+            db.start_visit("Marie Curie", host.id, "2024-06-14 18:00:00").await.unwrap();
+            db.set_presence("V001", "Serveurs", true, &format_timestamp(db.clock.now())).await.unwrap();
+            assert_eq!(db.set_max_occupancy("Serveurs", Some(1)).await.unwrap(), 1);
+            let zone = db.find_zone("Serveurs").await.unwrap().unwrap();
+            assert_eq!((zone.occupancy, zone.max_occupancy), (1, Some(1)));
+            assert!(zone.is_full());
+
+            let occupants = db.occupants(Some("Serveurs")).await.unwrap();
+            assert_eq!(occupants.iter().map(|presence| presence.holder.as_deref()).collect::<Vec<_>>(), [Some("Marie Curie (visiteur de Jean Dupont)")]);
+            assert!(db.occupants(Some("Bureaux")).await.unwrap().is_empty());
+
+            // Une position plus ancienne que le délai de remise à zéro n'est plus comptée, comme pour l'anti-retour
+            db.set_presence("0A0B", "Serveurs", true, "2024-06-14 08:00:00").await.unwrap();
+            assert_eq!(db.find_zone("Serveurs").await.unwrap().unwrap().occupancy, 1);
+            assert_eq!(db.occupants(None).await.unwrap().len(), 1);
+            db.set_passback("Serveurs", PassbackMode::Hard, None).await.unwrap();
+            assert_eq!(db.list_zones().await.unwrap()[0].occupancy, 2);
+            assert_eq!(db.occupants(None).await.unwrap().len(), 2);
+            db.end_visit("V001").await.unwrap();
+            assert_eq!(db.set_max_occupancy("Serveurs", None).await.unwrap(), 1);
+            assert!(!db.find_zone("Serveurs").await.unwrap().unwrap().is_full());
+
+            assert_eq!(db.clear_presence("0A0B", Some("Bureaux")).await.unwrap(), 0);
+            assert_eq!(db.clear_presence("0A0B", None).await.unwrap(), 1);
+            assert!(db.presence("0A0B", "Serveurs").await.unwrap().is_none());
+
+            // La position d'une carte supprimée est oubliée avec elle
+            db.set_presence("0A0B", "Serveurs", true, "2024-06-14 13:00:00").await.unwrap();
+            db.deluser("0A0B".to_string()).await.unwrap();
+            assert!(db.presence("0A0B", "Serveurs").await.unwrap().is_none());
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_encrypt() {
+            let temp = TempDb::new("encrypt");
+            let path = temp.path.as_str();
+            let key = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+            let db = temp.db().await;
+            db.adduser("0A0B0C0D".to_string(), "Jean Dupont").await.unwrap();
+            sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&db.dboconnector).await.unwrap();
+            db.close().await;
+            assert!(fs::read(path).unwrap().windows(11).any(|window| window == b"Jean Dupont"));
+
+            DboManager::encrypt(path, key).await.unwrap();
+            assert!(!fs::read(path).unwrap().windows(11).any(|window| window == b"Jean Dupont"));
+            assert!(!fs::read(path).unwrap().windows(8).any(|window| window == b"0A0B0C0D"));
+            // Sans la clé, ou avec une autre, la base est illisible
+            assert!(DboManager::open(path, None).await.is_err());
+            assert!(DboManager::open(path, Some(&key.replace('0', "f"))).await.is_err());
+            assert!(DboManager::encrypt(path, key).await.is_err());
+
+            let db = DboManager::new(path, Some(key)).await.unwrap();
+            assert_eq!(db.find_user_by_uid("0A0B0C0D").await.unwrap().unwrap().name, "Jean Dupont");
+            assert!(db.migration_status().await.unwrap().iter().all(|migration| migration.applied));
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_audit_log() {
+            let temp = TempDb::new("audit_log");
+            let path = temp.path.as_str();
+            let db = temp.db().await;
+            let first = db.add_audit_entry("admin", "zone add", "Serveurs", None, Some(r#"{"doors":["Baie"]}"#)).await.unwrap();
+            assert_eq!((first.id, first.prev_hash.as_str()), (1, GENESIS));
+            let second = db.add_audit_entry("admin", "zone del", "Serveurs", Some(r#"{"doors":["Baie"]}"#), None).await.unwrap();
+            assert_eq!(second.prev_hash, first.hash);
+            // Réglage d'un lecteur et suppression d'une porte, avec l'état précédent
+            let third = db.add_audit_entry("admin", "reader picc", "ACS ACR122U", Some(r#"{"picc":"FF"}"#), Some(r#"{"picc":"8F"}"#)).await.unwrap();
+            assert_eq!((third.prev_hash.as_str(), third.before.as_deref(), third.after.as_deref()), (second.hash.as_str(), Some(r#"{"picc":"FF"}"#), Some(r#"{"picc":"8F"}"#)));
+            let fourth = db.add_audit_entry("admin", "door del", "ACS ACR122U", Some(r#"{"zone":"Serveurs"}"#), None).await.unwrap();
+            assert_eq!((fourth.before.as_deref(), fourth.after.as_deref()), (Some(r#"{"zone":"Serveurs"}"#), None));
+            let entries = db.audit_entries().await.unwrap();
+            assert_eq!(entries, [first, second, third, fourth]);
+            assert!(verify(&entries).is_empty());
+
+            // Le journal refuse les modifications et les suppressions
+            assert!(sqlx::query("UPDATE audit_log SET actor = 'autre' WHERE id = 2").execute(&db.dboconnector).await.is_err());
+            assert!(sqlx::query("DELETE FROM audit_log WHERE id = 1").execute(&db.dboconnector).await.is_err());
+            assert_eq!(db.audit_entries().await.unwrap().len(), 4);
+            // Les requêtes refusées restent dans le cache des connexions : l'altération passe par une nouvelle connexion
+            db.close().await;
+            let db = DboManager::open(path, None).await.unwrap();
+
+            // Une fois les déclencheurs contournés, la vérification relève l'altération
+            sqlx::query("DROP TRIGGER audit_log_no_update").execute(&db.dboconnector).await.unwrap();
+            sqlx::query("UPDATE audit_log SET actor = 'autre' WHERE id = 1").execute(&db.dboconnector).await.unwrap();
+            assert_eq!(verify(&db.audit_entries().await.unwrap()), [AuditIssue::Modified(1)]);
+            sqlx::query("DROP TRIGGER audit_log_no_delete").execute(&db.dboconnector).await.unwrap();
+            sqlx::query("DELETE FROM audit_log WHERE id = 1").execute(&db.dboconnector).await.unwrap();
+            assert_eq!(verify(&db.audit_entries().await.unwrap()), [AuditIssue::Unchained(2)]);
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_pins() {
+            let temp = TempDb::new("pins");
+            let db = temp.db().await;
+            db.adduser("0A0B".to_string(), "Jean Dupont").await.unwrap();
+            let user = db.find_user("Jean Dupont").await.unwrap().unwrap();
+            assert!(db.pin_hash(user.id).await.unwrap().is_none());
+            assert_eq!(db.set_pin(user.id, Some("$argon2id$empreinte")).await.unwrap(), 1);
+            assert_eq!(db.pin_hash(user.id).await.unwrap().as_deref(), Some("$argon2id$empreinte"));
+            assert_eq!(db.set_pin(user.id + 1, Some("$argon2id$empreinte")).await.unwrap(), 0);
+
+            db.add_pin_failure(user.id, "ACS ACR122U").await.unwrap();
+            db.add_pin_failure(user.id, "ACS ACR122U").await.unwrap();
+            assert_eq!(db.pin_failures(user.id, 15).await.unwrap(), 2);
+            // Les échecs anciens ne comptent plus
+            sqlx::query("UPDATE pin_failures SET failed_at = '2000-01-01 00:00:00' WHERE id = 1").execute(&db.dboconnector).await.unwrap();
+            assert_eq!(db.pin_failures(user.id, 15).await.unwrap(), 1);
+            assert_eq!(db.clear_pin_failures(user.id).await.unwrap(), 2);
+            assert_eq!(db.pin_failures(user.id, 15).await.unwrap(), 0);
+
+            assert_eq!(db.set_pin(user.id, None).await.unwrap(), 1);
+            assert!(db.pin_hash(user.id).await.unwrap().is_none());
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_enrollment_tokens() {
+            let temp = TempDb::new("enrollment_tokens");
+            let db = temp.db().await;
+            db.add_enrollment_token("00ff", 10).await.unwrap();
+            db.add_enrollment_token("11ee", 0).await.unwrap();
+            let stored: String = sqlx::query_scalar("SELECT token_hash FROM enrollment_tokens WHERE id = 1").fetch_one(&db.dboconnector).await.unwrap();
+            assert_ne!(stored, "00ff");
+
+            // Un jeton n'est accepté qu'une fois, et jamais après son expiration
+            assert!(db.redeem_enrollment_token("00FF").await.unwrap());
+            assert!(!db.redeem_enrollment_token("00ff").await.unwrap());
+            assert!(!db.redeem_enrollment_token("11ee").await.unwrap());
+            assert!(!db.redeem_enrollment_token("22dd").await.unwrap());
+            db.close().await;
+        }
+
+        #[tokio::test]
+        async fn test_visitors() {
+            let temp = TempDb::new("visitors");
+            let db = temp.db().await;
+            db.adduser("0A0B".to_string(), "Jean Dupont").await.unwrap();
+            let host = db.find_user("Jean Dupont").await.unwrap().unwrap();
+            db.add_visitor_badge("V001").await.unwrap();
+            assert!(db.add_visitor_badge("V001").await.is_err());
+            assert!(db.is_visitor_badge("V001").await.unwrap());
+            assert!(!db.is_visitor_badge("0A0B").await.unwrap());
+
+            let visit = db.start_visit("Marie Curie", host.id, "2024-06-14 18:00:00").await.unwrap().unwrap();
+            assert_eq!(visit.badge_uid, "V001");
+            assert_eq!(visit.host.as_deref(), Some("Jean Dupont"));
+            assert!(!visit.is_expired(parse_timestamp("2024-06-14 17:59:00").unwrap()));
+            assert!(visit.is_expired(parse_timestamp("2024-06-14 18:00:00").unwrap()));
+            assert_eq!(db.open_visit("V001").await.unwrap(), Some(visit.clone()));
+            assert_eq!(db.open_visits().await.unwrap(), [visit]);
+            assert_eq!(db.list_visitor_badges().await.unwrap(), [VisitorBadge { uid: String::from("V001"), visitor: Some(String::from("Marie Curie")) }]);
+
+            // Le stock est épuisé, et un badge prêté ne peut pas en être retiré
+            assert!(db.start_visit("Pierre Curie", host.id, "2024-06-14 18:00:00").await.unwrap().is_none());
+            assert_eq!(db.delete_visitor_badge("V001").await.unwrap(), 0);
+
+            assert_eq!(db.end_visit("V001").await.unwrap(), 1);
+            assert_eq!(db.end_visit("V001").await.unwrap(), 0);
+            assert!(db.open_visit("V001").await.unwrap().is_none());
+            assert_eq!(db.list_visitor_badges().await.unwrap(), [VisitorBadge { uid: String::from("V001"), visitor: None }]);
+            assert_eq!(db.delete_visitor_badge("V001").await.unwrap(), 1);
+            db.close().await;
         }
 
         #[tokio::test]
-        async fn test_uuid_exist_existing_uuid() {
+        async fn test_find_user_by_existing_uid() {
             let existing_uuid = "A4504FA11A8406263646566676869";
             let expected_name = "tonton";
-            let result = DboManager::uuid_exist(existing_uuid).await;
+            let (_temp, db) = create_db("find_user").await;
+            let result = db.find_user_by_uid(existing_uuid).await;
             assert!(result.is_ok());
-            assert_eq!(result.unwrap(), expected_name);
+            assert_eq!(result.unwrap().unwrap().name, expected_name);
         }
 
 
 
-        async fn test_adduser() {
+        async fn test_adduser(db: &DboManager) {
             let _uuid = "FFFFFFFFFFFFFFFFFFFFFFFFFFFF";
             let _name = "MIKU";
 
-            let result = DboManager::adduser(_uuid.parse().unwrap(), _name).await;
+            let result = db.adduser(_uuid.parse().unwrap(), _name).await;
             assert!(result.is_ok());
             assert_eq!(result.unwrap(), 1);
         }
 
 
-        async fn test_deluser() {
+        async fn test_deluser(db: &DboManager) {
             let _uuid = "FFFFFFFFFFFFFFFFFFFFFFFFFFFF";
 
-            let result = DboManager::deluser(_uuid.parse().unwrap()).await;
+            let result = db.deluser(_uuid.parse().unwrap()).await;
             assert!(result.is_ok());
             assert_eq!(result.unwrap(), 1);
         }
 
         #[tokio::test]
         async fn test_add_and_del_user() {
-            test_adduser().await;
-            test_deluser().await;
+            let (_temp, db) = create_db("add_del_user").await;
+            test_adduser(&db).await;
+            test_deluser(&db).await;
         }
 
 
         #[tokio::test]
         async fn test_export_users_to_json_file() {
-            let file_path = "export_test_users.json";
-            let result = DboManager::export_users_to_json(file_path).await;
+            let (temp, db) = create_db("export").await;
+            let file_path = format!("{}.json", temp.path);
+            let result = db.export_users_to_json(&file_path).await;
             assert!(result.is_ok());
 
-            let json_data = fs::read_to_string(file_path).unwrap();
+            let json_data = fs::read_to_string(&file_path).unwrap();
             println!("{}", json_data);
+            fs::remove_file(&file_path).unwrap();
+            assert_eq!(json_data, r#"[{"name":"tonton","uuid":"A4504FA11A8406263646566676869"},{"name":"Yu","uuid":"D458D1A0FD8406263646566676869"}]"#);
 
         }
 
@@ -0,0 +1,229 @@
+/// Module `emulation` permet au lecteur de se présenter comme une étiquette NFC Forum de type 4
+/// servant un message NDEF, par exemple un lien d'enrôlement à usage unique lu par un téléphone.
+pub mod emulation {
+    /// Nom de l'application NDEF de type 4 (NFC Forum Type 4 Tag, version 2.0).
+    const NDEF_APPLICATION: [u8; 7] = [0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01];
+    /// Identifiant du fichier Capability Container.
+    const CC_FILE: [u8; 2] = [0xE1, 0x03];
+    /// Identifiant du fichier NDEF.
+    const NDEF_FILE: [u8; 2] = [0xE1, 0x04];
+    /// Taille maximale d'une réponse READ BINARY annoncée dans le Capability Container.
+    const MAX_READ: u16 = 0x3B;
+
+    /// Préfixes d'URI abrégés par les enregistrements NDEF de type `U`.
+    const URI_PREFIXES: [(&str, u8); 4] = [
+        ("http://www.", 0x01),
+        ("https://www.", 0x02),
+        ("http://", 0x03),
+        ("https://", 0x04),
+    ];
+
+    /// Enregistrement NDEF bien connu (TNF 0x01).
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct NdefRecord {
+        pub record_type: Vec<u8>,
+        pub payload: Vec<u8>,
+    }
+
+    impl NdefRecord {
+        /// Crée un enregistrement URI, en abrégeant le préfixe lorsque c'est possible.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let record = NdefRecord::uri("https://example.com");
+        /// assert_eq!(record.payload[0], 0x04);
+        /// ```
+        pub fn uri(uri: &str) -> NdefRecord {
+            let (rest, code) = URI_PREFIXES
+                .iter()
+                .find_map(|(prefix, code)| uri.strip_prefix(prefix).map(|rest| (rest, *code)))
+                .unwrap_or((uri, 0x00));
+
+            let mut payload = vec![code];
+            payload.extend_from_slice(rest.as_bytes());
+            NdefRecord { record_type: b"U".to_vec(), payload }
+        }
+    }
+
+    /// Message NDEF composé d'un ou plusieurs enregistrements.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct NdefMessage {
+        pub records: Vec<NdefRecord>,
+    }
+
+    impl NdefMessage {
+        /// Encode le message au format binaire NDEF.
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            let last = self.records.len().saturating_sub(1);
+
+            for (index, record) in self.records.iter().enumerate() {
+                let short = record.payload.len() < 256;
+                let mut header = 0x01; // TNF: NFC Forum well-known type
+                if index == 0 {
+                    header |= 0x80; // MB
+                }
+                if index == last {
+                    header |= 0x40; // ME
+                }
+                if short {
+                    header |= 0x10; // SR
+                }
+
+                bytes.push(header);
+                bytes.push(record.record_type.len() as u8);
+                if short {
+                    bytes.push(record.payload.len() as u8);
+                } else {
+                    bytes.extend_from_slice(&(record.payload.len() as u32).to_be_bytes());
+                }
+                bytes.extend_from_slice(&record.record_type);
+                bytes.extend_from_slice(&record.payload);
+            }
+
+            bytes
+        }
+    }
+
+    /// Fichier sélectionné par le lecteur distant.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum SelectedFile {
+        None,
+        Application,
+        CapabilityContainer,
+        Ndef,
+    }
+
+    /// Structure `Type4Tag` répond aux APDU d'un téléphone comme une étiquette NFC Forum de type 4
+    /// en lecture seule.
+    pub struct Type4Tag {
+        capability_container: Vec<u8>,
+        ndef_file: Vec<u8>,
+        selected: SelectedFile,
+        ndef_read: bool,
+    }
+
+    impl Type4Tag {
+        /// Prépare l'étiquette servant le message donné.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let mut tag = Type4Tag::new(&NdefMessage { records: vec![NdefRecord::uri("https://example.com")] });
+        /// ```
+        pub fn new(message: &NdefMessage) -> Type4Tag {
+            let ndef = message.to_bytes();
+            let mut ndef_file = (ndef.len() as u16).to_be_bytes().to_vec();
+            ndef_file.extend(ndef);
+
+            let max_size = (ndef_file.len() as u16).to_be_bytes();
+            let mut capability_container = vec![
+                0x00, 0x0F, // CCLEN
+                0x20, // Mapping version 2.0
+            ];
+            capability_container.extend_from_slice(&MAX_READ.to_be_bytes()); // MLe
+            capability_container.extend_from_slice(&[0x00, 0x34]); // MLc
+            capability_container.extend_from_slice(&[0x04, 0x06, NDEF_FILE[0], NDEF_FILE[1], max_size[0], max_size[1]]);
+            capability_container.extend_from_slice(&[
+                0x00, // Read access: granted
+                0xFF, // Write access: denied
+            ]);
+
+            Type4Tag { capability_container, ndef_file, selected: SelectedFile::None, ndef_read: false }
+        }
+
+        /// Indique si le message NDEF a été lu en entier par le lecteur distant.
+        pub fn ndef_read(&self) -> bool {
+            self.ndef_read
+        }
+
+        /// Traite une APDU de commande reçue du lecteur distant et renvoie la réponse.
+        ///
+        /// # Arguments
+        ///
+        /// * `capdu` - L'APDU de commande.
+        ///
+        /// # Retourne
+        ///
+        /// * `Vec<u8>` - L'APDU de réponse, mot de statut inclus.
+        pub fn process(&mut self, capdu: &[u8]) -> Vec<u8> {
+            match capdu {
+                // SELECT by name
+                [0x00, 0xA4, 0x04, 0x00, length, name @ ..] if name.get(..*length as usize) == Some(&NDEF_APPLICATION[..]) => {
+                    self.selected = SelectedFile::Application;
+                    vec![0x90, 0x00]
+                }
+                // SELECT by file identifier
+                [0x00, 0xA4, 0x00, 0x0C, 0x02, file @ ..] if self.selected != SelectedFile::None => {
+                    match file.get(..2) {
+                        Some(id) if id == CC_FILE => self.selected = SelectedFile::CapabilityContainer,
+                        Some(id) if id == NDEF_FILE => self.selected = SelectedFile::Ndef,
+                        _ => return vec![0x6A, 0x82],
+                    }
+                    vec![0x90, 0x00]
+                }
+                // READ BINARY
+                [0x00, 0xB0, offset_high, offset_low, length] => {
+                    let file = match self.selected {
+                        SelectedFile::CapabilityContainer => &self.capability_container,
+                        SelectedFile::Ndef => &self.ndef_file,
+                        _ => return vec![0x69, 0x86],
+                    };
+                    let offset = u16::from_be_bytes([*offset_high, *offset_low]) as usize;
+                    if offset > file.len() {
+                        return vec![0x6B, 0x00];
+                    }
+                    let end = (offset + (*length as usize).min(MAX_READ as usize)).min(file.len());
+
+                    let mut response = file[offset..end].to_vec();
+                    if self.selected == SelectedFile::Ndef && offset > 0 && end == file.len() {
+                        self.ndef_read = true;
+                    }
+                    response.extend_from_slice(&[0x90, 0x00]);
+                    response
+                }
+                [0x00, 0xA4, ..] => vec![0x6A, 0x82],
+                _ => vec![0x6D, 0x00],
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_uri_record_encoding() {
+            let message = NdefMessage { records: vec![NdefRecord::uri("https://example.com")] };
+            let mut expected = vec![0xD1, 0x01, 0x0C, b'U', 0x04];
+            expected.extend_from_slice(b"example.com");
+            assert_eq!(message.to_bytes(), expected);
+        }
+
+        #[test]
+        fn test_type4_tag_session() {
+            let message = NdefMessage { records: vec![NdefRecord::uri("https://example.com")] };
+            let ndef = message.to_bytes();
+            let mut tag = Type4Tag::new(&message);
+
+            assert_eq!(tag.process(&[0x00, 0xA4, 0x00, 0x0C, 0x02, 0xE1, 0x03]), [0x6A, 0x82]);
+            assert_eq!(tag.process(&[0x00, 0xA4, 0x04, 0x00, 0x07, 0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01, 0x00]), [0x90, 0x00]);
+            assert_eq!(tag.process(&[0x00, 0xA4, 0x00, 0x0C, 0x02, 0xE1, 0x03]), [0x90, 0x00]);
+
+            let cc = tag.process(&[0x00, 0xB0, 0x00, 0x00, 0x0F]);
+            assert_eq!(cc.len(), 17);
+            assert_eq!(&cc[11..13], [0x00, ndef.len() as u8 + 2]);
+
+            assert_eq!(tag.process(&[0x00, 0xA4, 0x00, 0x0C, 0x02, 0xE1, 0x04]), [0x90, 0x00]);
+            assert_eq!(tag.process(&[0x00, 0xB0, 0x00, 0x00, 0x02]), [0x00, ndef.len() as u8, 0x90, 0x00]);
+            assert!(!tag.ndef_read());
+
+            let body = tag.process(&[0x00, 0xB0, 0x00, 0x02, ndef.len() as u8]);
+            assert_eq!(&body[..ndef.len()], ndef.as_slice());
+            assert!(tag.ndef_read());
+
+            assert_eq!(tag.process(&[0x00, 0xD6, 0x00, 0x00, 0x01, 0x00]), [0x6D, 0x00]);
+        }
+    }
+}
@@ -0,0 +1,377 @@
+/// Module `export` produit les relevés de présence destinés aux ressources humaines,
+/// dans un format choisi parmi ceux enregistrés (CSV, XLSX), et le rapport d'évacuation des personnes présentes.
+pub mod export {
+    use serde_json::{json, Value};
+    use std::collections::{BTreeMap, BTreeSet};
+    use rust_xlsxwriter::Workbook;
+    use std::io::{self, Write};
+    use crate::config::config::Config;
+    use crate::dbo::dbo::{BadgeRecord, Presence};
+    use crate::timesheet::timesheet::{daily_seconds, format_day, format_hour, format_time, pair_sessions, punches, SECONDS_PER_DAY};
+
+    /// Cellule d'un tableau exporté.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Cell {
+        Text(String),
+        Number(f64),
+    }
+
+    /// Ligne du relevé de présence : un utilisateur sur une journée.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct AttendanceRow {
+        pub user: String,
+        pub day: i64,
+        /// Première entrée de la journée.
+        pub first_in: Option<i64>,
+        /// Dernière sortie de la journée, `None` si elle n'a pas été badgée.
+        pub last_out: Option<i64>,
+        /// Secondes travaillées dans la journée.
+        pub worked: i64,
+        /// Secondes travaillées au-delà de la journée de travail.
+        pub overtime: i64,
+    }
+
+    impl AttendanceRow {
+        /// En-tête des colonnes du relevé.
+        pub const HEADER: [&'static str; 6] = ["Utilisateur", "Jour", "Première entrée", "Dernière sortie", "Heures", "Heures supplémentaires"];
+
+        /// Cellules de la ligne, les durées étant exprimées en heures décimales.
+        pub fn cells(&self) -> Vec<Cell> {
+            vec![
+                Cell::Text(self.user.clone()),
+                Cell::Text(format_day(self.day)),
+                Cell::Text(self.first_in.map(format_hour).unwrap_or_default()),
+                Cell::Text(self.last_out.map(format_hour).unwrap_or_default()),
+                Cell::Number(hours(self.worked)),
+                Cell::Number(hours(self.overtime)),
+            ]
+        }
+    }
+
+    /// Convertit des secondes en heures, arrondies au centième.
+    fn hours(seconds: i64) -> f64 {
+        (seconds as f64 / 36.0).round() / 100.0
+    }
+
+    /// Construit le relevé de présence, une ligne par utilisateur et par jour travaillé.
+    ///
+    /// Les passages sont associés en sessions comme pour les feuilles de temps ; une journée avec
+    /// une entrée mais sans sortie apparaît avec une dernière sortie vide.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - Les passages, à partir de la veille du premier jour pour les sessions qui traversent minuit.
+    /// * `config` - La configuration, qui donne le rôle des lecteurs et les règles de calcul.
+    /// * `from` - Le premier jour du relevé.
+    /// * `to` - Le dernier jour du relevé.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// let (from, to) = parse_month("2024-06").unwrap();
+    /// let rows = attendance_rows(&records, &config, from, to);
+    /// ```
+    pub fn attendance_rows(records: &[BadgeRecord], config: &Config, from: i64, to: i64) -> Vec<AttendanceRow> {
+        let mut users: BTreeMap<&str, Vec<BadgeRecord>> = BTreeMap::new();
+        for record in records {
+            if let Some(user) = &record.user_name {
+                users.entry(user).or_default().push(record.clone());
+            }
+        }
+
+        let count = usize::try_from(to - from + 1).unwrap_or_default();
+        let daily = config.timesheet.daily_minutes as i64 * 60;
+        let mut rows = Vec::new();
+        for (user, records) in users {
+            let sessions = pair_sessions(&punches(&records, config), !config.has_exit_reader(), &config.timesheet);
+            for (day, worked) in (from..).zip(daily_seconds(&sessions, from, count)) {
+                let on_day = |time: &i64| time.div_euclid(SECONDS_PER_DAY) == day;
+                let first_in = sessions.iter().map(|session| session.start).filter(on_day).min();
+                let last_out = sessions.iter().filter_map(|session| session.end).filter(on_day).max();
+                if worked == 0 && first_in.is_none() {
+                    continue;
+                }
+                rows.push(AttendanceRow { user: user.to_string(), day, first_in, last_out, worked, overtime: (worked - daily).max(0) });
+            }
+        }
+        rows
+    }
+
+    /// Regroupe les personnes présentes par zone, dans l'ordre des zones.
+    fn by_zone(occupants: &[Presence]) -> BTreeMap<&str, Vec<&Presence>> {
+        let mut zones: BTreeMap<&str, Vec<&Presence>> = BTreeMap::new();
+        for presence in occupants {
+            zones.entry(&presence.zone).or_default().push(presence);
+        }
+        zones
+    }
+
+    /// Nombre de personnes distinctes présentes, une carte pouvant être comptée dans plusieurs zones imbriquées.
+    fn people(occupants: &[Presence]) -> usize {
+        occupants.iter().map(|presence| &presence.card_uid).collect::<BTreeSet<_>>().len()
+    }
+
+    /// Rédige le rapport d'évacuation imprimable : les personnes présentes par zone, avec une case à cocher pour l'appel.
+    ///
+    /// # Arguments
+    ///
+    /// * `occupants` - Les positions des cartes présentes, triées par zone.
+    /// * `time` - L'heure locale du site à laquelle le rapport est établi.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// print!("{}", muster_text(&db.occupants(None).await?, clock.now()));
+    /// ```
+    pub fn muster_text(occupants: &[Presence], time: i64) -> String {
+        let mut text = format!("Rapport d'évacuation du {} : {} personne(s) présente(s)\n", format_time(time), people(occupants));
+        for (zone, present) in by_zone(occupants) {
+            text.push_str(&format!("\n{} ({})\n", zone, present.len()));
+            for presence in present {
+                let holder = presence.holder.as_deref().unwrap_or("titulaire inconnu");
+                let since = presence.since.get(..16).unwrap_or(&presence.since);
+                text.push_str(&format!("  [ ] {:<40} entré le {}  carte {}\n", holder, since, presence.card_uid));
+            }
+        }
+        text
+    }
+
+    /// Construit le rapport d'évacuation au format JSON, pour un affichage ou un envoi automatisé.
+    pub fn muster_json(occupants: &[Presence], time: i64) -> Value {
+        let zones: Vec<Value> = by_zone(occupants)
+            .into_iter()
+            .map(|(zone, present)| {
+                let occupants: Vec<Value> = present
+                    .iter()
+                    .map(|presence| json!({"card_uid": presence.card_uid, "holder": presence.holder, "since": presence.since}))
+                    .collect();
+                json!({"zone": zone, "count": occupants.len(), "occupants": occupants})
+            })
+            .collect();
+        json!({"generated_at": format_time(time), "total": people(occupants), "zones": zones})
+    }
+
+    /// Écrit le rapport d'évacuation, en JSON si le fichier a l'extension `.json`, sinon en texte imprimable.
+    pub fn export_muster(occupants: &[Presence], time: i64, path: &str) -> io::Result<()> {
+        let content = if path.to_lowercase().ends_with(".json") {
+            serde_json::to_string_pretty(&muster_json(occupants, time))?
+        } else {
+            muster_text(occupants, time)
+        };
+        std::fs::write(path, content)
+    }
+
+    /// Trait `ExportFormat` écrit un tableau dans un format de fichier.
+    ///
+    /// Un nouveau format s'ajoute en implémentant ce trait et en l'enregistrant dans `formats`.
+    pub trait ExportFormat {
+        /// Extension des fichiers de ce format, sans le point.
+        fn extension(&self) -> &'static str;
+
+        /// Écrit le tableau, première ligne comprise.
+        ///
+        /// # Arguments
+        ///
+        /// * `rows` - Les lignes du tableau, en-tête compris.
+        /// * `out` - La destination.
+        fn write(&self, rows: &[Vec<Cell>], out: &mut dyn Write) -> io::Result<()>;
+    }
+
+    /// Formats d'export disponibles.
+    pub fn formats() -> Vec<Box<dyn ExportFormat>> {
+        vec![Box::new(Csv), Box::new(Xlsx)]
+    }
+
+    /// Choisit le format d'export d'après l'extension d'un fichier.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// let format = format_for("presence-2024-06.xlsx").unwrap();
+    /// ```
+    pub fn format_for(path: &str) -> Option<Box<dyn ExportFormat>> {
+        let (_, extension) = path.rsplit_once('.')?;
+        formats().into_iter().find(|format| format.extension().eq_ignore_ascii_case(extension))
+    }
+
+    /// Exporte le relevé de présence dans le format correspondant à l'extension du fichier.
+    ///
+    /// # Retourne
+    ///
+    /// * `io::Result<()>` - Une erreur `InvalidInput` si aucun format ne correspond à l'extension.
+    pub fn export_attendance(rows: &[AttendanceRow], path: &str) -> io::Result<()> {
+        let format = format_for(path).ok_or_else(|| {
+            let extensions: Vec<&str> = formats().iter().map(|format| format.extension()).collect();
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Format non pris en charge, extensions possibles: {}", extensions.join(", ")))
+        })?;
+
+        let mut table = vec![AttendanceRow::HEADER.iter().map(|title| Cell::Text(title.to_string())).collect()];
+        table.extend(rows.iter().map(AttendanceRow::cells));
+
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        format.write(&table, &mut file)?;
+        file.flush()
+    }
+
+    /// Format CSV (RFC 4180), séparé par des virgules.
+    ///
+    /// Un texte commençant par `=`, `+`, `-`, `@`, une tabulation ou un retour chariot est précédé d'une apostrophe,
+    /// pour qu'un tableur ne l'interprète pas comme une formule.
+    pub struct Csv;
+
+    /// Caractères par lesquels un tableur reconnaît une formule.
+    const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+    impl ExportFormat for Csv {
+        fn extension(&self) -> &'static str {
+            "csv"
+        }
+
+        fn write(&self, rows: &[Vec<Cell>], out: &mut dyn Write) -> io::Result<()> {
+            for row in rows {
+                let fields: Vec<String> = row
+                    .iter()
+                    .map(|cell| match cell {
+                        Cell::Text(text) => {
+                            let text = if text.starts_with(FORMULA_PREFIXES) { format!("'{}", text) } else { text.clone() };
+                            if text.contains([',', '"', '\n', '\r']) {
+                                format!("\"{}\"", text.replace('"', "\"\""))
+                            } else {
+                                text
+                            }
+                        }
+                        Cell::Number(number) => number.to_string(),
+                    })
+                    .collect();
+                write!(out, "{}\r\n", fields.join(","))?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Format XLSX (Office Open XML), une seule feuille, produit par `rust_xlsxwriter`.
+    pub struct Xlsx;
+
+    impl ExportFormat for Xlsx {
+        fn extension(&self) -> &'static str {
+            "xlsx"
+        }
+
+        fn write(&self, rows: &[Vec<Cell>], out: &mut dyn Write) -> io::Result<()> {
+            let mut workbook = Workbook::new();
+            let sheet = workbook.add_worksheet().set_name("Présence").map_err(io::Error::other)?;
+            for (row, cells) in rows.iter().enumerate() {
+                for (column, cell) in cells.iter().enumerate() {
+                    // Le texte est toujours écrit comme une chaîne, jamais comme une formule
+                    match cell {
+                        Cell::Text(text) => sheet.write_string(row as u32, column as u16, text),
+                        Cell::Number(number) => sheet.write_number(row as u32, column as u16, *number),
+                    }
+                    .map_err(io::Error::other)?;
+                }
+            }
+            out.write_all(&workbook.save_to_buffer().map_err(io::Error::other)?)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::dbo::dbo::BadgeResult;
+        use crate::timesheet::timesheet::{parse_day, parse_timestamp};
+
+        fn record(timestamp: &str, user: Option<&str>) -> BadgeRecord {
+            BadgeRecord {
+                id: 0,
+                timestamp: timestamp.to_string(),
+                card_uid: String::from("0A0B"),
+                user_id: user.map(|_| 1),
+                user_name: user.map(str::to_string),
+                reader: String::from("Lecteur 00"),
+                result: BadgeResult::Granted,
+                reason: None,
+            }
+        }
+
+        #[test]
+        fn test_attendance_rows() {
+            let records = [
+                record("2024-06-03 08:00:00", Some("Alice")),
+                record("2024-06-03 12:00:00", Some("Alice")),
+                record("2024-06-03 13:00:00", Some("Alice")),
+                record("2024-06-03 17:30:00", Some("Alice")),
+                record("2024-06-04 09:00:00", Some("Alice")),
+                record("2024-06-04 09:30:00", None),
+            ];
+            let day = parse_day("2024-06-03").unwrap();
+            let rows = attendance_rows(&records, &Config::default(), day, day + 6);
+
+            assert_eq!(rows.len(), 2);
+            assert_eq!(rows[0].first_in, parse_timestamp("2024-06-03 08:00:00"));
+            assert_eq!(rows[0].last_out, parse_timestamp("2024-06-03 17:30:00"));
+            assert_eq!(rows[0].worked, 8 * 3600 + 1800);
+            assert_eq!(rows[0].overtime, 3600 + 1800);
+            assert_eq!(rows[1].last_out, None);
+            assert_eq!(rows[0].cells()[2..], [Cell::Text("08:00".into()), Cell::Text("17:30".into()), Cell::Number(8.5), Cell::Number(1.5)]);
+        }
+
+        #[test]
+        fn test_muster_report() {
+            let presence = |zone: &str, uid: &str, holder: Option<&str>| Presence {
+                card_uid: uid.to_string(),
+                zone: zone.to_string(),
+                inside: true,
+                since: String::from("2024-06-14 08:00:00"),
+                holder: holder.map(str::to_string),
+            };
+            let occupants = [
+                presence("Bâtiment", "0A0B", Some("Jean Dupont")),
+                presence("Bâtiment", "0C0D", None),
+                presence("Serveurs", "0A0B", Some("Jean Dupont")),
+            ];
+            let time = parse_timestamp("2024-06-14 10:30:00").unwrap();
+
+            let text = muster_text(&occupants, time);
+            assert!(text.starts_with("Rapport d'évacuation du 2024-06-14 10:30 : 2 personne(s) présente(s)\n"));
+            assert!(text.contains("\nBâtiment (2)\n  [ ] Jean Dupont"));
+            assert!(text.contains("titulaire inconnu"));
+            assert!(text.contains("\nServeurs (1)\n"));
+
+            let report = muster_json(&occupants, time);
+            assert_eq!(report["total"], 2);
+            assert_eq!(report["zones"][0]["count"], 2);
+            assert_eq!(report["zones"][1]["occupants"][0]["holder"], "Jean Dupont");
+            assert!(report["zones"][0]["occupants"][1]["holder"].is_null());
+            assert!(muster_json(&[], time)["zones"].as_array().unwrap().is_empty());
+        }
+
+        #[test]
+        fn test_csv_format() {
+            let mut out = Vec::new();
+            Csv.write(&[vec![Cell::Text("Dupont, \"Jean\"".into()), Cell::Number(7.25)]], &mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), "\"Dupont, \"\"Jean\"\"\",7.25\r\n");
+
+            // Un nom qui ressemble à une formule reste du texte dans le tableur
+            let mut out = Vec::new();
+            Csv.write(&[vec![Cell::Text("=HYPERLINK(\"http://x\")".into()), Cell::Text("@SUM(A1)".into()), Cell::Text("-1+1".into()), Cell::Number(-1.5)]], &mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), "\"'=HYPERLINK(\"\"http://x\"\")\",'@SUM(A1),'-1+1,-1.5\r\n");
+            assert_eq!(format_for("presence.XLSX").unwrap().extension(), "xlsx");
+            assert!(format_for("presence.pdf").is_none());
+        }
+
+        #[test]
+        fn test_xlsx_archive() {
+            let mut out = Vec::new();
+            Xlsx.write(&[vec![Cell::Text("=A & B".into()), Cell::Number(1.5)]], &mut out).unwrap();
+            let mut archive = zip::ZipArchive::new(io::Cursor::new(out)).unwrap();
+            let read = |archive: &mut zip::ZipArchive<io::Cursor<Vec<u8>>>, name: &str| {
+                io::read_to_string(archive.by_name(name).unwrap()).unwrap()
+            };
+            assert!(read(&mut archive, "xl/workbook.xml").contains("name=\"Présence\""));
+            let sheet = read(&mut archive, "xl/worksheets/sheet1.xml");
+            assert!(sheet.contains("<v>1.5</v>"));
+            assert!(!sheet.contains("<f>"));
+            assert!(read(&mut archive, "xl/sharedStrings.xml").contains("=A &amp; B"));
+        }
+    }
+}
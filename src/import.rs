@@ -0,0 +1,168 @@
+/// Module `import` lit les fichiers d'utilisateurs à importer, au format JSON produit par
+/// `export_users_to_json` ou au format CSV.
+pub mod import {
+    use serde::Deserialize;
+    use std::fmt;
+    use std::fs;
+    use std::io::{self, ErrorKind};
+
+    /// Nombre maximal de chiffres hexadécimaux d'un UID (16 octets).
+    const MAX_UID_LENGTH: usize = 32;
+
+    /// Utilisateur lu dans un fichier d'import.
+    #[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+    pub struct ImportedUser {
+        /// Position dans le fichier : numéro de ligne en CSV, d'entrée en JSON.
+        #[serde(skip)]
+        pub line: usize,
+        pub uuid: String,
+        pub name: String,
+    }
+
+    /// Problème détecté lors de la vérification d'un import.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum ImportIssue {
+        /// L'UID n'est pas un hexadécimal de 1 à 32 chiffres.
+        InvalidUid { line: usize, uuid: String },
+        /// Le nom est vide.
+        EmptyName { line: usize },
+        /// L'UID apparaît plusieurs fois dans le fichier, ou appartient déjà à un autre utilisateur.
+        DuplicateUid { line: usize, uuid: String, existing: String },
+        /// Le nom est porté par plusieurs utilisateurs : la carte ne peut être rattachée à aucun d'eux.
+        DuplicateName { line: usize, name: String },
+    }
+
+    impl fmt::Display for ImportIssue {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ImportIssue::InvalidUid { line, uuid } => write!(f, "ligne {} : UID invalide \"{}\"", line, uuid),
+                ImportIssue::EmptyName { line } => write!(f, "ligne {} : nom vide", line),
+                ImportIssue::DuplicateUid { line, uuid, existing } => write!(f, "ligne {} : l'UID {} est déjà attribué à {}", line, uuid, existing),
+                ImportIssue::DuplicateName { line, name } => write!(f, "ligne {} : le nom {} est porté par plusieurs utilisateurs", line, name),
+            }
+        }
+    }
+
+    /// Valide et normalise un UID : chiffres hexadécimaux, en majuscules.
+    ///
+    /// # Retourne
+    ///
+    /// * `Option<String>` - L'UID normalisé, ou `None` s'il est invalide.
+    pub fn normalize_uid(uuid: &str) -> Option<String> {
+        let uuid = uuid.trim();
+        let valid = !uuid.is_empty() && uuid.len() <= MAX_UID_LENGTH && uuid.chars().all(|c| c.is_ascii_hexdigit());
+        valid.then(|| uuid.to_ascii_uppercase())
+    }
+
+    /// Lit un fichier d'import, au format déterminé par son extension (`.json` ou `.csv`).
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// let users = read_users("users.json")?;
+    /// ```
+    pub fn read_users(path: &str) -> io::Result<Vec<ImportedUser>> {
+        let content = fs::read_to_string(path)?;
+        match path.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase()).as_deref() {
+            Some("json") => parse_json(&content),
+            Some("csv") => parse_csv(&content),
+            _ => Err(io::Error::new(ErrorKind::InvalidInput, "Format non pris en charge, extensions possibles: json, csv")),
+        }
+    }
+
+    /// Analyse une liste d'utilisateurs au format JSON : `[{"uuid": "...", "name": "..."}]`.
+    pub fn parse_json(content: &str) -> io::Result<Vec<ImportedUser>> {
+        let mut users: Vec<ImportedUser> = serde_json::from_str(content)?;
+        for (index, user) in users.iter_mut().enumerate() {
+            user.line = index + 1;
+        }
+        Ok(users)
+    }
+
+    /// Analyse une liste d'utilisateurs au format CSV, séparée par des virgules ou des points-virgules.
+    ///
+    /// Une première ligne d'en-tête `uuid,name` (dans n'importe quel ordre) est reconnue ; sans en-tête,
+    /// les colonnes sont l'UID puis le nom.
+    pub fn parse_csv(content: &str) -> io::Result<Vec<ImportedUser>> {
+        let separator = match content.lines().next() {
+            Some(first) if first.contains(';') && !first.contains(',') => ';',
+            _ => ',',
+        };
+
+        let mut lines = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).peekable();
+        let mut columns = (0, 1);
+        if let Some((_, header)) = lines.peek() {
+            let fields: Vec<String> = split_csv_line(header, separator).iter().map(|field| field.trim().to_ascii_lowercase()).collect();
+            let uuid = fields.iter().position(|field| field == "uuid" || field == "uid");
+            let name = fields.iter().position(|field| field == "name" || field == "nom");
+            if let (Some(uuid), Some(name)) = (uuid, name) {
+                columns = (uuid, name);
+                lines.next();
+            }
+        }
+
+        lines
+            .map(|(index, line)| {
+                let fields = split_csv_line(line, separator);
+                match (fields.get(columns.0), fields.get(columns.1)) {
+                    (Some(uuid), Some(name)) => Ok(ImportedUser { line: index + 1, uuid: uuid.clone(), name: name.trim().to_string() }),
+                    _ => Err(io::Error::new(ErrorKind::InvalidData, format!("ligne {} : colonnes manquantes", index + 1))),
+                }
+            })
+            .collect()
+    }
+
+    /// Découpe une ligne CSV en champs, en tenant compte des guillemets.
+    fn split_csv_line(line: &str, separator: char) -> Vec<String> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = line.trim_end_matches('\r').chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => quoted = !quoted,
+                c if c == separator && !quoted => fields.push(std::mem::take(&mut field)),
+                c => field.push(c),
+            }
+        }
+        fields.push(field);
+        fields
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_exported_json() {
+            let users = parse_json(r#"[{"name":"tonton","uuid":"A4504FA11A8406263646566676869"},{"name":"Yu","uuid":"0102"}]"#).unwrap();
+            assert_eq!(users.len(), 2);
+            assert_eq!(users[1], ImportedUser { line: 2, uuid: String::from("0102"), name: String::from("Yu") });
+        }
+
+        #[test]
+        fn test_parse_csv() {
+            let users = parse_csv("name;uuid\r\n\"Dupont; Jean\";0a0b\r\n\r\nYu;0102\r\n").unwrap();
+            assert_eq!(users[0], ImportedUser { line: 2, uuid: String::from("0a0b"), name: String::from("Dupont; Jean") });
+            assert_eq!(users[1].line, 4);
+
+            let users = parse_csv("0A0B,\"Le \"\"Chef\"\"\"\n").unwrap();
+            assert_eq!(users[0].name, "Le \"Chef\"");
+            assert!(parse_csv("0A0B\n").is_err());
+        }
+
+        #[test]
+        fn test_normalize_uid() {
+            assert_eq!(normalize_uid(" a4504fa1 "), Some(String::from("A4504FA1")));
+            assert_eq!(normalize_uid("A4504FA11A8406263646566676869"), Some(String::from("A4504FA11A8406263646566676869")));
+            assert_eq!(normalize_uid(""), None);
+            assert_eq!(normalize_uid("XYZ"), None);
+            assert_eq!(normalize_uid(&"F".repeat(33)), None);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+/// Module `keystore` gère la clé de chiffrement de la base de données, conservée dans un fichier à part
+/// pour qu'une copie de la base seule ne donne pas accès aux noms et aux identifiants des badges.
+pub mod keystore {
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+    use std::fs::{self, OpenOptions};
+    use std::io::{Error, ErrorKind, Write};
+
+    /// Longueur de la clé en octets : une clé AES-256 brute, utilisée sans dérivation par SQLCipher.
+    pub const KEY_LENGTH: usize = 32;
+
+    /// Lit la clé du keystore.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Le chemin du fichier, qui contient la clé en hexadécimal.
+    ///
+    /// # Retourne
+    ///
+    /// * `Result<String, Error>` - La clé en hexadécimal, ou une erreur si le fichier est absent ou ne contient pas une clé valide.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// let key = load_key("/etc/badgeage/db.key")?;
+    /// ```
+    pub fn load_key(path: &str) -> Result<String, Error> {
+        let key = fs::read_to_string(path)?.trim().to_lowercase();
+        if key.len() != KEY_LENGTH * 2 || !key.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(Error::new(ErrorKind::InvalidData, format!("le fichier {} ne contient pas une clé de {} octets en hexadécimal", path, KEY_LENGTH)));
+        }
+        Ok(key)
+    }
+
+    /// Crée un keystore contenant une nouvelle clé aléatoire, lisible par son seul propriétaire.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Le chemin du fichier ; un fichier existant n'est jamais écrasé.
+    ///
+    /// # Retourne
+    ///
+    /// * `Result<String, Error>` - La clé créée, en hexadécimal.
+    pub fn create_key(path: &str) -> Result<String, Error> {
+        let mut bytes = [0u8; KEY_LENGTH];
+        OsRng.fill_bytes(&mut bytes);
+        let key: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        writeln!(file, "{}", key)?;
+        Ok(key)
+    }
+
+    /// Valeur de `PRAGMA key` pour une clé brute : SQLCipher l'utilise telle quelle, sans dérivation.
+    pub fn pragma_value(key: &str) -> String {
+        format!("\"x'{}'\"", key)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_keystore() {
+            let path = "test_keystore.key";
+            let _ = fs::remove_file(path);
+            assert_eq!(load_key(path).unwrap_err().kind(), ErrorKind::NotFound);
+
+            let key = create_key(path).unwrap();
+            assert_eq!(key.len(), 64);
+            assert_eq!(load_key(path).unwrap(), key);
+            // Une clé existante n'est jamais remplacée
+            assert_eq!(create_key(path).unwrap_err().kind(), ErrorKind::AlreadyExists);
+            #[cfg(unix)]
+            assert_eq!(std::os::unix::fs::PermissionsExt::mode(&fs::metadata(path).unwrap().permissions()) & 0o777, 0o600);
+
+            fs::write(path, "0011").unwrap();
+            assert_eq!(load_key(path).unwrap_err().kind(), ErrorKind::InvalidData);
+            fs::remove_file(path).unwrap();
+
+            assert_eq!(pragma_value("00ff"), "\"x'00ff'\"");
+        }
+    }
+}
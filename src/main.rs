@@ -1,106 +1,1502 @@
+#![allow(clippy::module_inception)]
+
+mod access;
+mod audit;
+mod badge;
 mod card_operations;
+mod config;
+mod emulation;
+mod export;
+mod import;
+mod keystore;
+mod pin;
+mod pn532;
+mod schedule;
+mod timesheet;
+mod transport;
 mod utils;
 mod dbo;
 
-use tokio::io::{self, AsyncBufReadExt, BufReader};
-use tokio::sync::mpsc;
-use tokio::time::{self, Duration};
-use crate::card_operations::card_operations::CardManager;
-use crate::dbo::dbo::DboManager;
-use crate::utils::utils::decimals_to_hex;
-
-const TIME: Duration = Duration::from_secs(3);
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use tokio::io::{self, AsyncBufReadExt, BufReader, Lines, Stdin};
+use tokio::sync::mpsc::{self, Receiver};
+use crate::audit::audit::{current_actor, verify};
+use crate::badge::badge::{badge_card, monitor_readers, BadgeEvent, ReaderEvent};
+use crate::card_operations::card_operations::{CardManager, PiccParameters, ReaderManager, EMULATION_TIMEOUT};
+use crate::config::config::{Config, DATABASE_ENV, EmulationConfig, ReaderConfig, ReaderRole, CONFIG_PATH};
+use crate::dbo::dbo::{BadgeRecord, CardState, DboManager, PassbackMode, User, Visit};
+use crate::pin::pin::{hash_pin, is_valid_pin, PinPad};
+use crate::pn532::pn532::{BaudRate, Pn532};
+use crate::export::export::{attendance_rows, export_attendance, export_muster, muster_text};
+use crate::import::import::read_users;
+use crate::keystore::keystore::{create_key, load_key};
+use crate::schedule::schedule::{parse_local_time, parse_minutes, parse_weekdays, parse_window, read_ics, Clock, FixedClock};
+use crate::timesheet::timesheet::{format_day, format_duration, format_time, parse_day, parse_month, parse_week, Timesheet, SECONDS_PER_DAY, WEEKDAYS};
+use crate::transport::transport::ReplayTransport;
+use crate::emulation::emulation::{NdefMessage, NdefRecord, Type4Tag};
+use crate::utils::utils::{hex_to_bytes, rngtoken};
 
-/// La fonction `main` est asynchrone et utilise Tokio pour la gestion asynchrone des tâches. Elle crée une tâche asynchrone pour lire les cartes RFID périodiquement,
-/// vérifier leur UUID dans la base de données, et gérer les entrées utilisateur via l'entrée standard.
+/// Met à jour la liste des lecteurs connectés à partir d'un événement de statut et l'affiche.
 ///
-/// Les commandes disponibles pour l'utilisateur sont :
-/// - `add <nom_utilisateur>` : Ajoute un utilisateur avec le nom donné dans la base de données.
-/// - `reset` : Supprime l'utilisateur associé à l'UUID de la carte lue de la base de données.
-/// - `help` : Affiche les commandes disponibles.
-/// - `exit` ou `quit` : Arrête le programme.
-#[tokio::main]
-async fn main() {
-    let (tx, mut rx) = mpsc::channel(1);
+/// Retourne l'événement de badge s'il s'agit d'une lecture de carte.
+fn handle_reader_event(readers: &mut BTreeMap<String, ReaderConfig>, event: ReaderEvent) -> Option<BadgeEvent> {
+    match event {
+        ReaderEvent::Badge(event) => Some(event),
+        ReaderEvent::Connected(reader) => {
+            println!("Lecteur connecté : {} ({}, {})", reader.name, reader.role, reader.location);
+            readers.insert(reader.name.clone(), reader);
+            None
+        }
+        ReaderEvent::Disconnected(reader) => {
+            println!("Lecteur déconnecté : {} ({}, {})", reader.name, reader.role, reader.location);
+            readers.remove(&reader.name);
+            None
+        }
+    }
+}
 
-    tokio::spawn(async move {
-        let mut interval = time::interval(TIME);
-        loop {
-            interval.tick().await;
-            let card = CardManager{card: match CardManager::loadreader(){
-            Ok(a) => a,
-            Err(_) => {
-                continue;
+/// Délai laissé pour présenter une carte sur le poste d'enrôlement.
+const ENROLLMENT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// Attend le prochain badge présenté sur un poste d'enrôlement.
+///
+/// Si aucun poste d'enrôlement n'est configuré, n'importe quel lecteur est accepté. La ligne de commande reste lue
+/// pendant l'attente : les codes PIN sont remis aux lecteurs à double authentification, `cancel` abandonne l'attente
+/// et les autres commandes sont ignorées.
+///
+/// # Retourne
+///
+/// * `Option<BadgeEvent>` - Le badge présenté, ou `None` si l'attente est annulée ou dépasse `ENROLLMENT_TIMEOUT`.
+async fn next_enrollment_event(rx: &mut Receiver<ReaderEvent>, readers: &mut BTreeMap<String, ReaderConfig>, any_reader: bool, lines: &mut Lines<BufReader<Stdin>>, pinpad: &PinPad) -> Option<BadgeEvent> {
+    println!("En attente d'un badge sur le poste d'enrôlement ({} s, `cancel` pour annuler)", ENROLLMENT_TIMEOUT.as_secs());
+    let deadline = tokio::time::sleep(ENROLLMENT_TIMEOUT);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            event = rx.recv() => match handle_reader_event(readers, event?) {
+                Some(event) if any_reader || event.reader.role == ReaderRole::Enrollment => return Some(event),
+                _ => {}
+            },
+            line = lines.next_line() => {
+                let line = line.ok().flatten()?;
+                if pinpad.submit(line.trim()) {
+                    continue;
                 }
-            }};
+                match line.trim() {
+                    "cancel" => {
+                        println!("Attente du badge annulée");
+                        return None;
+                    }
+                    "" => {}
+                    command => eprintln!("Commande ignorée pendant l'attente d'un badge : {}", command),
+                }
+            }
+            _ = &mut deadline => {
+                eprintln!("Aucun badge présenté sur le poste d'enrôlement");
+                return None;
+            }
+        }
+    }
+}
+
+/// Sous-commandes de `reader`, qui séparent le nom du lecteur de leurs arguments.
+const READER_COMMANDS: [&str; 6] = ["info", "picc", "antenna", "timeout", "scan", "exchange"];
 
+/// Trouve le lecteur connecté désigné dans une commande.
+///
+/// Sans nom, le seul lecteur connecté est retenu, ou à défaut le seul poste d'enrôlement.
+///
+/// # Retourne
+///
+/// * `Result<String, String>` - Le nom PC/SC du lecteur, ou le message d'erreur à afficher si aucun lecteur
+///   ou plusieurs lecteurs correspondent.
+fn select_reader(readers: &BTreeMap<String, ReaderConfig>, selector: &str) -> Result<String, String> {
+    let candidates: Vec<&ReaderConfig> = if selector.is_empty() {
+        match readers.len() {
+            1 => readers.values().collect(),
+            _ => readers.values().filter(|reader| reader.role == ReaderRole::Enrollment).collect(),
+        }
+    } else {
+        readers.values().filter(|reader| reader.selected_by(selector)).collect()
+    };
+    match candidates.as_slice() {
+        [reader] => Ok(reader.name.clone()),
+        [] if readers.is_empty() => Err(String::from("Aucun lecteur connecté")),
+        [] if selector.is_empty() => Err(String::from("Plusieurs lecteurs connectés, précisez le lecteur (voir `readers`)")),
+        [] => Err(format!("Aucun lecteur connecté ne correspond à {}", selector)),
+        _ => Err(format!("Plusieurs lecteurs correspondent à {}, précisez le nom (voir `readers`)", selector)),
+    }
+}
+
+/// Renvoie la valeur d'une option de la ligne de commande, par exemple `--db chemin`.
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter().position(|arg| arg == flag).and_then(|index| args.get(index + 1)).cloned()
+}
+
+/// Renvoie le texte qui suit une option, jusqu'à l'option suivante ; il peut contenir des espaces, par exemple `--host Jean Dupont`.
+fn flag_text(words: &[&str], flag: &str) -> Option<String> {
+    let index = words.iter().position(|word| *word == flag)?;
+    let text = words[index + 1..].iter().take_while(|word| !word.starts_with("--")).copied().collect::<Vec<&str>>().join(" ");
+    Some(text).filter(|text| !text.is_empty())
+}
+
+/// Découpe les affectations `champ=valeur` d'une commande ; une valeur peut contenir des espaces.
+fn parse_assignments<'a>(words: impl Iterator<Item = &'a str>) -> Result<Vec<(String, String)>, String> {
+    let mut assignments: Vec<(String, String)> = Vec::new();
+    for word in words {
+        match (word.split_once('='), assignments.last_mut()) {
+            (Some((field, value)), _) => assignments.push((field.to_string(), value.to_string())),
+            (None, Some((_, value))) => {
+                value.push(' ');
+                value.push_str(word);
+            }
+            (None, None) => return Err(format!("Affectation attendue (champ=valeur): {}", word)),
+        }
+    }
+    Ok(assignments)
+}
 
-            let carduuid = decimals_to_hex(card.read(0));
+/// Affiche la fiche d'un utilisateur.
+fn print_user(user: &User) {
+    let field = |value: &Option<String>| value.clone().unwrap_or_else(|| String::from("-"));
+    println!("Utilisateur {} : {} ({})", user.id, user.name, user.status);
+    println!("  Carte : {}", field(&user.uuid));
+    println!("  Prénom : {}", field(&user.first_name));
+    println!("  Nom : {}", field(&user.last_name));
+    println!("  Matricule : {}", field(&user.employee_number));
+    println!("  E-mail : {}", field(&user.email));
+    println!("  Service : {}", field(&user.department));
+    println!("  Créé le : {}", field(&user.created_at));
+    println!("  Modifié le : {}", field(&user.updated_at));
+}
 
-            let result: String = match DboManager::uuid_exist(&carduuid).await {
-                Ok(uuid) => format!("Bienvenue {} !", uuid.to_owned()),
-                Err(_) => String::from("Carte non configuré")
-            };
+/// Recherche un utilisateur par identifiant, matricule ou nom, en signalant s'il est introuvable.
+async fn lookup_user(db: &DboManager, key: &str) -> Option<User> {
+    match db.find_user(key).await {
+        Ok(Some(user)) => Some(user),
+        Ok(None) => {
+            eprintln!("Utilisateur introuvable: {}", key);
+            None
+        }
+        Err(e) => {
+            eprintln!("Impossible de lire l'utilisateur: {}", e);
+            None
+        }
+    }
+}
 
-            if let Err(_) = tx.send([result, carduuid]).await {
-                break;
+/// Chiffre en place une base en clair avec la clé du keystore, créée si le fichier n'existe pas encore.
+///
+/// # Retourne
+///
+/// * `i32` - Le code de sortie du programme, 0 si la base est chiffrée.
+async fn encrypt_database(database: &str, keystore: Option<&str>) -> i32 {
+    let Some(keystore) = keystore else {
+        eprintln!("Aucun keystore configuré : renseigner le champ \"keystore\" de {}", CONFIG_PATH);
+        return 1;
+    };
+    let key = match load_key(keystore) {
+        Ok(key) => key,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => match create_key(keystore) {
+            Ok(key) => {
+                println!("Clé de chiffrement créée dans {}", keystore);
+                key
+            }
+            Err(e) => {
+                eprintln!("Impossible de créer la clé de chiffrement {}: {}", keystore, e);
+                return 1;
             }
+        },
+        Err(e) => {
+            eprintln!("Impossible de lire la clé de chiffrement {}: {}", keystore, e);
+            return 1;
+        }
+    };
+    if let Err(e) = DboManager::encrypt(database, &key).await {
+        eprintln!("Impossible de chiffrer la base {} (déjà chiffrée ou illisible ?): {}", database, e);
+        return 1;
+    }
+    match DboManager::open(database, Some(&key)).await {
+        Ok(db) => {
+            audit(&db, "encrypt", database, None, Some(json!({"keystore": keystore}))).await;
+            db.close().await;
+            println!("Base {} chiffrée ; la clé {} est nécessaire pour l'ouvrir", database, keystore);
+            0
+        }
+        Err(e) => {
+            eprintln!("La base chiffrée {} ne s'ouvre pas avec la clé {}: {}", database, keystore, e);
+            1
         }
+    }
+}
+
+/// État du code PIN d'un utilisateur pour le journal d'audit, sans le code ni son empreinte.
+async fn pin_state(db: &DboManager, user_id: i64) -> Option<Value> {
+    let hash = db.pin_hash(user_id).await.ok()?;
+    Some(json!({"pin": if hash.is_some() { "défini" } else { "aucun" }}))
+}
+
+/// Réglages d'une zone pour le journal d'audit, `None` si elle est inconnue.
+async fn zone_state(db: &DboManager, name: &str) -> Option<Value> {
+    let zone = db.find_zone(name).await.ok()??;
+    Some(json!({"doors": zone.doors, "passback": zone.passback.as_str(), "passback_reset": zone.passback_reset, "max_occupancy": zone.max_occupancy}))
+}
+
+/// Zone d'un lecteur pour le journal d'audit, `None` s'il n'a pas de porte à son nom exact.
+async fn door_state(db: &DboManager, reader: &str) -> Option<Value> {
+    // `zone_of_reader` accepte aussi les préfixes : la porte n'est retenue que si elle porte ce nom
+    let zone = db.zone_of_reader(reader).await.ok()??;
+    let doors = db.find_zone(&zone).await.ok()??.doors;
+    doors.iter().any(|door| door == reader).then(|| json!({"zone": zone}))
+}
+
+/// Membres et zones d'un groupe d'accès pour le journal d'audit, `None` s'il est inconnu.
+async fn group_state(db: &DboManager, name: &str) -> Option<Value> {
+    let group = db.list_groups().await.ok()?.into_iter().find(|group| group.name == name)?;
+    Some(json!({"members": group.members, "zones": group.zones}))
+}
+
+/// Affiche le résultat d'une modification des zones ou des groupes, 0 ligne modifiée signalant un nom inconnu.
+/// Renvoie `true` si la modification est faite.
+fn report_change(result: Result<u64, sqlx::Error>, done: &str, missing: &str) -> bool {
+    match result {
+        Ok(0) => eprintln!("{}", missing),
+        Ok(_) => {
+            println!("{}", done);
+            return true;
+        }
+        Err(e) => eprintln!("Modification impossible: {}", e),
+    }
+    false
+}
+
+/// Enregistre une action d'administration dans le journal d'audit, au nom du compte système courant.
+/// L'action est déjà faite : un échec de l'enregistrement est signalé sans l'annuler.
+async fn audit(db: &DboManager, action: &str, target: &str, before: Option<Value>, after: Option<Value>) {
+    let (before, after) = (before.map(|value| value.to_string()), after.map(|value| value.to_string()));
+    if let Err(e) = db.add_audit_entry(&current_actor(), action, target, before.as_deref(), after.as_deref()).await {
+        eprintln!("Impossible d'enregistrer l'action dans le journal d'audit: {}", e);
+    }
+}
+
+/// Affiche les badges visiteurs prêtés, en signalant ceux dont la validité est passée.
+fn print_visits(visits: &[Visit], now: i64) {
+    if visits.is_empty() {
+        println!("Aucun badge visiteur prêté");
+    }
+    for visit in visits {
+        let expired = visit.is_expired(now);
+        let host = visit.host.as_deref().unwrap_or("-");
+        let until = visit.valid_until.get(..16).unwrap_or(&visit.valid_until);
+        println!("{}  {:<24} hôte : {:<20} depuis {} jusqu'à {}{}", visit.badge_uid, visit.visitor, host, visit.issued_at, until, if expired { " (expiré)" } else { "" });
+    }
+}
+
+/// Affiche des passages de badge, un par ligne.
+fn print_badge_records(records: &[BadgeRecord]) {
+    if records.is_empty() {
+        println!("Aucun passage enregistré");
+    }
+    for record in records {
+        let user = record.user_name.as_deref().unwrap_or("inconnu");
+        let reason = record.reason.as_deref().map(|reason| format!(" ({})", reason)).unwrap_or_default();
+        println!("{} [{}] {} {} - {}{}", record.timestamp, record.reader, user, record.card_uid, record.result, reason);
+    }
+}
+
+/// La fonction `main` est asynchrone et utilise Tokio pour la gestion asynchrone des tâches. Elle surveille le branchement des lecteurs, crée une tâche asynchrone par lecteur connecté pour lire les cartes RFID périodiquement,
+/// vérifier leur UUID dans la base de données, et gérer les entrées utilisateur via l'entrée standard.
+///
+/// Les lecteurs sont nommés dans `config.json` (rôle entrée, sortie ou enrôlement et emplacement) ;
+/// chaque badge affiché indique le lecteur dont il provient. Le branchement et le débranchement d'un lecteur sont signalés
+/// et la connexion au service PC/SC est rétablie automatiquement.
+///
+/// La base de données est ouverte depuis l'option `--db <chemin>`, la variable `BADGEAGE_DB` ou le champ `database`
+/// de `config.json` (par défaut `sqlite3.db`) ; elle est créée si elle n'existe pas et les migrations en attente
+/// lui sont appliquées, sauf avec l'option `--no-migrate`. Si le champ `keystore` de `config.json` est renseigné, la base
+/// est chiffrée avec SQLCipher par la clé qu'il contient ; l'option `--encrypt` chiffre en place une base en clair, puis quitte.
+///
+/// Les commandes disponibles pour l'utilisateur sont :
+/// - `add <nom_utilisateur>` : Ajoute un utilisateur avec le nom donné (espaces compris) dans la base de données, à partir de la carte lue sur le poste d'enrôlement.
+/// - `reset` : Supprime l'utilisateur associé à l'UUID de la carte lue sur le poste d'enrôlement.
+/// - `export attendance <mois|du au> <fichier>` : Exporte le relevé de présence (une ligne par utilisateur et par jour) en CSV ou XLSX.
+/// - `import <fichier> [--dry-run]` : Importe des utilisateurs depuis un export JSON ou un fichier CSV, en une seule transaction.
+/// - `user list`, `user show <utilisateur>`, `user edit <utilisateur> champ=valeur ...` : Consultent et modifient les fiches utilisateur
+///   (identifiant, matricule ou nom).
+/// - `card list <utilisateur>` : Liste les cartes d'un utilisateur avec leur état et leurs dates d'émission et d'expiration.
+/// - `card lost|stolen|return <uid>` : Déclare une carte perdue, volée ou restituée ; elle est refusée immédiatement
+///   et une carte perdue ou volée présentée à nouveau déclenche une alerte.
+/// - `card expire <uid> [AAAA-MM-JJ]` : Fixe la date d'expiration d'une carte, ou la fait expirer immédiatement.
+/// - `card replace <utilisateur> [AAAA-MM-JJ]` : Attribue une nouvelle carte lue sur le poste d'enrôlement, les cartes encore actives étant déclarées perdues.
+/// - `pin set <utilisateur> <code>`, `pin clear <utilisateur>` : Enregistrent (empreinte Argon2) ou retirent le code PIN demandé
+///   sur les lecteurs `"two_factor": true` de `config.json`. Le code est saisi sur la ligne de commande après la lecture de la carte.
+/// - `pin unlock <utilisateur>` : Débloque un code PIN après trop d'échecs de saisie.
+/// - `zone list`, `zone add <zone>`, `zone del <zone>` : Gèrent les zones protégées.
+/// - `zone capacity <zone> <nombre|none>` : Fixe le nombre maximal de personnes présentes dans une zone, au-delà duquel
+///   les entrées sont refusées.
+/// - `occupancy [zone]` : Affiche le nombre de personnes présentes dans chaque zone, ou la liste des présents d'une zone.
+///   La présence est suivie d'après les passages accordés sur les lecteurs d'entrée et de sortie de chaque zone.
+/// - `muster [fichier]` : Affiche le rapport d'évacuation des personnes présentes, ou l'écrit dans un fichier
+///   (JSON avec l'extension `.json`, texte imprimable sinon).
+/// - `door set <lecteur> <zone>`, `door del <lecteur>` : Rattachent un lecteur (nom PC/SC ou préfixe) à la zone dont il est la porte.
+/// - `passback set <zone> off|soft|hard [minutes]` : Règle l'anti-retour d'une zone : une carte entrée par une porte d'entrée
+///   doit sortir par une porte de sortie avant d'entrer à nouveau. Le passage est refusé en mode `hard`, accordé mais signalé
+///   en mode `soft`, et la position de la carte est oubliée après le délai donné.
+/// - `passback reset <uid> [zone]` : Oublie la position d'une carte pour lever un blocage anti-retour.
+/// - `group list`, `group add|del <groupe>`, `group join|leave <groupe> <utilisateur>`, `group allow|revoke <groupe> <zone>` :
+///   Gèrent les groupes d'accès, leurs membres et les zones où ils sont autorisés. Sur un lecteur rattaché à une zone,
+///   seuls les membres d'un groupe autorisé sont acceptés ; un lecteur sans zone accepte toute carte active.
+/// - `schedule show <groupe>`, `schedule add <groupe> <jours> <HH:MM-HH:MM>`, `schedule clear <groupe>` : Consultent et
+///   définissent les plages horaires hebdomadaires d'un groupe (`lun-ven`, `lun,mer,ven`) ; sans plage, l'accès est permanent.
+/// - `schedule except <groupe> <AAAA-MM-JJ> open|closed|none` : Ouvre ou ferme un groupe toute une journée, ou retire l'exception.
+/// - `schedule calendar <groupe> <calendrier|none>` : Applique un calendrier de jours fériés à un groupe.
+/// - `holiday import <calendrier> <fichier.ics>`, `holiday list [calendrier]` : Importent et listent les jours fériés.
+/// - `visitor add <nom> --host <utilisateur> --until <AAAA-MM-JJTHH:MM|HH:MM>` : Prête un badge du stock à un visiteur, refusé
+///   après sa fin de validité ; le visiteur a les accès de son hôte.
+/// - `visitor return [uid]` : Restitue un badge visiteur, lu sur le poste d'enrôlement sans UID, qui retourne dans le stock.
+/// - `visitor list`, `visitor pool add|list`, `visitor pool del <uid>` : Listent les badges prêtés et gèrent le stock.
+///   Les badges non restitués sont aussi affichés chaque jour à l'heure `visitor_report` de `config.json`.
+/// - `readers` : Liste les lecteurs surveillés avec leur rôle et leur emplacement.
+/// - `reader [lecteur] info` : Affiche la version du firmware et les paramètres PICC du lecteur. Le lecteur est désigné par
+///   son nom PC/SC (ou un préfixe), son emplacement ou son rôle ; sans nom, le seul lecteur connecté ou le poste d'enrôlement.
+/// - `reader [lecteur] picc <hex>`, `reader [lecteur] antenna on|off`, `reader [lecteur] timeout <n>` : Configurent le lecteur.
+/// - `reader [lecteur] scan [débit]` : Recherche une cible via le PN532 (`106a`, `106b`, `212f`, `424f` ou `jewel`).
+/// - `reader [lecteur] exchange <hex>` : Envoie une trame ISO 14443 brute à la carte ISO 14443-A présente.
+/// - `emulate [lecteur] [url]` : Le lecteur se présente comme une étiquette NFC servant un lien à usage unique à un téléphone,
+///   pendant 60 secondes au plus.
+/// - `enroll redeem <jeton>` : Consomme le jeton d'un lien d'enrôlement, accepté une seule fois avant son expiration.
+/// - `history <utilisateur>` : Affiche l'historique des passages d'un utilisateur (`--day AAAA-MM-JJ` pour une journée,
+///   `--reader <lecteur>` pour un lecteur).
+/// - `timesheet <utilisateur> <semaine>` : Affiche les heures travaillées par jour sur une semaine (`AAAA-Wss` ou une date de la semaine).
+/// - `migrate status` / `migrate up` : Affiche l'état des migrations du schéma ou applique celles en attente.
+/// - `audit verify` : Vérifie la chaîne des empreintes du journal des actions d'administration.
+/// - `audit list [nombre]` : Affiche les dernières actions d'administration, 20 par défaut.
+/// - `replay <fichier> [--at AAAA-MM-JJTHH:MM|HH:MM]` : Rejoue une trace APDU enregistrée comme un passage de badge, à l'heure
+///   courante ou à l'heure donnée.
+/// - `help` : Affiche les commandes disponibles.
+/// - `exit` ou `quit` : Arrête le programme.
+#[tokio::main]
+async fn main() {
+    let config = Config::load(CONFIG_PATH).unwrap_or_else(|e| {
+        eprintln!("Configuration {} illisible, configuration par défaut utilisée: {}", CONFIG_PATH, e);
+        Config::default()
+    });
+    let any_reader = !config.has_enrollment_reader();
+    let args: Vec<String> = std::env::args().collect();
+    let database = config.database_path(flag_value(&args, "--db"), std::env::var(DATABASE_ENV).ok());
+    if args.iter().any(|arg| arg == "--encrypt") {
+        std::process::exit(encrypt_database(&database, config.keystore.as_deref()).await);
+    }
+    let key = config.keystore.as_deref().map(|keystore| {
+        load_key(keystore).unwrap_or_else(|e| {
+            eprintln!("Impossible de lire la clé de chiffrement {}: {} (l'option --encrypt crée la clé et chiffre la base)", keystore, e);
+            std::process::exit(1);
+        })
     });
+    // Avec --no-migrate, les migrations sont laissées à la commande `migrate up`
+    let db = if args.iter().any(|arg| arg == "--no-migrate") {
+        DboManager::open(&database, key.as_deref()).await
+    } else {
+        DboManager::new(&database, key.as_deref()).await
+    };
+    let db = db.unwrap_or_else(|e| {
+        eprintln!("Impossible d'ouvrir la base de données {}: {}", database, e);
+        std::process::exit(1);
+    }).with_clock(config.clock());
+
+    let (tx, mut rx) = mpsc::channel(1);
+    // Les codes PIN des lecteurs à double authentification sont saisis sur la ligne de commande
+    let pinpad = PinPad::default();
+    tokio::spawn(monitor_readers(config.clone(), db.clone(), pinpad.clone(), tx));
 
     let stdin = io::stdin();
     let reader = BufReader::new(stdin);
     let mut lines = reader.lines();
-    let mut last_message = [String::from("Message"), String::from("uuid")];
+    let mut readers: BTreeMap<String, ReaderConfig> = BTreeMap::new();
+    let mut last_messages: HashMap<String, BadgeEvent> = HashMap::new();
+
+    // Rapport quotidien des badges visiteurs non restitués, à partir du lendemain s'il est déjà passé
+    let clock = config.clock();
+    let report_at = config.visitor_report.as_deref().and_then(|time| {
+        let minutes = parse_minutes(time).filter(|minutes| *minutes < 24 * 60);
+        if minutes.is_none() {
+            eprintln!("Heure du rapport des visiteurs invalide (HH:MM): {}", time);
+        }
+        minutes
+    });
+    let mut reported_day = report_at.filter(|at| clock.now().rem_euclid(SECONDS_PER_DAY) / 60 >= *at).map(|_| clock.now().div_euclid(SECONDS_PER_DAY));
+    let mut report_timer = tokio::time::interval(std::time::Duration::from_secs(60));
 
     loop {
         tokio::select! {
-            Some(message) = rx.recv() => {
-                if message != last_message {
-                println!("{:?}", message);
-                last_message = message; // Mettre à jour le dernier message affiché
+            Some(event) = rx.recv() => {
+                if let Some(event) = handle_reader_event(&mut readers, event) {
+                    if last_messages.get(&event.reader.name) != Some(&event) {
+                    println!("[{} ({}, {})] {} {}", event.reader.name, event.reader.role, event.reader.location, event.message, event.uuid);
+                    last_messages.insert(event.reader.name.clone(), event); // Mettre à jour le dernier message affiché
+                    }
+                }
+                last_messages.retain(|name, _| readers.contains_key(name));
+            }
+            _ = report_timer.tick(), if report_at.is_some() => {
+                let now = clock.now();
+                let day = now.div_euclid(SECONDS_PER_DAY);
+                if reported_day != Some(day) && report_at.is_some_and(|at| now.rem_euclid(SECONDS_PER_DAY) / 60 >= at) {
+                    reported_day = Some(day);
+                    match db.open_visits().await {
+                        Ok(visits) => {
+                            println!("Badges visiteurs non restitués le {} :", format_time(now));
+                            print_visits(&visits, now);
+                        }
+                        Err(e) => eprintln!("Impossible de lister les visites: {}", e),
+                    }
                 }
             }
             // Lire l'entrée utilisateur
             result = lines.next_line() => {
                 match result {
                     Ok(Some(line)) => {
+                        if pinpad.submit(line.trim()) {
+                            continue;
+                        }
                         let command = line.trim();
-                        match command.split_whitespace().nth(0) {
+                        match command.split_whitespace().next() {
                             Some("exit") | Some("quit") => {
                                 println!("Arrêt de la boucle principale.");
                                 break;
                             }
                             Some("add") => {
-                                if let Some(arg) = command.split_whitespace().nth(1) {
-                                    if let Some(msg) = rx.recv().await{
-                                        DboManager::adduser(msg[1].clone(), &arg).await.expect("Erreur db");
+                                let name = command.split_whitespace().skip(1).collect::<Vec<&str>>().join(" ");
+                                if let Some(arg) = Some(name.as_str()).filter(|name| !name.is_empty()) {
+                                    if let Some(event) = next_enrollment_event(&mut rx, &mut readers, any_reader, &mut lines, &pinpad).await{
+                                        if let Ok(Some(owner)) = db.find_user_by_uid(&event.uuid).await {
+                                            eprintln!("La carte {} est déjà attribuée à {}", event.uuid, owner.name);
+                                            continue;
+                                        }
+                                        if db.is_visitor_badge(&event.uuid).await.unwrap_or(false) {
+                                            eprintln!("La carte {} est un badge visiteur", event.uuid);
+                                            continue;
+                                        }
+                                        if let Err(e) = db.adduser(event.uuid.clone(), arg).await {
+                                            eprintln!("Ajout de l'utilisateur impossible: {}", e);
+                                            continue;
+                                        }
+                                        audit(&db, "add", &event.uuid, None, Some(json!({"name": arg}))).await;
+                                        println!("Ajout de l'utilisateur, {}!", arg);
                                     }
-                                    println!("Ajout de l'utilisateur, {}!", arg);
                                 } else {
                                     eprintln!("Merci de saisir au moins 1 nom d'utilisateur");
                                 }
                             }
                             Some("reset") => {
-                                if let Some(msg) = rx.recv().await{
-                                        DboManager::deluser(msg[1].clone()).await.expect("Erreur db");
+                                if let Some(event) = next_enrollment_event(&mut rx, &mut readers, any_reader, &mut lines, &pinpad).await{
+                                        let owner = db.find_user_by_uid(&event.uuid).await.ok().flatten();
+                                        if let Err(e) = db.deluser(event.uuid.clone()).await {
+                                            eprintln!("Réinitialisation de la carte impossible: {}", e);
+                                            continue;
+                                        }
+                                        audit(&db, "reset", &event.uuid, owner.map(|user| json!({"name": user.name})), None).await;
+                                        println!("Réinitialisation de la carte!");
                                     }
-                                    println!("Réinitialisation de la carte!");
                                 }
 
+                            Some("export") if command.split_whitespace().nth(1) == Some("attendance") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(2).collect();
+                                let range = match words[..] {
+                                    [month, path] => parse_month(month).map(|(from, to)| (from, to, path)),
+                                    [from, to, path] => parse_day(from).zip(parse_day(to)).map(|(from, to)| (from, to, path)),
+                                    _ => None,
+                                };
+                                let Some((from, to, path)) = range.filter(|(from, to, _)| from <= to) else {
+                                    eprintln!("Usage : export attendance <AAAA-MM | AAAA-MM-JJ AAAA-MM-JJ> <fichier.csv|fichier.xlsx>");
+                                    continue;
+                                };
+                                // La veille du premier jour est incluse pour les sessions qui traversent minuit
+                                match db.events_between(&format_day(from - 1), &format_day(to)).await {
+                                    Ok(records) => match export_attendance(&attendance_rows(&records, &config, from, to), path) {
+                                        Ok(()) => {
+                                            println!("Relevé de présence du {} au {} exporté dans {}", format_day(from), format_day(to), path);
+                                            audit(&db, "export attendance", path, None, Some(json!({"from": format_day(from), "to": format_day(to)}))).await;
+                                        }
+                                        Err(e) => eprintln!("Impossible d'écrire le fichier {}: {}", path, e),
+                                    },
+                                    Err(e) => eprintln!("Impossible de lire l'historique: {}", e),
+                                }
+                            }
                             Some("export") => {
                                 if let Some(arg) = command.split_whitespace().nth(1) {
-                                    DboManager::export_users_to_json(arg).await.expect("Impossible d'écrire le fichier");
+                                    if let Err(e) = db.export_users_to_json(arg).await {
+                                        eprintln!("Impossible d'écrire le fichier {}: {}", arg, e);
+                                        continue;
+                                    }
                                     println!("Exportation du fichier {} réussie", arg);
+                                    audit(&db, "export", arg, None, None).await;
                                 }
 
                                 
+                            }
+                            Some("import") => {
+                                let dry_run = command.split_whitespace().any(|word| word == "--dry-run");
+                                let Some(path) = command.split_whitespace().skip(1).find(|word| *word != "--dry-run") else {
+                                    eprintln!("Usage : import <fichier.json|fichier.csv> [--dry-run]");
+                                    continue;
+                                };
+                                let users = match read_users(path) {
+                                    Ok(users) => users,
+                                    Err(e) => {
+                                        eprintln!("Impossible de lire le fichier {}: {}", path, e);
+                                        continue;
+                                    }
+                                };
+                                match db.import_users(&users, dry_run).await {
+                                    Ok(report) if !report.issues.is_empty() => {
+                                        for issue in &report.issues {
+                                            eprintln!("  {}", issue);
+                                        }
+                                        eprintln!("Import annulé : {} problème(s), aucun utilisateur ajouté", report.issues.len());
+                                    }
+                                    Ok(report) => {
+                                        for user in &report.added {
+                                            println!("  {} {}", user.uuid, user.name);
+                                        }
+                                        for card in &report.cards {
+                                            println!("  {} {} (carte supplémentaire)", card.uuid, card.name);
+                                        }
+                                        let verb = if dry_run { "à ajouter" } else { "ajouté(s)" };
+                                        println!("{} utilisateur(s) et {} carte(s) supplémentaire(s) {}, {} déjà présent(s)", report.added.len(), report.cards.len(), verb, report.unchanged);
+                                        if !dry_run {
+                                            let added: Vec<Value> = report.added.iter().chain(&report.cards).map(|user| json!({"uid": user.uuid, "name": user.name})).collect();
+                                            audit(&db, "import", path, None, Some(Value::from(added))).await;
+                                        }
+                                    }
+                                    Err(e) => eprintln!("Import impossible: {}", e),
+                                }
+                            }
+                            Some("user") => {
+                                let mut words = command.split_whitespace().skip(1);
+                                match (words.next(), words.next()) {
+                                    (Some("list"), None) => match db.list_users().await {
+                                        Ok(users) => {
+                                            for user in users {
+                                                let department = user.department.as_deref().unwrap_or("-");
+                                                let employee_number = user.employee_number.as_deref().unwrap_or("-");
+                                                println!("{:>4}  {:<24} {:<10} {:<16} {}", user.id, user.name, employee_number, department, user.status);
+                                            }
+                                        }
+                                        Err(e) => eprintln!("Impossible de lister les utilisateurs: {}", e),
+                                    },
+                                    (Some("show"), Some(key)) => match db.find_user(key).await {
+                                        Ok(Some(user)) => print_user(&user),
+                                        Ok(None) => eprintln!("Utilisateur introuvable: {}", key),
+                                        Err(e) => eprintln!("Impossible de lire l'utilisateur: {}", e),
+                                    },
+                                    (Some("edit"), Some(key)) => {
+                                        let mut user = match db.find_user(key).await {
+                                            Ok(Some(user)) => user,
+                                            Ok(None) => {
+                                                eprintln!("Utilisateur introuvable: {}", key);
+                                                continue;
+                                            }
+                                            Err(e) => {
+                                                eprintln!("Impossible de lire l'utilisateur: {}", e);
+                                                continue;
+                                            }
+                                        };
+                                        let previous = user.clone();
+                                        let changes = parse_assignments(words).and_then(|assignments| {
+                                            if assignments.is_empty() {
+                                                return Err(format!("Aucune modification ({})", User::FIELDS.join(", ")));
+                                            }
+                                            assignments.iter().try_for_each(|(field, value)| user.set(field, value))?;
+                                            Ok(assignments)
+                                        });
+                                        let assignments = match changes {
+                                            Ok(assignments) => assignments,
+                                            Err(e) => {
+                                                eprintln!("{}", e);
+                                                continue;
+                                            }
+                                        };
+                                        match db.update_user(&user).await {
+                                            Ok(_) => {
+                                                println!("Utilisateur {} modifié", user.name);
+                                                let before = assignments.iter().map(|(field, _)| (field.to_string(), json!(previous.get(field)))).collect();
+                                                let after = assignments.iter().map(|(field, _)| (field.to_string(), json!(user.get(field)))).collect();
+                                                audit(&db, "user edit", &user.id.to_string(), Some(Value::Object(before)), Some(Value::Object(after))).await;
+                                            }
+                                            Err(e) => eprintln!("Modification impossible: {}", e),
+                                        }
+                                    }
+                                    _ => eprintln!("Usage : user list | user show <id|matricule|nom> | user edit <id|matricule|nom> champ=valeur ..."),
+                                }
+                            }
+                            Some("card") => {
+                                let mut words = command.split_whitespace().skip(1);
+                                match (words.next(), words.next(), words.next()) {
+                                    (Some("list"), Some(key), None) => {
+                                        let Some(user) = lookup_user(&db, key).await else {
+                                            continue;
+                                        };
+                                        match db.cards_of_user(user.id).await {
+                                            Ok(cards) => {
+                                                if cards.is_empty() {
+                                                    println!("Aucune carte pour {}", user.name);
+                                                }
+                                                for card in cards {
+                                                    let expires_at = card.expires_at.as_deref().unwrap_or("-");
+                                                    println!("{:<30} {:<10} émise le {}  expire le {}", card.uid, card.effective_state(clock.now()), card.issued_at, expires_at);
+                                                }
+                                            }
+                                            Err(e) => eprintln!("Impossible de lister les cartes: {}", e),
+                                        }
+                                    }
+                                    (Some(action @ ("lost" | "stolen" | "return")), Some(uid), None) => {
+                                        let state = match action {
+                                            "lost" => CardState::Lost,
+                                            "stolen" => CardState::Stolen,
+                                            _ => CardState::Returned,
+                                        };
+                                        let before = db.find_card(uid).await.ok().flatten().map(|card| json!({"state": card.state.as_str()}));
+                                        if report_change(db.set_card_state(uid, state).await, &format!("Carte {} déclarée {}", uid, state), &format!("Carte inconnue: {}", uid)) {
+                                            audit(&db, &format!("card {}", action), uid, before, Some(json!({"state": state.as_str()}))).await;
+                                        }
+                                    }
+                                    // Sans date, la carte expire immédiatement
+                                    (Some("expire"), Some(uid), day) => {
+                                        let before = db.find_card(uid).await.ok().flatten().map(|card| json!({"state": card.state.as_str(), "expires_at": card.expires_at}));
+                                        let result = match day {
+                                            None => db.set_card_state(uid, CardState::Expired).await,
+                                            Some(day) if parse_day(day).is_some() => db.set_card_expiry(uid, Some(day)).await,
+                                            Some(day) => {
+                                                eprintln!("Date invalide (AAAA-MM-JJ): {}", day);
+                                                continue;
+                                            }
+                                        };
+                                        if report_change(result, &format!("Expiration de la carte {} enregistrée", uid), &format!("Carte inconnue: {}", uid)) {
+                                            let after = db.find_card(uid).await.ok().flatten().map(|card| json!({"state": card.state.as_str(), "expires_at": card.expires_at}));
+                                            audit(&db, "card expire", uid, before, after).await;
+                                        }
+                                    }
+                                    (Some("replace"), Some(key), expires_at) => {
+                                        if expires_at.is_some_and(|day| parse_day(day).is_none()) {
+                                            eprintln!("Date invalide (AAAA-MM-JJ): {}", expires_at.unwrap_or_default());
+                                            continue;
+                                        }
+                                        let Some(user) = lookup_user(&db, key).await else {
+                                            continue;
+                                        };
+                                        let cards = match db.cards_of_user(user.id).await {
+                                            Ok(cards) => cards,
+                                            Err(e) => {
+                                                eprintln!("Impossible de lister les cartes: {}", e);
+                                                continue;
+                                            }
+                                        };
+                                        println!("Présentez la nouvelle carte de {} sur le poste d'enrôlement", user.name);
+                                        let Some(event) = next_enrollment_event(&mut rx, &mut readers, any_reader, &mut lines, &pinpad).await else {
+                                            continue;
+                                        };
+                                        if let Ok(Some(owner)) = db.find_user_by_uid(&event.uuid).await {
+                                            eprintln!("La carte {} est déjà attribuée à {}", event.uuid, owner.name);
+                                            continue;
+                                        }
+                                        if db.is_visitor_badge(&event.uuid).await.unwrap_or(false) {
+                                            eprintln!("La carte {} est un badge visiteur", event.uuid);
+                                            continue;
+                                        }
+                                        // Les cartes encore actives sont remplacées : elles sont déclarées perdues
+                                        for card in cards.iter().filter(|card| card.state == CardState::Active) {
+                                            if report_change(db.set_card_state(&card.uid, CardState::Lost).await, &format!("Carte {} déclarée {}", card.uid, CardState::Lost), &format!("Carte inconnue: {}", card.uid)) {
+                                                audit(&db, "card replace", &card.uid, Some(json!({"state": card.state.as_str()})), Some(json!({"state": CardState::Lost.as_str()}))).await;
+                                            }
+                                        }
+                                        match db.add_card(user.id, &event.uuid, expires_at).await {
+                                            Ok(_) => {
+                                                println!("Carte {} attribuée à {}", event.uuid, user.name);
+                                                audit(&db, "card replace", &event.uuid, None, Some(json!({"user": user.id, "expires_at": expires_at}))).await;
+                                            }
+                                            Err(e) => eprintln!("Attribution impossible: {}", e),
+                                        }
+                                    }
+                                    _ => eprintln!("Usage : card list <utilisateur> | card lost|stolen|return <uid> | card expire <uid> [AAAA-MM-JJ] | card replace <utilisateur> [AAAA-MM-JJ]"),
+                                }
+                            }
+                            Some("pin") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                // Le nom de l'utilisateur peut contenir des espaces : le code est le dernier mot
+                                match words[..] {
+                                    ["set", ref key @ .., pin] if !key.is_empty() => {
+                                        if !is_valid_pin(pin) {
+                                            eprintln!("Le code PIN doit compter de 4 à 8 chiffres");
+                                            continue;
+                                        }
+                                        let Some(user) = lookup_user(&db, &key.join(" ")).await else {
+                                            continue;
+                                        };
+                                        let hash = match hash_pin(pin) {
+                                            Ok(hash) => hash,
+                                            Err(e) => {
+                                                eprintln!("Impossible de calculer l'empreinte du code PIN: {}", e);
+                                                continue;
+                                            }
+                                        };
+                                        // Le journal indique seulement si un code était défini, jamais le code ni son empreinte
+                                        let before = pin_state(&db, user.id).await;
+                                        if report_change(db.set_pin(user.id, Some(&hash)).await, &format!("Code PIN de {} enregistré", user.name), "Utilisateur introuvable") {
+                                            audit(&db, "pin set", &user.id.to_string(), before, Some(json!({"pin": "défini"}))).await;
+                                        }
+                                    }
+                                    ["clear", ref key @ ..] if !key.is_empty() => {
+                                        let Some(user) = lookup_user(&db, &key.join(" ")).await else {
+                                            continue;
+                                        };
+                                        let before = pin_state(&db, user.id).await;
+                                        if report_change(db.set_pin(user.id, None).await, &format!("Code PIN de {} retiré", user.name), "Utilisateur introuvable") {
+                                            audit(&db, "pin clear", &user.id.to_string(), before, Some(json!({"pin": "aucun"}))).await;
+                                        }
+                                    }
+                                    ["unlock", ref key @ ..] if !key.is_empty() => {
+                                        let Some(user) = lookup_user(&db, &key.join(" ")).await else {
+                                            continue;
+                                        };
+                                        if report_change(db.clear_pin_failures(user.id).await, &format!("Code PIN de {} débloqué", user.name), &format!("Aucun échec récent pour {}", user.name)) {
+                                            audit(&db, "pin unlock", &user.id.to_string(), None, None).await;
+                                        }
+                                    }
+                                    _ => eprintln!("Usage : pin set <utilisateur> <code> | pin clear <utilisateur> | pin unlock <utilisateur>"),
+                                }
+                            }
+                            Some("zone") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                match words[..] {
+                                    ["list"] => match db.list_zones().await {
+                                        Ok(zones) => {
+                                            if zones.is_empty() {
+                                                println!("Aucune zone définie");
+                                            }
+                                            for zone in zones {
+                                                let doors = if zone.doors.is_empty() { String::from("aucun lecteur") } else { zone.doors.join(", ") };
+                                                let reset = zone.passback_reset.map(|minutes| format!(", remis à zéro après {} min", minutes)).unwrap_or_default();
+                                                println!("{} : {} (anti-retour {}{})", zone.name, doors, zone.passback, reset);
+                                            }
+                                        }
+                                        Err(e) => eprintln!("Impossible de lister les zones: {}", e),
+                                    },
+                                    ["add", name] => match db.add_zone(name).await {
+                                        Ok(_) => {
+                                            println!("Zone {} créée", name);
+                                            audit(&db, "zone add", name, None, None).await;
+                                        }
+                                        Err(e) => eprintln!("Création impossible: {}", e),
+                                    },
+                                    ["del", name] => {
+                                        let before = zone_state(&db, name).await;
+                                        if report_change(db.delete_zone(name).await, &format!("Zone {} supprimée", name), &format!("Zone inconnue: {}", name)) {
+                                            audit(&db, "zone del", name, before, None).await;
+                                        }
+                                    }
+                                    ["capacity", name, max] => {
+                                        let max = match max {
+                                            "none" => None,
+                                            max => match max.parse::<i64>() {
+                                                Ok(max) if max > 0 => Some(max),
+                                                _ => {
+                                                    eprintln!("Capacité invalide (nombre positif ou none): {}", max);
+                                                    continue;
+                                                }
+                                            },
+                                        };
+                                        let done = match max {
+                                            Some(max) => format!("La zone {} accueille au plus {} personne(s)", name, max),
+                                            None => format!("La zone {} n'a plus de capacité maximale", name),
+                                        };
+                                        let before = zone_state(&db, name).await;
+                                        if report_change(db.set_max_occupancy(name, max).await, &done, &format!("Zone inconnue: {}", name)) {
+                                            audit(&db, "zone capacity", name, before, zone_state(&db, name).await).await;
+                                        }
+                                    }
+                                    _ => eprintln!("Usage : zone list | zone add <zone> | zone del <zone> | zone capacity <zone> <nombre|none>"),
+                                }
+                            }
+                            Some("passback") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                match words[..] {
+                                    ["set", zone, mode, ref reset @ ..] if reset.len() <= 1 => {
+                                        let (Ok(mode), Ok(reset)) = (mode.parse::<PassbackMode>(), reset.first().map(|minutes| minutes.parse::<i64>()).transpose()) else {
+                                            eprintln!("Mode (off, soft ou hard) ou délai en minutes invalide");
+                                            continue;
+                                        };
+                                        if reset.is_some_and(|minutes| minutes <= 0) {
+                                            eprintln!("Le délai de remise à zéro doit être positif");
+                                            continue;
+                                        }
+                                        let before = zone_state(&db, zone).await;
+                                        if report_change(db.set_passback(zone, mode, reset).await, &format!("Anti-retour {} pour la zone {}", mode, zone), &format!("Zone inconnue: {}", zone)) {
+                                            audit(&db, "passback set", zone, before, zone_state(&db, zone).await).await;
+                                        }
+                                    }
+                                    // Levée manuelle d'un blocage : la prochaine entrée ou sortie de la carte est acceptée
+                                    ["reset", uid, ref zone @ ..] if zone.len() <= 1 => {
+                                        let zone = zone.first().copied();
+                                        let done = format!("Position de la carte {} oubliée{}", uid, zone.map(|zone| format!(" dans la zone {}", zone)).unwrap_or_default());
+                                        if report_change(db.clear_presence(uid, zone).await, &done, &format!("Aucune position connue pour la carte {}", uid)) {
+                                            audit(&db, "passback reset", uid, None, zone.map(|zone| json!({"zone": zone}))).await;
+                                        }
+                                    }
+                                    _ => eprintln!("Usage : passback set <zone> off|soft|hard [minutes] | passback reset <uid> [zone]"),
+                                }
+                            }
+                            Some("occupancy") => match command.split_whitespace().nth(1) {
+                                None => match db.list_zones().await {
+                                    Ok(zones) => {
+                                        if zones.is_empty() {
+                                            println!("Aucune zone définie");
+                                        }
+                                        for zone in zones {
+                                            let max = zone.max_occupancy.map(|max| format!(" / {}", max)).unwrap_or_default();
+                                            println!("{} : {}{} présent(s){}", zone.name, zone.occupancy, max, if zone.is_full() { " (complète)" } else { "" });
+                                        }
+                                    }
+                                    Err(e) => eprintln!("Impossible de lister les zones: {}", e),
+                                },
+                                Some(zone) => match db.occupants(Some(zone)).await {
+                                    Ok(occupants) => {
+                                        if occupants.is_empty() {
+                                            println!("Personne dans la zone {}", zone);
+                                        }
+                                        for presence in occupants {
+                                            println!("{}  {:<40} depuis {}", presence.card_uid, presence.holder.as_deref().unwrap_or("titulaire inconnu"), presence.since);
+                                        }
+                                    }
+                                    Err(e) => eprintln!("Impossible de lister les présents: {}", e),
+                                },
+                            },
+                            Some("muster") => {
+                                let occupants = match db.occupants(None).await {
+                                    Ok(occupants) => occupants,
+                                    Err(e) => {
+                                        eprintln!("Impossible de lister les présents: {}", e);
+                                        continue;
+                                    }
+                                };
+                                let now = clock.now();
+                                match command.split_whitespace().nth(1) {
+                                    None => print!("{}", muster_text(&occupants, now)),
+                                    Some(path) => match export_muster(&occupants, now, path) {
+                                        Ok(()) => {
+                                            println!("Rapport d'évacuation écrit dans {}", path);
+                                            audit(&db, "muster", path, None, None).await;
+                                        }
+                                        Err(e) => eprintln!("Impossible d'écrire le fichier {}: {}", path, e),
+                                    },
+                                }
+                            }
+                            Some("door") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                // Le nom PC/SC d'un lecteur peut contenir des espaces : la zone est le dernier mot
+                                match words[..] {
+                                    ["set", ref reader @ .., zone] if !reader.is_empty() => {
+                                        let reader = reader.join(" ");
+                                        let before = door_state(&db, &reader).await;
+                                        if report_change(db.set_door(&reader, zone).await, &format!("Le lecteur {} donne accès à la zone {}", reader, zone), &format!("Zone inconnue: {}", zone)) {
+                                            audit(&db, "door set", &reader, before, Some(json!({"zone": zone}))).await;
+                                        }
+                                    }
+                                    ["del", ref reader @ ..] if !reader.is_empty() => {
+                                        let reader = reader.join(" ");
+                                        let before = door_state(&db, &reader).await;
+                                        if report_change(db.delete_door(&reader).await, &format!("Le lecteur {} n'est plus rattaché à une zone", reader), &format!("Aucune porte pour le lecteur {}", reader)) {
+                                            audit(&db, "door del", &reader, before, None).await;
+                                        }
+                                    }
+                                    _ => eprintln!("Usage : door set <lecteur> <zone> | door del <lecteur>"),
+                                }
+                            }
+                            Some("group") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                match words[..] {
+                                    ["list"] => match db.list_groups().await {
+                                        Ok(groups) => {
+                                            if groups.is_empty() {
+                                                println!("Aucun groupe défini");
+                                            }
+                                            for group in groups {
+                                                println!("{}", group.name);
+                                                println!("  Membres : {}", if group.members.is_empty() { String::from("-") } else { group.members.join(", ") });
+                                                println!("  Zones : {}", if group.zones.is_empty() { String::from("-") } else { group.zones.join(", ") });
+                                            }
+                                        }
+                                        Err(e) => eprintln!("Impossible de lister les groupes: {}", e),
+                                    },
+                                    ["add", name] => match db.add_group(name).await {
+                                        Ok(_) => {
+                                            println!("Groupe {} créé", name);
+                                            audit(&db, "group add", name, None, None).await;
+                                        }
+                                        Err(e) => eprintln!("Création impossible: {}", e),
+                                    },
+                                    ["del", name] => {
+                                        let before = group_state(&db, name).await;
+                                        if report_change(db.delete_group(name).await, &format!("Groupe {} supprimé", name), &format!("Groupe inconnu: {}", name)) {
+                                            audit(&db, "group del", name, before, None).await;
+                                        }
+                                    }
+                                    [action @ ("join" | "leave"), group, ref key @ ..] if !key.is_empty() => {
+                                        let Some(user) = lookup_user(&db, &key.join(" ")).await else {
+                                            continue;
+                                        };
+                                        let changed = if action == "join" {
+                                            report_change(db.add_group_member(group, user.id).await, &format!("{} ajouté au groupe {}", user.name, group), &format!("Groupe inconnu: {}", group))
+                                        } else {
+                                            report_change(db.remove_group_member(group, user.id).await, &format!("{} retiré du groupe {}", user.name, group), &format!("{} n'est pas membre du groupe {}", user.name, group))
+                                        };
+                                        if changed {
+                                            audit(&db, &format!("group {}", action), group, None, Some(json!({"user": user.id}))).await;
+                                        }
+                                    }
+                                    ["allow", group, zone] => {
+                                        if report_change(db.allow_zone(group, zone).await, &format!("Le groupe {} a accès à la zone {}", group, zone), "Groupe ou zone inconnu") {
+                                            audit(&db, "group allow", group, None, Some(json!({"zone": zone}))).await;
+                                        }
+                                    }
+                                    ["revoke", group, zone] => {
+                                        if report_change(db.revoke_zone(group, zone).await, &format!("Le groupe {} n'a plus accès à la zone {}", group, zone), "Aucune règle pour ce groupe et cette zone") {
+                                            audit(&db, "group revoke", group, Some(json!({"zone": zone})), None).await;
+                                        }
+                                    }
+                                    _ => eprintln!("Usage : group list | group add|del <groupe> | group join|leave <groupe> <utilisateur> | group allow|revoke <groupe> <zone>"),
+                                }
+                            }
+                            Some("schedule") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                match words[..] {
+                                    ["show", group] => match db.group_schedule(group).await {
+                                        Ok(Some(schedule)) => {
+                                            println!("Groupe {}", schedule.group);
+                                            if schedule.windows.is_empty() {
+                                                println!("  Accès à toute heure");
+                                            }
+                                            for window in &schedule.windows {
+                                                println!("  {}", window);
+                                            }
+                                            println!("  Jours fériés : {}", schedule.calendar.as_deref().unwrap_or("aucun calendrier"));
+                                            for (day, open) in &schedule.exceptions {
+                                                println!("  {} : {}", day, if *open { "ouvert" } else { "fermé" });
+                                            }
+                                        }
+                                        Ok(None) => eprintln!("Groupe inconnu: {}", group),
+                                        Err(e) => eprintln!("Impossible de lire les horaires: {}", e),
+                                    },
+                                    ["add", group, days, window] => match (parse_weekdays(days), parse_window(window)) {
+                                        (Some(weekdays), Some((start, end))) => {
+                                            if report_change(db.add_schedule(group, &weekdays, start, end).await, &format!("Plage {} {} ajoutée au groupe {}", days, window, group), "Groupe inconnu ou plage déjà définie") {
+                                                audit(&db, "schedule add", group, None, Some(json!({"days": days, "window": window}))).await;
+                                            }
+                                        }
+                                        _ => eprintln!("Jours (lun-ven, lun,mer,ven) ou plage horaire (HH:MM-HH:MM) invalide"),
+                                    },
+                                    ["clear", group] => {
+                                        let before = db.group_schedule(group).await.ok().flatten().map(|schedule| json!(schedule.windows.iter().map(|window| window.to_string()).collect::<Vec<String>>()));
+                                        if report_change(db.clear_schedule(group).await, &format!("Le groupe {} a accès à toute heure", group), "Groupe inconnu ou sans plage horaire") {
+                                            audit(&db, "schedule clear", group, before, None).await;
+                                        }
+                                    }
+                                    ["except", group, day, state @ ("open" | "closed" | "none")] if parse_day(day).is_some() => {
+                                        let open = match state {
+                                            "open" => Some(true),
+                                            "closed" => Some(false),
+                                            _ => None,
+                                        };
+                                        if report_change(db.set_schedule_exception(group, day, open).await, &format!("Exception du {} enregistrée pour le groupe {}", day, group), "Groupe inconnu ou aucune exception à supprimer") {
+                                            audit(&db, "schedule except", group, None, Some(json!({"day": day, "open": open}))).await;
+                                        }
+                                    }
+                                    ["calendar", group, calendar] => {
+                                        let calendar = Some(calendar).filter(|calendar| *calendar != "none");
+                                        let before = db.group_schedule(group).await.ok().flatten().map(|schedule| json!({"calendar": schedule.calendar}));
+                                        if report_change(db.set_group_calendar(group, calendar).await, &format!("Calendrier du groupe {} enregistré", group), &format!("Groupe inconnu: {}", group)) {
+                                            audit(&db, "schedule calendar", group, before, Some(json!({"calendar": calendar}))).await;
+                                        }
+                                    }
+                                    _ => eprintln!("Usage : schedule show <groupe> | schedule add <groupe> <jours> <HH:MM-HH:MM> | schedule clear <groupe> | schedule except <groupe> <AAAA-MM-JJ> open|closed|none | schedule calendar <groupe> <calendrier|none>"),
+                                }
+                            }
+                            Some("holiday") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                match words[..] {
+                                    ["import", calendar, path] => match read_ics(path) {
+                                        Ok(holidays) => match db.import_holidays(calendar, &holidays).await {
+                                            Ok(count) => {
+                                                println!("{} jour(s) férié(s) importé(s) dans le calendrier {}", count, calendar);
+                                                audit(&db, "holiday import", calendar, None, Some(json!({"file": path, "days": count}))).await;
+                                            }
+                                            Err(e) => eprintln!("Import impossible: {}", e),
+                                        },
+                                        Err(e) => eprintln!("Impossible de lire le fichier {}: {}", path, e),
+                                    },
+                                    ["list"] => match db.list_calendars().await {
+                                        Ok(calendars) => {
+                                            if calendars.is_empty() {
+                                                println!("Aucun calendrier importé");
+                                            }
+                                            for calendar in calendars {
+                                                println!("{} : {} jour(s)", calendar.calendar, calendar.days);
+                                            }
+                                        }
+                                        Err(e) => eprintln!("Impossible de lister les calendriers: {}", e),
+                                    },
+                                    ["list", calendar] => match db.list_holidays(calendar).await {
+                                        Ok(holidays) => {
+                                            for holiday in holidays {
+                                                println!("{} {}", holiday.day, holiday.summary.as_deref().unwrap_or(""));
+                                            }
+                                        }
+                                        Err(e) => eprintln!("Impossible de lister les jours fériés: {}", e),
+                                    },
+                                    _ => eprintln!("Usage : holiday import <calendrier> <fichier.ics> | holiday list [calendrier]"),
+                                }
+                            }
+                            Some("visitor") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                match words[..] {
+                                    ["add", ref args @ ..] => {
+                                        let name = args.iter().take_while(|word| !word.starts_with("--")).copied().collect::<Vec<&str>>().join(" ");
+                                        let (Some(host), Some(until)) = (flag_text(args, "--host"), flag_text(args, "--until")) else {
+                                            eprintln!("Usage : visitor add <nom> --host <utilisateur> --until <AAAA-MM-JJTHH:MM|HH:MM>");
+                                            continue;
+                                        };
+                                        let now = clock.now();
+                                        let Some(valid_until) = parse_local_time(&until, now).filter(|_| !name.is_empty()) else {
+                                            eprintln!("Nom du visiteur manquant ou heure invalide (AAAA-MM-JJTHH:MM ou HH:MM): {}", until);
+                                            continue;
+                                        };
+                                        if valid_until <= now {
+                                            eprintln!("La fin de validité {} est déjà passée", format_time(valid_until));
+                                            continue;
+                                        }
+                                        let Some(host) = lookup_user(&db, &host).await else {
+                                            continue;
+                                        };
+                                        match db.start_visit(&name, host.id, &format!("{}:00", format_time(valid_until))).await {
+                                            Ok(Some(visit)) => {
+                                                println!("Remettre le badge {} à {}, visiteur de {}, valable jusqu'à {}", visit.badge_uid, visit.visitor, host.name, format_time(valid_until));
+                                                audit(&db, "visitor add", &visit.badge_uid, None, Some(json!({"visitor": visit.visitor, "host": host.id, "valid_until": visit.valid_until}))).await;
+                                            }
+                                            Ok(None) => eprintln!("Aucun badge visiteur disponible"),
+                                            Err(e) => eprintln!("Attribution impossible: {}", e),
+                                        }
+                                    }
+                                    ["return", ref uid @ ..] if uid.len() <= 1 => {
+                                        // Sans UID, le badge est lu sur le poste d'enrôlement
+                                        let uid = match uid.first() {
+                                            Some(uid) => uid.to_string(),
+                                            None => match next_enrollment_event(&mut rx, &mut readers, any_reader, &mut lines, &pinpad).await {
+                                                Some(event) => event.uuid,
+                                                None => continue,
+                                            },
+                                        };
+                                        if report_change(db.end_visit(&uid).await, &format!("Badge visiteur {} restitué", uid), &format!("Le badge {} n'est pas prêté", uid)) {
+                                            audit(&db, "visitor return", &uid, None, None).await;
+                                        }
+                                    }
+                                    ["list"] => match db.open_visits().await {
+                                        Ok(visits) => print_visits(&visits, clock.now()),
+                                        Err(e) => eprintln!("Impossible de lister les visites: {}", e),
+                                    },
+                                    ["pool", "add"] => {
+                                        let Some(event) = next_enrollment_event(&mut rx, &mut readers, any_reader, &mut lines, &pinpad).await else {
+                                            continue;
+                                        };
+                                        if let Ok(Some(owner)) = db.find_user_by_uid(&event.uuid).await {
+                                            eprintln!("La carte {} est déjà attribuée à {}", event.uuid, owner.name);
+                                            continue;
+                                        }
+                                        match db.add_visitor_badge(&event.uuid).await {
+                                            Ok(_) => {
+                                                println!("Badge visiteur {} ajouté au stock", event.uuid);
+                                                audit(&db, "visitor pool add", &event.uuid, None, None).await;
+                                            }
+                                            Err(e) => eprintln!("Ajout impossible: {}", e),
+                                        }
+                                    }
+                                    ["pool", "del", uid] => {
+                                        if report_change(db.delete_visitor_badge(uid).await, &format!("Badge visiteur {} retiré du stock", uid), "Badge inconnu ou encore prêté") {
+                                            audit(&db, "visitor pool del", uid, None, None).await;
+                                        }
+                                    }
+                                    ["pool", "list"] => match db.list_visitor_badges().await {
+                                        Ok(badges) => {
+                                            if badges.is_empty() {
+                                                println!("Aucun badge visiteur");
+                                            }
+                                            for badge in badges {
+                                                println!("{}  {}", badge.uid, badge.visitor.as_deref().unwrap_or("disponible"));
+                                            }
+                                        }
+                                        Err(e) => eprintln!("Impossible de lister les badges visiteurs: {}", e),
+                                    },
+                                    _ => eprintln!("Usage : visitor add <nom> --host <utilisateur> --until <AAAA-MM-JJTHH:MM|HH:MM> | visitor return [uid] | visitor list | visitor pool add|list | visitor pool del <uid>"),
+                                }
+                            }
+                            Some("readers") => {
+                                if readers.is_empty() {
+                                    println!("Aucun lecteur connecté");
+                                }
+                                for reader in readers.values() {
+                                    println!("{} - {} - {}", reader.name, reader.role, reader.location);
+                                }
+                            }
+                            Some("reader") => {
+                                // Le nom du lecteur, qui peut contenir des espaces, précède la sous-commande
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                let split = words.iter().position(|word| READER_COMMANDS.contains(word)).unwrap_or(words.len());
+                                let name = match select_reader(&readers, &words[..split].join(" ")) {
+                                    Ok(name) => name,
+                                    Err(e) => {
+                                        eprintln!("{}", e);
+                                        continue;
+                                    }
+                                };
+                                let reader = match ReaderManager::loadreader(&name) {
+                                    Ok(reader) => ReaderManager{reader},
+                                    Err(e) => {
+                                        eprintln!("Lecteur {} indisponible: {}", name, e);
+                                        continue;
+                                    }
+                                };
+                                let mut args = words[split..].iter().copied();
+                                match (args.next(), args.next()) {
+                                    (Some("info"), _) => {
+                                        match reader.firmware_version() {
+                                            Ok(version) => println!("Firmware : {}", version),
+                                            Err(e) => eprintln!("Lecture du firmware impossible: {}", e),
+                                        }
+                                        match Pn532::new(&reader).firmware_version() {
+                                            Ok(version) => println!("PN5{:02X} : version {}.{}", version.ic, version.version, version.revision),
+                                            Err(e) => eprintln!("Lecture de la version du PN532 impossible: {}", e),
+                                        }
+                                        match reader.picc_parameters() {
+                                            Ok(parameters) => println!("Paramètres PICC : {:02X} {:?}", parameters.to_byte(), parameters),
+                                            Err(e) => eprintln!("Lecture des paramètres PICC impossible: {}", e),
+                                        }
+                                    }
+                                    (Some("picc"), Some(value)) => match u8::from_str_radix(value, 16) {
+                                        Ok(byte) => {
+                                            let before = reader.picc_parameters().ok().map(|parameters| json!({"picc": format!("{:02X}", parameters.to_byte())}));
+                                            match reader.set_picc_parameters(PiccParameters::from_byte(byte)) {
+                                                Ok(parameters) => {
+                                                    println!("Paramètres PICC appliqués : {:02X}", parameters.to_byte());
+                                                    audit(&db, "reader picc", &name, before, Some(json!({"picc": format!("{:02X}", parameters.to_byte())}))).await;
+                                                }
+                                                Err(e) => eprintln!("Modification des paramètres PICC impossible: {}", e),
+                                            }
+                                        }
+                                        Err(_) => eprintln!("Valeur hexadécimale invalide: {}", value),
+                                    },
+                                    // L'état de l'antenne et le délai d'attente ne se relisent pas sur le lecteur : seule la nouvelle valeur est connue
+                                    (Some("antenna"), Some(state @ ("on" | "off"))) => match reader.antenna(state == "on") {
+                                        Ok(()) => {
+                                            println!("Antenne {}", if state == "on" { "allumée" } else { "éteinte" });
+                                            audit(&db, "reader antenna", &name, None, Some(json!({"antenna": state}))).await;
+                                        }
+                                        Err(e) => eprintln!("Commande de l'antenne impossible: {}", e),
+                                    },
+                                    (Some("timeout"), Some(value)) => match value.parse::<u8>() {
+                                        Ok(timeout) => match reader.set_timeout(timeout) {
+                                            Ok(()) => {
+                                                println!("Délai d'attente réglé à {}", timeout);
+                                                audit(&db, "reader timeout", &name, None, Some(json!({"timeout": timeout}))).await;
+                                            }
+                                            Err(e) => eprintln!("Réglage du délai impossible: {}", e),
+                                        },
+                                        Err(_) => eprintln!("Délai invalide: {}", value),
+                                    },
+                                    (Some("scan"), baud_rate) => match baud_rate.unwrap_or("106a").parse::<BaudRate>() {
+                                        Ok(baud_rate) => {
+                                            let pn532 = Pn532::new(&reader);
+                                            match pn532.in_list_passive_target(baud_rate) {
+                                                Ok(Some(target)) => {
+                                                    println!("Cible {} ({:?}) : {:02X?}", target.number, target.baud_rate, target.uid().unwrap_or_default());
+                                                    let _ = pn532.in_release(target.number);
+                                                }
+                                                Ok(None) => println!("Aucune cible détectée"),
+                                                Err(e) => eprintln!("Recherche de cible impossible: {}", e),
+                                            }
+                                        }
+                                        Err(e) => eprintln!("{}", e),
+                                    },
+                                    (Some("exchange"), Some(value)) => match hex_to_bytes(value) {
+                                        Some(frame) => {
+                                            let pn532 = Pn532::new(&reader);
+                                            match pn532.in_list_passive_target(BaudRate::Iso14443A) {
+                                                Ok(Some(target)) => {
+                                                    match pn532.in_data_exchange(target.number, &frame) {
+                                                        Ok(response) => println!("Réponse : {:02X?}", response),
+                                                        Err(e) => eprintln!("Échange impossible: {}", e),
+                                                    }
+                                                    let _ = pn532.in_release(target.number);
+                                                }
+                                                Ok(None) => println!("Aucune cible détectée"),
+                                                Err(e) => eprintln!("Recherche de cible impossible: {}", e),
+                                            }
+                                        }
+                                        None => eprintln!("Valeur hexadécimale invalide: {}", value),
+                                    },
+                                    _ => eprintln!("Usage : reader [lecteur] info | picc <hex> | antenna on|off | timeout <n> | scan [106a|106b|212f|424f|jewel] | exchange <hex>"),
+                                }
+                            }
+                            Some("emulate") => {
+                                // L'url, reconnue à son schéma, suit le nom du lecteur
+                                let mut words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                let emulation = match words.pop_if(|word| word.contains("://")) {
+                                    Some(url) => EmulationConfig { url: url.to_string(), ..config.emulation.clone() },
+                                    None => config.emulation.clone(),
+                                };
+                                let name = match select_reader(&readers, &words.join(" ")) {
+                                    Ok(name) => name,
+                                    Err(e) => {
+                                        eprintln!("{}", e);
+                                        continue;
+                                    }
+                                };
+                                // Le jeton n'est valable qu'une fois et pour une durée limitée, voir `enroll redeem`
+                                let token = rngtoken();
+                                match db.add_enrollment_token(&token, emulation.token_minutes).await {
+                                    // Le jeton lui-même n'apparaît pas dans le journal, seul son numéro
+                                    Ok(id) => audit(&db, "emulate", &name, None, Some(json!({"token": id, "minutes": emulation.token_minutes}))).await,
+                                    Err(e) => {
+                                        eprintln!("Impossible d'enregistrer le jeton: {}", e);
+                                        continue;
+                                    }
+                                }
+                                let url = emulation.url_for(&token);
+                                println!("Émulation d'une étiquette NFC, approchez un téléphone dans les {} s : {}", EMULATION_TIMEOUT as u32 * 5, url);
+
+                                tokio::task::spawn_blocking(move || {
+                                    let reader = match ReaderManager::loadreader(&name) {
+                                        Ok(reader) => ReaderManager{reader},
+                                        Err(e) => {
+                                            eprintln!("Lecteur {} indisponible: {}", name, e);
+                                            return;
+                                        }
+                                    };
+                                    let card = CardManager{card: reader};
+                                    let message = NdefMessage { records: vec![NdefRecord::uri(&url)] };
+                                    match card.emulate_for(&mut Type4Tag::new(&message), EMULATION_TIMEOUT) {
+                                        Ok(true) => println!("Lien transmis au téléphone"),
+                                        Ok(false) => println!("Le téléphone est parti avant la fin de la lecture"),
+                                        Err(e) => eprintln!("Émulation terminée sans transmettre le lien: {}", e),
+                                    }
+                                });
+                            }
+                            Some("enroll") => match command.split_whitespace().skip(1).collect::<Vec<&str>>()[..] {
+                                ["redeem", token] => match db.redeem_enrollment_token(token).await {
+                                    Ok(redeemed) => {
+                                        if redeemed {
+                                            println!("Jeton valide, enrôlement autorisé");
+                                        } else {
+                                            eprintln!("Jeton inconnu, expiré ou déjà utilisé");
+                                        }
+                                        audit(&db, "enroll redeem", "jeton", None, Some(json!({"redeemed": redeemed}))).await;
+                                    }
+                                    Err(e) => eprintln!("Impossible de vérifier le jeton: {}", e),
+                                },
+                                _ => eprintln!("Usage : enroll redeem <jeton>"),
+                            },
+                            Some("history") => {
+                                let mut words = command.split_whitespace().skip(1);
+                                let records = match (words.next(), words.next()) {
+                                    (Some("--day"), Some(day)) => db.events_by_day(day).await,
+                                    (Some("--reader"), Some(_)) => {
+                                        let reader = command.split_once("--reader").map(|(_, reader)| reader.trim()).unwrap_or_default();
+                                        db.events_by_reader(reader).await
+                                    }
+                                    (Some(user), _) if !user.starts_with("--") => db.events_by_user(user).await,
+                                    _ => {
+                                        eprintln!("Usage : history <utilisateur> | history --day AAAA-MM-JJ | history --reader <lecteur>");
+                                        continue;
+                                    }
+                                };
+                                match records {
+                                    Ok(records) => print_badge_records(&records),
+                                    Err(e) => eprintln!("Impossible de lire l'historique: {}", e),
+                                }
+                            }
+                            Some("timesheet") => {
+                                let mut words = command.split_whitespace().skip(1);
+                                let (Some(user), Some(week_start)) = (words.next(), words.next().and_then(parse_week)) else {
+                                    eprintln!("Usage : timesheet <utilisateur> <AAAA-Wss|AAAA-MM-JJ>");
+                                    continue;
+                                };
+                                // La veille du lundi est incluse pour les sessions qui traversent minuit
+                                match db.events_by_user_between(user, &format_day(week_start - 1), &format_day(week_start + 6)).await {
+                                    Ok(records) => {
+                                        let timesheet = Timesheet::build(&records, &config, week_start);
+                                        println!("Feuille de temps de {}, semaine du {} au {}", user, format_day(week_start), format_day(week_start + 6));
+                                        for (index, seconds) in timesheet.days.iter().enumerate() {
+                                            println!("  {:<9} {} : {}", WEEKDAYS[index], format_day(week_start + index as i64), format_duration(*seconds));
+                                        }
+                                        println!("  Total : {}", format_duration(timesheet.total()));
+                                        for session in timesheet.forgotten() {
+                                            println!("  Sortie non badgée après l'entrée du {}", format_time(session.start));
+                                        }
+                                    }
+                                    Err(e) => eprintln!("Impossible de lire l'historique: {}", e),
+                                }
+                            }
+                            Some("migrate") => match command.split_whitespace().nth(1) {
+                                Some("status") => match db.migration_status().await {
+                                    Ok(migrations) => {
+                                        for migration in migrations {
+                                            let state = if migration.applied { "appliquée" } else { "en attente" };
+                                            println!("{:04} {} - {}", migration.version, migration.description, state);
+                                        }
+                                    }
+                                    Err(e) => eprintln!("Impossible de lire l'état des migrations: {}", e),
+                                },
+                                Some("up") => match db.migrate_up().await {
+                                    Ok(0) => println!("La base de données est à jour"),
+                                    Ok(applied) => {
+                                        println!("{} migration(s) appliquée(s)", applied);
+                                        audit(&db, "migrate up", &database, None, Some(json!({"applied": applied}))).await;
+                                    }
+                                    Err(e) => eprintln!("Migration impossible: {}", e),
+                                },
+                                _ => eprintln!("Usage : migrate status | migrate up"),
+                            },
+                            Some("audit") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                let count = match words[..] {
+                                    ["verify"] | ["list"] => None,
+                                    ["list", count] => match count.parse::<usize>() {
+                                        Ok(count) => Some(count),
+                                        Err(_) => {
+                                            eprintln!("Nombre d'entrées invalide: {}", count);
+                                            continue;
+                                        }
+                                    },
+                                    _ => {
+                                        eprintln!("Usage : audit verify | audit list [nombre]");
+                                        continue;
+                                    }
+                                };
+                                let entries = match db.audit_entries().await {
+                                    Ok(entries) => entries,
+                                    Err(e) => {
+                                        eprintln!("Impossible de lire le journal d'audit: {}", e);
+                                        continue;
+                                    }
+                                };
+                                if words[0] == "list" {
+                                    // Les dernières entrées, 20 par défaut
+                                    for entry in &entries[entries.len().saturating_sub(count.unwrap_or(20))..] {
+                                        let change = [("avant", &entry.before), ("après", &entry.after)]
+                                            .iter()
+                                            .filter_map(|(label, state)| state.as_ref().map(|state| format!(" {} {}", label, state)))
+                                            .collect::<String>();
+                                        println!("{:>5}  {} UTC  {:<12} {} {}{}", entry.id, entry.at, entry.actor, entry.action, entry.target, change);
+                                    }
+                                    continue;
+                                }
+                                let issues = verify(&entries);
+                                for issue in &issues {
+                                    eprintln!("  {}", issue);
+                                }
+                                match entries.last() {
+                                    _ if !issues.is_empty() => eprintln!("Journal d'audit altéré : {} anomalie(s)", issues.len()),
+                                    None => println!("Journal d'audit vide"),
+                                    // Une suppression des dernières entrées ne se voit qu'en comparant l'empreinte finale à une copie
+                                    Some(last) => println!("Journal d'audit intègre : {} entrée(s), empreinte finale {} (à conserver pour détecter une suppression des dernières entrées)", entries.len(), last.hash),
+                                }
+                            }
+                            Some("replay") => {
+                                let words: Vec<&str> = command.split_whitespace().skip(1).collect();
+                                let (path, at) = match words[..] {
+                                    [path] => (path, None),
+                                    [path, "--at", at] => (path, Some(at)),
+                                    _ => {
+                                        eprintln!("Usage : replay <fichier> [--at AAAA-MM-JJTHH:MM|HH:MM]");
+                                        continue;
+                                    }
+                                };
+                                // Avec --at, le passage est évalué à l'heure donnée plutôt qu'à l'heure courante
+                                let clock: Box<dyn Clock> = match at {
+                                    None => Box::new(config.clock()),
+                                    Some(at) => match parse_local_time(at, config.clock().now()) {
+                                        Some(time) => Box::new(FixedClock(time)),
+                                        None => {
+                                            eprintln!("Heure invalide (AAAA-MM-JJTHH:MM ou HH:MM): {}", at);
+                                            continue;
+                                        }
+                                    },
+                                };
+                                match ReplayTransport::load(path) {
+                                    Ok(replay) => {
+                                        let card = CardManager{card: replay};
+                                        let reader = ReaderConfig { name: path.to_string(), ..Default::default() };
+                                        match badge_card(&card, &db, &reader, clock.as_ref(), &config.feedback, None, None).await {
+                                            Ok(event) => println!("[{}] {} {}", path, event.message, event.uuid),
+                                            Err(e) => eprintln!("[{}] Lecture de la carte impossible: {}", path, e),
+                                        }
+                                        let remaining = card.card.remaining();
+                                        if remaining > 0 {
+                                            println!("{} échange(s) de la trace non rejoué(s)", remaining);
+                                        }
+                                    }
+                                    Err(e) => eprintln!("Impossible de charger la trace {}: {}", path, e),
+                                }
                             }
                             Some("help") => {
                                 println!("Commandes disponibles :");
-                                println!("  add nomdutilisateur  - permet l'ajout d'une carte dans la base de donnée");
+                                println!("  add nom d'utilisateur  - permet l'ajout d'une carte dans la base de donnée");
                                 println!("  reset   - Supprime l'uuid de la carte dans la base de donnée");
                                 println!("  export   - Exporte la base de données dans le dossier courant au format json");
+                                println!("  export attendance <AAAA-MM | AAAA-MM-JJ AAAA-MM-JJ> <fichier.csv|.xlsx>   - Exporte le relevé de présence");
+                                println!("  import <fichier.json|fichier.csv> [--dry-run]   - Importe des utilisateurs, --dry-run vérifie sans rien écrire");
+                                println!("  user list | user show <id|matricule|nom>   - Liste les utilisateurs ou affiche une fiche");
+                                println!("  user edit <id|matricule|nom> champ=valeur ...   - Modifie une fiche ({})", User::FIELDS.join(", "));
+                                println!("  card list <utilisateur>                    - Liste les cartes d'un utilisateur");
+                                println!("  card lost|stolen|return <uid>              - Déclare une carte perdue, volée ou restituée");
+                                println!("  card expire <uid> [AAAA-MM-JJ]             - Fixe l'expiration d'une carte, immédiate sans date");
+                                println!("  card replace <utilisateur> [AAAA-MM-JJ]    - Attribue une carte de remplacement lue sur le poste d'enrôlement");
+                                println!("  pin set <utilisateur> <code> | pin clear <utilisateur>   - Enregistre ou retire le code PIN d'un utilisateur");
+                                println!("  pin unlock <utilisateur>                   - Débloque un code PIN après trop d'échecs");
+                                println!("  zone list | zone add|del <zone>            - Liste, crée ou supprime les zones");
+                                println!("  zone capacity <zone> <nombre|none>         - Fixe le nombre maximal de personnes présentes dans une zone");
+                                println!("  occupancy [zone]                           - Affiche l'occupation des zones, ou les présents d'une zone");
+                                println!("  muster [fichier.json|fichier.txt]          - Affiche ou écrit le rapport d'évacuation");
+                                println!("  door set <lecteur> <zone> | door del <lecteur>   - Rattache un lecteur à une zone ou l'en retire");
+                                println!("  passback set <zone> off|soft|hard [minutes]   - Règle l'anti-retour d'une zone et son délai de remise à zéro");
+                                println!("  passback reset <uid> [zone]                - Lève un blocage anti-retour en oubliant la position d'une carte");
+                                println!("  group list | group add|del <groupe>        - Liste, crée ou supprime les groupes d'accès");
+                                println!("  group join|leave <groupe> <utilisateur>    - Ajoute ou retire un membre");
+                                println!("  group allow|revoke <groupe> <zone>         - Autorise ou retire l'accès d'un groupe à une zone");
+                                println!("  schedule show <groupe>                     - Affiche les horaires d'un groupe");
+                                println!("  schedule add <groupe> <jours> <HH:MM-HH:MM>   - Ajoute une plage horaire, par exemple lun-ven 07:00-20:00");
+                                println!("  schedule clear <groupe>                    - Supprime les plages, l'accès devient permanent");
+                                println!("  schedule except <groupe> <AAAA-MM-JJ> open|closed|none   - Ouvre ou ferme un groupe toute une journée");
+                                println!("  schedule calendar <groupe> <calendrier|none>   - Applique un calendrier de jours fériés");
+                                println!("  holiday import <calendrier> <fichier.ics> | holiday list [calendrier]   - Importe ou liste les jours fériés");
+                                println!("  visitor add <nom> --host <utilisateur> --until <AAAA-MM-JJTHH:MM|HH:MM>   - Prête un badge visiteur");
+                                println!("  visitor return [uid]                       - Restitue un badge visiteur, lu sur le poste d'enrôlement sans UID");
+                                println!("  visitor list                               - Liste les badges visiteurs prêtés");
+                                println!("  visitor pool add|list | visitor pool del <uid>   - Gère le stock de badges visiteurs");
+                                println!("  readers   - Liste les lecteurs surveillés avec leur rôle et leur emplacement");
+                                println!("  reader [lecteur] info   - Affiche le firmware et les paramètres PICC du lecteur (nom, emplacement ou rôle)");
+                                println!("  reader [lecteur] picc <hex>   - Modifie les paramètres PICC (polling, types de cartes, intervalle)");
+                                println!("  reader [lecteur] antenna on|off   - Allume ou éteint l'antenne");
+                                println!("  reader [lecteur] timeout <n>   - Règle le délai d'attente du lecteur (unités de 5 s)");
+                                println!("  reader [lecteur] scan [106a|106b|212f|424f|jewel]   - Recherche une cible via le PN532");
+                                println!("  reader [lecteur] exchange <hex>   - Envoie une trame brute à la carte ISO 14443-A présente");
+                                println!("  emulate [lecteur] [url]   - Sert un lien à usage unique aux téléphones, le lecteur jouant le rôle d'étiquette NFC");
+                                println!("  enroll redeem <jeton>   - Consomme le jeton d'un lien d'enrôlement (une fois, avant expiration)");
+                                println!("  history <utilisateur>|--day AAAA-MM-JJ|--reader <lecteur>   - Affiche l'historique des passages");
+                                println!("  timesheet <utilisateur> <AAAA-Wss|AAAA-MM-JJ>   - Affiche les heures travaillées sur une semaine");
+                                println!("  migrate status|up   - Affiche ou applique les migrations du schéma de la base");
+                                println!("  audit verify        - Vérifie que le journal des actions d'administration n'a pas été altéré");
+                                println!("  audit list [nombre] - Affiche les dernières actions d'administration (20 par défaut)");
+                                println!("  replay <fichier> [--at AAAA-MM-JJTHH:MM|HH:MM]   - Rejoue une trace APDU enregistrée comme un passage de badge");
                                 println!("  exit   - Quitte le programme");
                             }
                             _ => {
@@ -122,4 +1518,5 @@ async fn main() {
         }
 
     }
+    db.close().await;
 }
\ No newline at end of file
@@ -0,0 +1,120 @@
+/// Module `pin` gère le code PIN demandé en second facteur sur les lecteurs à double authentification :
+/// empreinte Argon2 du code et saisie, la ligne de commande tenant lieu de clavier.
+pub mod pin {
+    use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+    use argon2::Argon2;
+    use rand::rngs::OsRng;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::{oneshot, Mutex as TurnLock};
+    use tokio::time::{self, Duration};
+
+    /// Délai laissé pour saisir le code PIN après la lecture de la carte.
+    pub const PIN_TIMEOUT: Duration = Duration::from_secs(30);
+    /// Nombre d'échecs au-delà duquel le code d'un utilisateur est bloqué.
+    pub const MAX_FAILURES: i64 = 3;
+    /// Durée du blocage, pendant laquelle les échecs sont comptés.
+    pub const LOCKOUT_MINUTES: i64 = 15;
+
+    /// Indique si un code PIN est bien formé : de 4 à 8 chiffres.
+    pub fn is_valid_pin(pin: &str) -> bool {
+        (4..=8).contains(&pin.len()) && pin.bytes().all(|byte| byte.is_ascii_digit())
+    }
+
+    /// Calcule l'empreinte Argon2 d'un code PIN, avec un sel aléatoire, au format PHC.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// db.set_pin(user.id, Some(&hash_pin("4321")?)).await?;
+    /// ```
+    pub fn hash_pin(pin: &str) -> Result<String, argon2::password_hash::Error> {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(Argon2::default().hash_password(pin.as_bytes(), &salt)?.to_string())
+    }
+
+    /// Vérifie un code PIN saisi contre son empreinte ; une empreinte illisible ne valide aucun code.
+    pub fn verify_pin(pin: &str, hash: &str) -> bool {
+        PasswordHash::new(hash).is_ok_and(|hash| Argon2::default().verify_password(pin.as_bytes(), &hash).is_ok())
+    }
+
+    /// Clavier de saisie du code PIN, partagé entre les lecteurs et la ligne de commande.
+    ///
+    /// Pendant une demande, la prochaine ligne saisie est remise au lecteur qui attend au lieu d'être exécutée
+    /// comme une commande. Les demandes de plusieurs lecteurs sont servies l'une après l'autre.
+    #[derive(Clone, Debug, Default)]
+    pub struct PinPad {
+        pending: Arc<Mutex<Option<oneshot::Sender<String>>>>,
+        turn: Arc<TurnLock<()>>,
+    }
+
+    impl PinPad {
+        /// Affiche une invite et attend la saisie d'un code.
+        ///
+        /// # Arguments
+        ///
+        /// * `prompt` - L'invite affichée lorsque vient le tour de cette demande.
+        /// * `timeout` - Le délai de saisie, attente des autres demandes comprise.
+        ///
+        /// # Retourne
+        ///
+        /// * `Option<String>` - Le code saisi, ou `None` si le délai expire.
+        pub async fn read(&self, prompt: &str, timeout: Duration) -> Option<String> {
+            time::timeout(timeout, async {
+                let _turn = self.turn.lock().await;
+                let (tx, rx) = oneshot::channel();
+                *self.pending.lock().unwrap() = Some(tx);
+                println!("{}", prompt);
+                rx.await.ok()
+            })
+            .await
+            .ok()
+            .flatten()
+        }
+
+        /// Remet une ligne saisie à la demande en cours.
+        ///
+        /// # Retourne
+        ///
+        /// * `bool` - `true` si la ligne a été prise comme code PIN, `false` si aucun code n'est attendu.
+        pub fn submit(&self, line: &str) -> bool {
+            match self.pending.lock().unwrap().take() {
+                Some(tx) => tx.send(line.to_string()).is_ok(),
+                None => false,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_hash_and_verify() {
+            let hash = hash_pin("4321").unwrap();
+            assert!(hash.starts_with("$argon2id$"));
+            assert_ne!(hash, hash_pin("4321").unwrap());
+            assert!(verify_pin("4321", &hash));
+            assert!(!verify_pin("1234", &hash));
+            assert!(!verify_pin("4321", "empreinte illisible"));
+            assert!(is_valid_pin("0000") && is_valid_pin("12345678"));
+            assert!(!is_valid_pin("123") && !is_valid_pin("123456789") && !is_valid_pin("12a4"));
+        }
+
+        #[tokio::test]
+        async fn test_pin_pad() {
+            let pad = PinPad::default();
+            assert!(!pad.submit("help"));
+
+            let reader = pad.clone();
+            let request = tokio::spawn(async move { reader.read("Code PIN :", Duration::from_secs(5)).await });
+            while !pad.submit("4321") {
+                tokio::task::yield_now().await;
+            }
+            assert_eq!(request.await.unwrap().as_deref(), Some("4321"));
+
+            // Une demande expirée ne retient pas la ligne suivante
+            assert_eq!(pad.read("Code PIN :", Duration::from_millis(10)).await, None);
+            assert!(!pad.submit("help"));
+        }
+    }
+}
@@ -0,0 +1,407 @@
+/// Module `pn532` fournit une couche typée pour envoyer des commandes au contrôleur NXP PN532
+/// intégré à l'ACR122U, via la pseudo-APDU "Direct Transmit" (`FF 00 00 00 Lc D4 ...`).
+pub mod pn532 {
+    use pcsc::Error;
+    use std::str::FromStr;
+    use crate::transport::transport::Transport;
+
+    /// Octet de direction des trames envoyées au PN532.
+    const HOST_TO_PN532: u8 = 0xD4;
+    /// Octet de direction des trames renvoyées par le PN532.
+    const PN532_TO_HOST: u8 = 0xD5;
+
+    /// Débit et type de modulation utilisés par `InListPassiveTarget`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum BaudRate {
+        /// ISO 14443-A à 106 kbps (MIFARE).
+        Iso14443A = 0x00,
+        /// FeliCa à 212 kbps.
+        Felica212 = 0x01,
+        /// FeliCa à 424 kbps.
+        Felica424 = 0x02,
+        /// ISO 14443-B à 106 kbps.
+        Iso14443B = 0x03,
+        /// Innovision Jewel à 106 kbps.
+        Jewel = 0x04,
+    }
+
+    impl BaudRate {
+        /// Données d'initialisation par défaut attendues par le PN532 pour ce type de cible.
+        pub fn initiator_data(self) -> Vec<u8> {
+            match self {
+                BaudRate::Iso14443A | BaudRate::Jewel => Vec::new(),
+                // Polling FeliCa : code système FFFF, pas de requête, un seul créneau
+                BaudRate::Felica212 | BaudRate::Felica424 => vec![0x00, 0xFF, 0xFF, 0x00, 0x00],
+                // AFI 0x00 : toutes les familles d'applications
+                BaudRate::Iso14443B => vec![0x00],
+            }
+        }
+    }
+
+    impl FromStr for BaudRate {
+        type Err = String;
+
+        fn from_str(value: &str) -> Result<Self, Self::Err> {
+            match value.to_lowercase().as_str() {
+                "106a" => Ok(BaudRate::Iso14443A),
+                "212f" => Ok(BaudRate::Felica212),
+                "424f" => Ok(BaudRate::Felica424),
+                "106b" => Ok(BaudRate::Iso14443B),
+                "jewel" => Ok(BaudRate::Jewel),
+                _ => Err(format!("Débit inconnu: {}", value)),
+            }
+        }
+    }
+
+    /// Commande PN532 transmise au travers de l'ACR122U.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Pn532Command {
+        /// Lit la version du PN532.
+        GetFirmwareVersion,
+        /// Configure l'interface RF (champ, délais, nombre de tentatives...).
+        RfConfiguration { item: u8, data: Vec<u8> },
+        /// Recherche des cibles passives au débit indiqué.
+        InListPassiveTarget { max_targets: u8, baud_rate: BaudRate, initiator_data: Vec<u8> },
+        /// Échange une trame brute avec une cible préalablement sélectionnée.
+        InDataExchange { target: u8, data: Vec<u8> },
+        /// Désélectionne une cible (0 pour toutes).
+        InRelease { target: u8 },
+        /// Place le PN532 en mode cible (émulation de carte) et attend un initiateur.
+        TgInitAsTarget { mode: u8, mifare_params: [u8; 6], felica_params: [u8; 18], nfcid3t: [u8; 10] },
+        /// Lit la prochaine commande envoyée par l'initiateur.
+        TgGetData,
+        /// Renvoie une réponse à l'initiateur.
+        TgSetData { data: Vec<u8> },
+    }
+
+    impl Pn532Command {
+        /// Code de la commande PN532.
+        pub fn code(&self) -> u8 {
+            match self {
+                Pn532Command::GetFirmwareVersion => 0x02,
+                Pn532Command::RfConfiguration { .. } => 0x32,
+                Pn532Command::InListPassiveTarget { .. } => 0x4A,
+                Pn532Command::InDataExchange { .. } => 0x40,
+                Pn532Command::InRelease { .. } => 0x52,
+                Pn532Command::TgInitAsTarget { .. } => 0x8C,
+                Pn532Command::TgGetData => 0x86,
+                Pn532Command::TgSetData { .. } => 0x8E,
+            }
+        }
+
+        /// Paramètres de la commande, sans l'octet de direction ni le code.
+        pub fn payload(&self) -> Vec<u8> {
+            match self {
+                Pn532Command::GetFirmwareVersion => Vec::new(),
+                Pn532Command::RfConfiguration { item, data } => [&[*item], data.as_slice()].concat(),
+                Pn532Command::InListPassiveTarget { max_targets, baud_rate, initiator_data } => {
+                    [&[*max_targets, *baud_rate as u8], initiator_data.as_slice()].concat()
+                }
+                Pn532Command::InDataExchange { target, data } => [&[*target], data.as_slice()].concat(),
+                Pn532Command::InRelease { target } => vec![*target],
+                Pn532Command::TgInitAsTarget { mode, mifare_params, felica_params, nfcid3t } => {
+                    // Pas d'octets généraux ni d'octets historiques
+                    [&[*mode], &mifare_params[..], &felica_params[..], &nfcid3t[..], &[0x00, 0x00]].concat()
+                }
+                Pn532Command::TgGetData => Vec::new(),
+                Pn532Command::TgSetData { data } => data.clone(),
+            }
+        }
+
+        /// Construit la pseudo-APDU "Direct Transmit" de l'ACR122U encapsulant la commande.
+        ///
+        /// # Retourne
+        ///
+        /// * `Ok(Vec<u8>)` - La pseudo-APDU.
+        /// * `Err(Error::InvalidParameter)` - Si la trame PN532 dépasse les 255 octets que peut annoncer `Lc`.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let apdu = Pn532Command::GetFirmwareVersion.apdu()?;
+        /// assert_eq!(apdu, [0xFF, 0x00, 0x00, 0x00, 0x02, 0xD4, 0x02]);
+        /// ```
+        pub fn apdu(&self) -> Result<Vec<u8>, Error> {
+            let payload = self.payload();
+            let Ok(length) = u8::try_from(payload.len() + 2) else {
+                eprintln!("Trame PN532 trop longue: {} octets", payload.len() + 2);
+                return Err(Error::InvalidParameter);
+            };
+            let mut apdu = vec![
+                0xFF, // Class
+                0x00, // INS
+                0x00, // P1
+                0x00, // P2
+                length, // Lc: Length of PN532 frame
+                HOST_TO_PN532,
+                self.code(),
+            ];
+            apdu.extend(payload);
+            Ok(apdu)
+        }
+    }
+
+    /// Version du PN532 renvoyée par `GetFirmwareVersion`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct FirmwareVersion {
+        pub ic: u8,
+        pub version: u8,
+        pub revision: u8,
+        pub support: u8,
+    }
+
+    /// Cible détectée par `InListPassiveTarget`.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct PassiveTarget {
+        /// Numéro logique attribué par le PN532, à utiliser avec `InDataExchange`.
+        pub number: u8,
+        pub baud_rate: BaudRate,
+        /// Données de la cible telles que renvoyées par le PN532.
+        pub data: Vec<u8>,
+    }
+
+    impl PassiveTarget {
+        /// Identifiant de la cible (UID, IDm FeliCa, PUPI ISO 14443-B ou identifiant Jewel).
+        pub fn uid(&self) -> Option<&[u8]> {
+            match self.baud_rate {
+                // SENS_RES (2), SEL_RES (1), longueur de l'UID, UID
+                BaudRate::Iso14443A => {
+                    let length = *self.data.get(3)? as usize;
+                    self.data.get(4..4 + length)
+                }
+                // Longueur POL_RES, code réponse 01, IDm (8)
+                BaudRate::Felica212 | BaudRate::Felica424 => self.data.get(2..10),
+                // ATQB : 50, PUPI (4)
+                BaudRate::Iso14443B => self.data.get(1..5),
+                // SENS_RES (2), JEWELID (4)
+                BaudRate::Jewel => self.data.get(2..6),
+            }
+        }
+    }
+
+    /// Structure `Pn532` envoie des commandes au PN532 au travers d'un `Transport`.
+    pub struct Pn532<'a, T: Transport> {
+        transport: &'a T,
+    }
+
+    impl<'a, T: Transport> Pn532<'a, T> {
+        /// Crée la couche PN532 au-dessus d'un transport.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let pn532 = Pn532::new(&reader_manager);
+        /// ```
+        pub fn new(transport: &'a T) -> Pn532<'a, T> {
+            Pn532 { transport }
+        }
+
+        /// Exécute une commande et renvoie les données de la réponse du PN532.
+        ///
+        /// # Arguments
+        ///
+        /// * `command` - La commande à exécuter.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Vec<u8>, Error>` - Les données suivant l'en-tête `D5 <code + 1>`, sans le mot de statut.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let response = pn532.execute(&Pn532Command::GetFirmwareVersion)?;
+        /// ```
+        pub fn execute(&self, command: &Pn532Command) -> Result<Vec<u8>, Error> {
+            let response = self.transport.exchange(&command.apdu()?)?;
+            match response.as_slice() {
+                [PN532_TO_HOST, code, data @ .., 0x90, 0x00] if *code == command.code() + 1 => Ok(data.to_vec()),
+                _ => {
+                    eprintln!("Réponse PN532 inattendue, code: {:02X?}", response);
+                    Err(Error::UnknownError)
+                }
+            }
+        }
+
+        /// Lit la version du PN532.
+        pub fn firmware_version(&self) -> Result<FirmwareVersion, Error> {
+            match self.execute(&Pn532Command::GetFirmwareVersion)?.as_slice() {
+                [ic, version, revision, support, ..] => Ok(FirmwareVersion { ic: *ic, version: *version, revision: *revision, support: *support }),
+                _ => Err(Error::UnknownError),
+            }
+        }
+
+        /// Envoie une commande RFConfiguration.
+        ///
+        /// # Arguments
+        ///
+        /// * `item` - L'élément de configuration (par exemple `0x01` pour le champ RF).
+        /// * `data` - Les valeurs de l'élément.
+        pub fn rf_configuration(&self, item: u8, data: &[u8]) -> Result<(), Error> {
+            self.execute(&Pn532Command::RfConfiguration { item, data: data.to_vec() })?;
+            Ok(())
+        }
+
+        /// Recherche une cible passive au débit indiqué.
+        ///
+        /// # Arguments
+        ///
+        /// * `baud_rate` - Le débit et le type de cible recherchés.
+        ///
+        /// # Retourne
+        ///
+        /// * `Ok(Some(PassiveTarget))` - La cible détectée.
+        /// * `Ok(None)` - Si aucune cible n'est présente.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let target = pn532.in_list_passive_target(BaudRate::Iso14443A)?;
+        /// ```
+        pub fn in_list_passive_target(&self, baud_rate: BaudRate) -> Result<Option<PassiveTarget>, Error> {
+            let command = Pn532Command::InListPassiveTarget { max_targets: 1, baud_rate, initiator_data: baud_rate.initiator_data() };
+            match self.execute(&command)?.as_slice() {
+                [0x00, ..] | [] => Ok(None),
+                [_, number, data @ ..] => Ok(Some(PassiveTarget { number: *number, baud_rate, data: data.to_vec() })),
+                _ => Err(Error::UnknownError),
+            }
+        }
+
+        /// Échange une trame ISO 14443-3/4 brute avec une cible sélectionnée.
+        ///
+        /// # Arguments
+        ///
+        /// * `target` - Le numéro logique de la cible.
+        /// * `data` - La trame à envoyer.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Vec<u8>, Error>` - La trame renvoyée par la cible.
+        pub fn in_data_exchange(&self, target: u8, data: &[u8]) -> Result<Vec<u8>, Error> {
+            let response = self.execute(&Pn532Command::InDataExchange { target, data: data.to_vec() })?;
+            check_status(&response)?;
+            Ok(response[1..].to_vec())
+        }
+
+        /// Désélectionne une cible, ou toutes les cibles si `target` vaut 0.
+        pub fn in_release(&self, target: u8) -> Result<(), Error> {
+            let response = self.execute(&Pn532Command::InRelease { target })?;
+            check_status(&response)
+        }
+
+        /// Place le PN532 en mode cible ISO 14443-4A et attend qu'un initiateur (un téléphone) le sélectionne.
+        ///
+        /// # Arguments
+        ///
+        /// * `nfcid` - Les trois derniers octets de l'UID annoncé, le premier étant imposé à `08` (UID aléatoire).
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Vec<u8>, Error>` - Le mode d'activation suivi de la première trame de l'initiateur.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// pn532.tg_init_as_target([0x12, 0x34, 0x56])?;
+        /// ```
+        pub fn tg_init_as_target(&self, nfcid: [u8; 3]) -> Result<Vec<u8>, Error> {
+            self.execute(&Pn532Command::TgInitAsTarget {
+                mode: 0x05, // PICC only, passive only
+                mifare_params: [
+                    0x04, 0x00, // SENS_RES
+                    nfcid[0], nfcid[1], nfcid[2],
+                    0x20, // SEL_RES: ISO 14443-4 compliant
+                ],
+                felica_params: [0x00; 18],
+                nfcid3t: [0x00; 10],
+            })
+        }
+
+        /// Lit la prochaine APDU envoyée par l'initiateur.
+        ///
+        /// # Retourne
+        ///
+        /// * `Ok(Vec<u8>)` - L'APDU de commande reçue.
+        /// * `Err(Error::RemovedCard)` - Si l'initiateur a quitté le champ ou libéré la cible.
+        pub fn tg_get_data(&self) -> Result<Vec<u8>, Error> {
+            let response = self.execute(&Pn532Command::TgGetData)?;
+            check_status(&response)?;
+            Ok(response[1..].to_vec())
+        }
+
+        /// Envoie une APDU de réponse à l'initiateur.
+        pub fn tg_set_data(&self, data: &[u8]) -> Result<(), Error> {
+            let response = self.execute(&Pn532Command::TgSetData { data: data.to_vec() })?;
+            check_status(&response)
+        }
+    }
+
+    /// Vérifie l'octet de statut placé en tête des réponses du PN532.
+    pub(crate) fn check_status(response: &[u8]) -> Result<(), Error> {
+        match response.first().map(|status| status & 0x3F) {
+            Some(0x00) => Ok(()),
+            Some(0x01) => Err(Error::Timeout),
+            Some(0x29) => Err(Error::RemovedCard),
+            status => {
+                eprintln!("Erreur PN532, statut: {:02X?}", status);
+                Err(Error::CommError)
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::cell::RefCell;
+
+        /// Transport de test renvoyant une réponse fixe et mémorisant les APDU envoyées.
+        struct MockTransport {
+            response: Vec<u8>,
+            sent: RefCell<Vec<Vec<u8>>>,
+        }
+
+        impl Transport for MockTransport {
+            fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+                self.sent.borrow_mut().push(apdu.to_vec());
+                Ok(self.response.clone())
+            }
+        }
+
+        fn mock(response: &[u8]) -> MockTransport {
+            MockTransport { response: response.to_vec(), sent: RefCell::new(Vec::new()) }
+        }
+
+        #[test]
+        fn test_command_apdu() {
+            let command = Pn532Command::InDataExchange { target: 1, data: vec![0x30, 0x04] };
+            assert_eq!(command.apdu().unwrap(), [0xFF, 0x00, 0x00, 0x00, 0x05, 0xD4, 0x40, 0x01, 0x30, 0x04]);
+
+            // Lc tient sur un octet : la trame la plus longue porte 252 octets de données après le numéro de cible
+            assert_eq!(Pn532Command::InDataExchange { target: 1, data: vec![0x00; 252] }.apdu().unwrap()[4], 0xFF);
+            let transport = mock(&[0xD5, 0x41, 0x00, 0x90, 0x00]);
+            assert_eq!(Pn532::new(&transport).in_data_exchange(1, &[0x00; 253]), Err(Error::InvalidParameter));
+            assert!(transport.sent.borrow().is_empty());
+        }
+
+        #[test]
+        fn test_in_list_passive_target() {
+            let transport = mock(&[0xD5, 0x4B, 0x01, 0x01, 0x00, 0x04, 0x08, 0x04, 0xA4, 0x50, 0x4F, 0xA1, 0x90, 0x00]);
+            let target = Pn532::new(&transport).in_list_passive_target(BaudRate::Iso14443A).unwrap().unwrap();
+            assert_eq!(target.number, 1);
+            assert_eq!(target.uid(), Some(&[0xA4, 0x50, 0x4F, 0xA1][..]));
+            assert_eq!(transport.sent.borrow()[0], [0xFF, 0x00, 0x00, 0x00, 0x04, 0xD4, 0x4A, 0x01, 0x00]);
+        }
+
+        #[test]
+        fn test_in_data_exchange_error() {
+            let transport = mock(&[0xD5, 0x41, 0x01, 0x90, 0x00]);
+            let result = Pn532::new(&transport).in_data_exchange(1, &[0x30, 0x04]);
+            assert_eq!(result, Err(Error::Timeout));
+        }
+
+        #[test]
+        fn test_unexpected_response() {
+            let transport = mock(&[0x63, 0x00]);
+            assert!(Pn532::new(&transport).firmware_version().is_err());
+        }
+    }
+}
@@ -0,0 +1,361 @@
+/// Module `schedule` évalue les plages horaires d'accès des groupes, à l'heure du site, et lit les calendriers
+/// de jours fériés au format iCalendar.
+pub mod schedule {
+    use chrono::{Local, Offset, TimeZone, Utc};
+    use chrono_tz::Tz;
+    use std::fmt;
+    use std::fs;
+    use std::io::{self, ErrorKind};
+    use crate::timesheet::timesheet::{format_day, parse_day, weekday, SECONDS_PER_DAY, WEEKDAYS};
+
+    /// Nombre de minutes dans une journée.
+    pub const MINUTES_PER_DAY: i64 = 1440;
+
+    /// Source de l'heure courante, remplaçable pour les tests ou pour rejouer un passage à une date donnée.
+    pub trait Clock: Send + Sync {
+        /// Heure locale du site, en secondes depuis le 1er janvier 1970, comme `parse_timestamp`.
+        fn now(&self) -> i64;
+    }
+
+    /// Horloge système, convertie dans le fuseau horaire du site ou, à défaut, dans celui de la machine.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct SystemClock {
+        pub timezone: Option<Tz>,
+    }
+
+    impl SystemClock {
+        /// Convertit un instant UTC, en secondes Unix, en heure locale du site, changements d'heure compris.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let clock = SystemClock { timezone: Some(chrono_tz::Europe::Paris) };
+        /// assert_eq!(clock.local(0), 3600);
+        /// ```
+        pub fn local(&self, utc: i64) -> i64 {
+            utc + self.offset(utc)
+        }
+
+        /// Convertit une heure locale du site en instant UTC, réciproque de `local`.
+        ///
+        /// Une heure répétée au passage à l'heure d'hiver est prise avec le décalage d'après le changement.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let clock = SystemClock { timezone: Some(chrono_tz::Europe::Paris) };
+        /// assert_eq!(clock.utc(3600), 0);
+        /// ```
+        pub fn utc(&self, local: i64) -> i64 {
+            local - self.offset(local - self.offset(local))
+        }
+
+        /// Décalage de l'heure locale sur UTC, en secondes, à un instant UTC donné.
+        fn offset(&self, utc: i64) -> i64 {
+            let Some(instant) = Utc.timestamp_opt(utc, 0).single() else {
+                return 0;
+            };
+            let offset = match self.timezone {
+                Some(timezone) => timezone.offset_from_utc_datetime(&instant.naive_utc()).fix().local_minus_utc(),
+                None => Local.offset_from_utc_datetime(&instant.naive_utc()).local_minus_utc(),
+            };
+            offset as i64
+        }
+    }
+
+    impl Clock for SystemClock {
+        fn now(&self) -> i64 {
+            self.local(Utc::now().timestamp())
+        }
+    }
+
+    /// Horloge arrêtée à une heure locale donnée.
+    #[derive(Clone, Copy, Debug)]
+    pub struct FixedClock(pub i64);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> i64 {
+            self.0
+        }
+    }
+
+    /// Plage horaire hebdomadaire, en minutes depuis minuit.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct TimeWindow {
+        /// Jour de la semaine, de 0 pour lundi à 6 pour dimanche.
+        pub weekday: usize,
+        pub start: i64,
+        /// Fin de la plage, exclue.
+        pub end: i64,
+    }
+
+    impl TimeWindow {
+        /// Indique si une heure locale tombe dans la plage.
+        pub fn contains(&self, time: i64) -> bool {
+            let minute = time.rem_euclid(SECONDS_PER_DAY) / 60;
+            weekday(time.div_euclid(SECONDS_PER_DAY)) == self.weekday && self.start <= minute && minute < self.end
+        }
+    }
+
+    impl fmt::Display for TimeWindow {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{} {}-{}", WEEKDAYS[self.weekday], format_minutes(self.start), format_minutes(self.end))
+        }
+    }
+
+    /// Horaires d'un groupe d'accès.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct GroupSchedule {
+        pub group: String,
+        pub windows: Vec<TimeWindow>,
+        /// Calendrier des jours fériés appliqué au groupe.
+        pub calendar: Option<String>,
+        /// Dates (`AAAA-MM-JJ`) où le groupe est ouvert (`true`) ou fermé toute la journée.
+        pub exceptions: Vec<(String, bool)>,
+        /// Jours fériés du calendrier du groupe, au format `AAAA-MM-JJ`.
+        pub holidays: Vec<String>,
+    }
+
+    impl GroupSchedule {
+        /// Indique si le groupe a accès à une heure locale donnée.
+        ///
+        /// Une exception l'emporte sur le reste, un jour férié est fermé, et un groupe sans plage horaire
+        /// a accès à toute heure.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let now = parse_timestamp("2024-06-14 08:30:00").unwrap();
+        /// assert!(schedule.is_open(now));
+        /// ```
+        pub fn is_open(&self, time: i64) -> bool {
+            let day = format_day(time.div_euclid(SECONDS_PER_DAY));
+            if let Some((_, open)) = self.exceptions.iter().find(|(date, _)| *date == day) {
+                return *open;
+            }
+            if self.holidays.contains(&day) {
+                return false;
+            }
+            self.windows.is_empty() || self.windows.iter().any(|window| window.contains(time))
+        }
+    }
+
+    /// Analyse une liste de jours de la semaine : `lun-ven`, `lun,mer,ven` ou `samedi`.
+    ///
+    /// # Retourne
+    ///
+    /// * `Option<Vec<usize>>` - Les jours, de 0 pour lundi à 6 pour dimanche, ou `None` si la liste est invalide.
+    pub fn parse_weekdays(days: &str) -> Option<Vec<usize>> {
+        let index = |day: &str| WEEKDAYS.iter().position(|name| day.len() >= 3 && name.starts_with(&day.to_lowercase()));
+        let mut weekdays = Vec::new();
+        for part in days.split(',') {
+            match part.split_once('-') {
+                Some((from, to)) => {
+                    let (from, to) = (index(from)?, index(to)?);
+                    if from > to {
+                        return None;
+                    }
+                    weekdays.extend(from..=to);
+                }
+                None => weekdays.push(index(part)?),
+            }
+        }
+        weekdays.sort_unstable();
+        weekdays.dedup();
+        Some(weekdays)
+    }
+
+    /// Analyse une plage horaire `HH:MM-HH:MM`, la fin pouvant aller jusqu'à `24:00`.
+    ///
+    /// # Retourne
+    ///
+    /// * `Option<(i64, i64)>` - Le début et la fin en minutes depuis minuit.
+    pub fn parse_window(window: &str) -> Option<(i64, i64)> {
+        let (start, end) = window.split_once('-')?;
+        let (start, end) = (parse_minutes(start)?, parse_minutes(end)?);
+        (start < end).then_some((start, end))
+    }
+
+    /// Analyse une heure `HH:MM`, de `00:00` à `24:00`, en minutes depuis minuit.
+    pub fn parse_minutes(time: &str) -> Option<i64> {
+        let (hours, minutes) = time.split_once(':')?;
+        let minutes = hours.parse::<i64>().ok()? * 60 + minutes.parse::<i64>().ok().filter(|minutes| (0..60).contains(minutes))?;
+        (0..=MINUTES_PER_DAY).contains(&minutes).then_some(minutes)
+    }
+
+    /// Analyse une heure locale `AAAA-MM-JJTHH:MM`, ou `HH:MM` pour le jour de `now`.
+    ///
+    /// # Retourne
+    ///
+    /// * `Option<i64>` - L'heure en secondes depuis le 1er janvier 1970, comme `Clock::now`.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// let until = parse_local_time("18:00", clock.now());
+    /// ```
+    pub fn parse_local_time(value: &str, now: i64) -> Option<i64> {
+        let (day, time) = match value.split_once('T') {
+            Some((day, time)) => (parse_day(day)?, time),
+            None => (now.div_euclid(SECONDS_PER_DAY), value),
+        };
+        let minutes = parse_minutes(time).filter(|minutes| *minutes < MINUTES_PER_DAY)?;
+        Some(day * SECONDS_PER_DAY + minutes * 60)
+    }
+
+    /// Formate un nombre de minutes depuis minuit au format `HH:MM`.
+    pub fn format_minutes(minutes: i64) -> String {
+        format!("{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+
+    /// Jour férié lu dans un calendrier.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Holiday {
+        /// Date au format `AAAA-MM-JJ`.
+        pub day: String,
+        pub summary: Option<String>,
+    }
+
+    /// Lit un calendrier de jours fériés au format iCalendar (`.ics`).
+    pub fn read_ics(path: &str) -> io::Result<Vec<Holiday>> {
+        parse_ics(&fs::read_to_string(path)?)
+    }
+
+    /// Analyse un calendrier iCalendar (RFC 5545) et renvoie les jours couverts par ses événements.
+    ///
+    /// Un événement de plusieurs jours couvre chaque jour de `DTSTART` jusqu'à la veille de `DTEND`.
+    /// Les règles de récurrence (`RRULE`) ne sont pas développées : seule la première occurrence est retenue.
+    pub fn parse_ics(content: &str) -> io::Result<Vec<Holiday>> {
+        let invalid = |message: String| io::Error::new(ErrorKind::InvalidData, message);
+
+        // Les lignes repliées commencent par un espace ou une tabulation
+        let mut lines: Vec<String> = Vec::new();
+        for line in content.lines().map(|line| line.trim_end_matches('\r')) {
+            match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+                (Some(continuation), Some(last)) => last.push_str(continuation),
+                _ => lines.push(line.to_string()),
+            }
+        }
+
+        let mut holidays = Vec::new();
+        let mut event: Option<(Option<i64>, Option<i64>, Option<String>)> = None;
+        for (index, line) in lines.iter().enumerate() {
+            let Some((property, value)) = line.split_once(':') else {
+                continue;
+            };
+            let name = property.split(';').next().unwrap_or_default().to_ascii_uppercase();
+            let date = || {
+                let digits = value.get(..8).filter(|digits| digits.chars().all(|c| c.is_ascii_digit()));
+                digits.and_then(|digits| parse_day(&format!("{}-{}-{}", &digits[..4], &digits[4..6], &digits[6..])))
+            };
+            match (name.as_str(), event.as_mut()) {
+                ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => event = Some((None, None, None)),
+                ("DTSTART", Some(event)) => event.0 = Some(date().ok_or_else(|| invalid(format!("ligne {} : date invalide {}", index + 1, value)))?),
+                ("DTEND", Some(event)) => event.1 = date(),
+                ("SUMMARY", Some(event)) => event.2 = Some(unescape_text(value)),
+                ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                    let Some((Some(start), end, summary)) = event.take() else {
+                        return Err(invalid(format!("ligne {} : événement sans DTSTART", index + 1)));
+                    };
+                    let end = end.filter(|end| *end > start).unwrap_or(start + 1);
+                    holidays.extend((start..end).map(|day| Holiday { day: format_day(day), summary: summary.clone() }));
+                }
+                _ => {}
+            }
+        }
+        holidays.sort_by(|a, b| a.day.cmp(&b.day));
+        holidays.dedup_by(|a, b| a.day == b.day);
+        Ok(holidays)
+    }
+
+    /// Retire l'échappement d'une valeur texte iCalendar.
+    fn unescape_text(value: &str) -> String {
+        let mut text = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('n') | Some('N') => text.push(' '),
+                    Some(escaped) => text.push(escaped),
+                    None => {}
+                },
+                c => text.push(c),
+            }
+        }
+        text
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::timesheet::timesheet::parse_timestamp;
+
+        #[test]
+        fn test_is_open() {
+            // Du lundi au vendredi de 07:00 à 20:00, le 14 juillet férié
+            let (start, end) = parse_window("07:00-20:00").unwrap();
+            let schedule = GroupSchedule {
+                group: String::from("Bureaux"),
+                windows: parse_weekdays("lun-ven").unwrap().into_iter().map(|weekday| TimeWindow { weekday, start, end }).collect(),
+                calendar: Some(String::from("France")),
+                exceptions: vec![(String::from("2024-07-13"), true), (String::from("2024-07-12"), false)],
+                holidays: vec![String::from("2024-07-14"), String::from("2024-07-15")],
+            };
+            let at = |timestamp: &str| schedule.is_open(parse_timestamp(timestamp).unwrap());
+            assert!(at("2024-07-10 07:00:00"));
+            assert!(!at("2024-07-10 06:59:59"));
+            assert!(!at("2024-07-10 20:00:00"));
+            assert!(!at("2024-07-12 10:00:00"));
+            assert!(at("2024-07-13 23:00:00"));
+            assert!(!at("2024-07-15 10:00:00"));
+            assert!(!at("2024-07-20 10:00:00"));
+            assert!(GroupSchedule::default().is_open(parse_timestamp("2024-07-20 03:00:00").unwrap()));
+        }
+
+        #[test]
+        fn test_parse_weekdays_and_window() {
+            assert_eq!(parse_weekdays("lun-ven"), Some(vec![0, 1, 2, 3, 4]));
+            assert_eq!(parse_weekdays("samedi,dim,sam"), Some(vec![5, 6]));
+            assert_eq!(parse_weekdays("ven-lun"), None);
+            assert_eq!(parse_weekdays("lu"), None);
+            assert_eq!(parse_window("07:30-24:00"), Some((450, 1440)));
+            assert_eq!(parse_window("20:00-07:00"), None);
+            assert_eq!(parse_window("07:60-08:00"), None);
+            assert_eq!(format_minutes(450), "07:30");
+
+            let now = parse_timestamp("2024-06-14 10:00:00").unwrap();
+            assert_eq!(parse_local_time("18:30", now), parse_timestamp("2024-06-14 18:30:00"));
+            assert_eq!(parse_local_time("2024-06-15T08:05", now), parse_timestamp("2024-06-15 08:05:00"));
+            assert_eq!(parse_local_time("24:00", now), None);
+            assert_eq!(parse_local_time("2024-06-31T08:00", now), None);
+        }
+
+        #[test]
+        fn test_parse_ics() {
+            let calendar = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20250714\r\nSUMMARY:Fête\r\n  nationale\r\nEND:VEVENT\r\n\
+                BEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20251224\r\nDTEND;VALUE=DATE:20251227\r\nSUMMARY:Noël\\, fermeture\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+            let holidays = parse_ics(calendar).unwrap();
+            let days: Vec<&str> = holidays.iter().map(|holiday| holiday.day.as_str()).collect();
+            assert_eq!(days, ["2025-07-14", "2025-12-24", "2025-12-25", "2025-12-26"]);
+            assert_eq!(holidays[0].summary.as_deref(), Some("Fête nationale"));
+            assert_eq!(holidays[1].summary.as_deref(), Some("Noël, fermeture"));
+            assert!(parse_ics("BEGIN:VEVENT\nSUMMARY:x\nEND:VEVENT\n").is_err());
+            assert!(parse_ics("BEGIN:VEVENT\nDTSTART:2025\nEND:VEVENT\n").is_err());
+        }
+
+        #[test]
+        fn test_system_clock_timezone() {
+            let paris = SystemClock { timezone: Some(chrono_tz::Europe::Paris) };
+            let utc = |timestamp: &str| parse_timestamp(timestamp).unwrap();
+            assert_eq!(paris.local(utc("2024-01-15 10:00:00")), utc("2024-01-15 11:00:00"));
+            assert_eq!(paris.local(utc("2024-07-15 10:00:00")), utc("2024-07-15 12:00:00"));
+            // Passage à l'heure d'été le 31 mars 2024 à 01:00 UTC
+            assert_eq!(paris.local(utc("2024-03-31 00:59:59")), utc("2024-03-31 01:59:59"));
+            assert_eq!(paris.local(utc("2024-03-31 01:00:00")), utc("2024-03-31 03:00:00"));
+            assert_eq!(paris.utc(utc("2024-07-15 00:00:00")), utc("2024-07-14 22:00:00"));
+            assert_eq!(paris.utc(utc("2024-03-31 03:00:00")), utc("2024-03-31 01:00:00"));
+            assert_eq!(paris.utc(utc("2024-10-27 02:30:00")), utc("2024-10-27 01:30:00"));
+            assert_eq!(FixedClock(42).now(), 42);
+        }
+    }
+}
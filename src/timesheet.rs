@@ -0,0 +1,404 @@
+/// Module `timesheet` regroupe les passages de badge en sessions de travail et calcule les heures effectuées.
+///
+/// Les horodatages sont exprimés en secondes depuis le 1er janvier 1970 en heure locale, et les jours
+/// en nombre de jours depuis cette même date.
+pub mod timesheet {
+    use crate::config::config::{Config, TimesheetConfig};
+    use crate::dbo::dbo::{BadgeRecord, BadgeResult};
+
+    pub const SECONDS_PER_DAY: i64 = 86_400;
+
+    /// Noms des jours de la semaine, du lundi au dimanche.
+    pub const WEEKDAYS: [&str; 7] = ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"];
+
+    /// Sens d'un pointage.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Direction {
+        In,
+        Out,
+    }
+
+    /// Pointage : un passage de badge accepté sur un lecteur d'entrée ou de sortie.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Punch {
+        pub time: i64,
+        pub direction: Direction,
+    }
+
+    /// Session de travail entre une entrée et une sortie.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct WorkSession {
+        pub start: i64,
+        /// Heure de sortie, `None` si la sortie n'a pas été badgée.
+        pub end: Option<i64>,
+    }
+
+    /// Feuille de temps hebdomadaire d'un utilisateur.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Timesheet {
+        /// Le lundi de la semaine.
+        pub week_start: i64,
+        /// Secondes travaillées chaque jour, du lundi au dimanche.
+        pub days: [i64; 7],
+        /// Sessions commençant ou se terminant dans la semaine.
+        pub sessions: Vec<WorkSession>,
+    }
+
+    impl Timesheet {
+        /// Calcule la feuille de temps d'une semaine à partir de l'historique des passages.
+        ///
+        /// L'historique doit commencer la veille du lundi pour compter les sessions qui traversent minuit.
+        ///
+        /// # Arguments
+        ///
+        /// * `records` - Les passages de l'utilisateur, du plus ancien au plus récent.
+        /// * `config` - La configuration, qui donne le rôle des lecteurs et les règles de calcul.
+        /// * `week_start` - Le lundi de la semaine.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let week_start = parse_week("2024-W24").unwrap();
+        /// let timesheet = Timesheet::build(&records, &config, week_start);
+        /// println!("{}", format_duration(timesheet.total()));
+        /// ```
+        pub fn build(records: &[BadgeRecord], config: &Config, week_start: i64) -> Timesheet {
+            let sessions = pair_sessions(&punches(records, config), !config.has_exit_reader(), &config.timesheet);
+            let week_end = (week_start + 7) * SECONDS_PER_DAY;
+
+            let mut days = [0; 7];
+            days.copy_from_slice(&daily_seconds(&sessions, week_start, 7));
+            let sessions = sessions
+                .into_iter()
+                .filter(|session| session.start < week_end && session.end.unwrap_or(session.start) >= week_start * SECONDS_PER_DAY)
+                .collect();
+
+            Timesheet { week_start, days, sessions }
+        }
+
+        /// Total de la semaine en secondes.
+        pub fn total(&self) -> i64 {
+            self.days.iter().sum()
+        }
+
+        /// Sessions dont la sortie n'a pas été badgée.
+        pub fn forgotten(&self) -> impl Iterator<Item = &WorkSession> {
+            self.sessions.iter().filter(|session| session.end.is_none())
+        }
+    }
+
+    /// Convertit les passages acceptés en pointages, selon le rôle du lecteur.
+    ///
+    /// Les passages refusés, illisibles ou effectués sur le poste d'enrôlement sont ignorés.
+    pub fn punches(records: &[BadgeRecord], config: &Config) -> Vec<Punch> {
+        records
+            .iter()
+            .filter(|record| record.result == BadgeResult::Granted)
+            .filter_map(|record| {
+                let direction = config.reader(&record.reader).role.direction()?;
+                Some(Punch { time: parse_timestamp(&record.timestamp)?, direction })
+            })
+            .collect()
+    }
+
+    /// Associe les pointages en sessions de travail.
+    ///
+    /// Une entrée suivie d'une autre entrée (ou une session plus longue que `max_session_hours`) est un oubli
+    /// de badge à la sortie : la session est conservée sans heure de sortie et ne compte aucune heure.
+    /// Une sortie sans entrée est ignorée.
+    ///
+    /// # Arguments
+    ///
+    /// * `punches` - Les pointages, du plus ancien au plus récent.
+    /// * `alternate` - Sans lecteur de sortie, chaque passage alterne entre entrée et sortie.
+    /// * `config` - L'arrondi des heures et la durée maximale d'une session.
+    pub fn pair_sessions(punches: &[Punch], alternate: bool, config: &TimesheetConfig) -> Vec<WorkSession> {
+        let max_session = config.max_session_hours as i64 * 3600;
+        let mut sessions = Vec::new();
+        let mut open: Option<i64> = None;
+
+        for punch in punches {
+            let time = round(punch.time, config.rounding_minutes);
+            if let Some(start) = open.filter(|start| time - start > max_session) {
+                sessions.push(WorkSession { start, end: None });
+                open = None;
+            }
+
+            match (punch.direction, open) {
+                (Direction::In, Some(start)) if alternate => {
+                    sessions.push(WorkSession { start, end: Some(time) });
+                    open = None;
+                }
+                (Direction::In, Some(start)) => {
+                    sessions.push(WorkSession { start, end: None });
+                    open = Some(time);
+                }
+                (Direction::In, None) => open = Some(time),
+                (Direction::Out, Some(start)) => {
+                    sessions.push(WorkSession { start, end: Some(time) });
+                    open = None;
+                }
+                (Direction::Out, None) => {}
+            }
+        }
+
+        if let Some(start) = open {
+            sessions.push(WorkSession { start, end: None });
+        }
+        sessions
+    }
+
+    /// Répartit les heures des sessions par jour, en coupant à minuit.
+    ///
+    /// # Arguments
+    ///
+    /// * `sessions` - Les sessions de travail.
+    /// * `first_day` - Le premier jour.
+    /// * `count` - Le nombre de jours.
+    ///
+    /// # Retourne
+    ///
+    /// * `Vec<i64>` - Les secondes travaillées chaque jour.
+    pub fn daily_seconds(sessions: &[WorkSession], first_day: i64, count: usize) -> Vec<i64> {
+        let mut days = vec![0; count];
+        for session in sessions {
+            let Some(end) = session.end else { continue };
+            let mut time = session.start;
+            while time < end {
+                let day = time.div_euclid(SECONDS_PER_DAY);
+                let next = ((day + 1) * SECONDS_PER_DAY).min(end);
+                if let Some(total) = usize::try_from(day - first_day).ok().and_then(|index| days.get_mut(index)) {
+                    *total += next - time;
+                }
+                time = next;
+            }
+        }
+        days
+    }
+
+    /// Arrondit une heure au multiple de `minutes` le plus proche.
+    fn round(time: i64, minutes: u32) -> i64 {
+        let step = minutes as i64 * 60;
+        if step == 0 {
+            return time;
+        }
+        (time + step / 2).div_euclid(step) * step
+    }
+
+    /// Nombre de jours depuis le 1er janvier 1970 pour une date du calendrier grégorien.
+    fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// Date du calendrier grégorien correspondant à un nombre de jours depuis le 1er janvier 1970.
+    fn civil_from_days(days: i64) -> (i64, i64, i64) {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    }
+
+    /// Jour de la semaine, de 0 pour lundi à 6 pour dimanche.
+    pub fn weekday(day: i64) -> usize {
+        (day + 3).rem_euclid(7) as usize
+    }
+
+    /// Analyse une date au format `AAAA-MM-JJ`.
+    pub fn parse_day(day: &str) -> Option<i64> {
+        let mut parts = day.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+        let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        let days = days_from_civil(year, month, day);
+        (civil_from_days(days) == (year, month, day)).then_some(days)
+    }
+
+    /// Analyse un horodatage au format `AAAA-MM-JJ HH:MM:SS`.
+    pub fn parse_timestamp(timestamp: &str) -> Option<i64> {
+        let (day, time) = timestamp.split_once(' ')?;
+        let mut parts = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+        let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+        Some(parse_day(day)? * SECONDS_PER_DAY + hours * 3600 + minutes * 60 + seconds)
+    }
+
+    /// Analyse une semaine ISO (`AAAA-Wss`) ou une date de la semaine (`AAAA-MM-JJ`).
+    ///
+    /// # Retourne
+    ///
+    /// * `Option<i64>` - Le lundi de la semaine.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// assert_eq!(parse_week("2026-W01"), parse_day("2025-12-29"));
+    /// ```
+    pub fn parse_week(week: &str) -> Option<i64> {
+        match week.split_once("-W") {
+            Some((year, number)) => {
+                let (year, number) = (year.parse::<i64>().ok()?, number.parse::<i64>().ok()?);
+                let january_4 = days_from_civil(year, 1, 4);
+                let monday = january_4 - weekday(january_4) as i64 + (number - 1) * 7;
+                // Le jeudi d'une semaine ISO appartient toujours à l'année de la semaine
+                (number >= 1 && civil_from_days(monday + 3).0 == year).then_some(monday)
+            }
+            None => parse_day(week).map(|day| day - weekday(day) as i64),
+        }
+    }
+
+    /// Formate un jour au format `AAAA-MM-JJ`.
+    pub fn format_day(day: i64) -> String {
+        let (year, month, day) = civil_from_days(day);
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    /// Analyse un mois au format `AAAA-MM`.
+    ///
+    /// # Retourne
+    ///
+    /// * `Option<(i64, i64)>` - Le premier et le dernier jour du mois.
+    pub fn parse_month(month: &str) -> Option<(i64, i64)> {
+        let (year, month) = month.split_once('-')?;
+        let (year, month) = (year.parse::<i64>().ok()?, month.parse::<i64>().ok()?);
+        if !(1..=12).contains(&month) {
+            return None;
+        }
+        let next = if month == 12 { days_from_civil(year + 1, 1, 1) } else { days_from_civil(year, month + 1, 1) };
+        Some((days_from_civil(year, month, 1), next - 1))
+    }
+
+    /// Formate l'heure d'un horodatage au format `HH:MM`.
+    pub fn format_hour(time: i64) -> String {
+        let seconds = time.rem_euclid(SECONDS_PER_DAY);
+        format!("{:02}:{:02}", seconds / 3600, seconds % 3600 / 60)
+    }
+
+    /// Formate un horodatage au format `AAAA-MM-JJ HH:MM:SS`, réciproque de `parse_timestamp`.
+    pub fn format_timestamp(time: i64) -> String {
+        format!("{}:{:02}", format_time(time), time.rem_euclid(60))
+    }
+
+    /// Formate un horodatage au format `AAAA-MM-JJ HH:MM`.
+    pub fn format_time(time: i64) -> String {
+        format!("{} {}", format_day(time.div_euclid(SECONDS_PER_DAY)), format_hour(time))
+    }
+
+    /// Formate une durée en heures et minutes, par exemple `7h05`.
+    pub fn format_duration(seconds: i64) -> String {
+        format!("{}h{:02}", seconds / 3600, seconds % 3600 / 60)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::config::config::{ReaderConfig, ReaderRole};
+
+        fn time(timestamp: &str) -> i64 {
+            parse_timestamp(timestamp).unwrap()
+        }
+
+        fn punch(timestamp: &str, direction: Direction) -> Punch {
+            Punch { time: time(timestamp), direction }
+        }
+
+        fn record(timestamp: &str, reader: &str) -> BadgeRecord {
+            BadgeRecord {
+                id: 0,
+                timestamp: timestamp.to_string(),
+                card_uid: String::from("0A0B"),
+                user_id: Some(1),
+                user_name: Some(String::from("Alice")),
+                reader: reader.to_string(),
+                result: BadgeResult::Granted,
+                reason: None,
+            }
+        }
+
+        #[test]
+        fn test_dates() {
+            assert_eq!(parse_day("1970-01-01"), Some(0));
+            assert_eq!(format_day(parse_day("2024-02-29").unwrap()), "2024-02-29");
+            assert_eq!(parse_day("2023-02-29"), None);
+            assert_eq!(weekday(parse_day("2026-10-18").unwrap()), 6);
+            assert_eq!(parse_week("2026-W01"), parse_day("2025-12-29"));
+            assert_eq!(parse_week("2026-10-15"), parse_day("2026-10-12"));
+            assert_eq!(parse_week("2026-W53"), parse_day("2026-12-28"));
+            assert_eq!(parse_week("2025-W53"), None);
+            assert_eq!(format_time(time("2026-10-12 08:05:59")), "2026-10-12 08:05");
+            assert_eq!(format_timestamp(time("2026-10-12 08:05:59")), "2026-10-12 08:05:59");
+            assert_eq!(format_duration(7 * 3600 + 5 * 60), "7h05");
+            assert_eq!(parse_month("2024-02"), Some((parse_day("2024-02-01").unwrap(), parse_day("2024-02-29").unwrap())));
+            assert_eq!(parse_month("2024-12").map(|(_, last)| format_day(last)), Some(String::from("2024-12-31")));
+            assert_eq!(parse_month("2024-13"), None);
+        }
+
+        #[test]
+        fn test_pair_sessions_with_exit_readers() {
+            let punches = [
+                punch("2026-10-12 08:00:00", Direction::In),
+                punch("2026-10-12 12:00:00", Direction::Out),
+                punch("2026-10-12 13:00:00", Direction::In),
+                punch("2026-10-13 08:00:00", Direction::In),
+                punch("2026-10-13 08:30:00", Direction::Out),
+                punch("2026-10-13 09:00:00", Direction::Out),
+            ];
+            let sessions = pair_sessions(&punches, false, &TimesheetConfig::default());
+            assert_eq!(sessions, [
+                WorkSession { start: time("2026-10-12 08:00:00"), end: Some(time("2026-10-12 12:00:00")) },
+                WorkSession { start: time("2026-10-12 13:00:00"), end: None },
+                WorkSession { start: time("2026-10-13 08:00:00"), end: Some(time("2026-10-13 08:30:00")) },
+            ]);
+        }
+
+        #[test]
+        fn test_pair_sessions_alternate_and_rounding() {
+            let punches = [
+                punch("2026-10-12 07:53:00", Direction::In),
+                punch("2026-10-12 16:08:00", Direction::In),
+                punch("2026-10-13 08:00:00", Direction::In),
+                punch("2026-10-14 09:00:00", Direction::In),
+            ];
+            let config = TimesheetConfig { rounding_minutes: 15, ..Default::default() };
+            let sessions = pair_sessions(&punches, true, &config);
+            assert_eq!(sessions, [
+                WorkSession { start: time("2026-10-12 08:00:00"), end: Some(time("2026-10-12 16:15:00")) },
+                WorkSession { start: time("2026-10-13 08:00:00"), end: None },
+                WorkSession { start: time("2026-10-14 09:00:00"), end: None },
+            ]);
+        }
+
+        #[test]
+        fn test_weekly_timesheet_across_midnight() {
+            let config = Config {
+                readers: vec![
+                    ReaderConfig { name: String::from("Entrée"), role: ReaderRole::Entry, ..ReaderConfig::default() },
+                    ReaderConfig { name: String::from("Sortie"), role: ReaderRole::Exit, ..ReaderConfig::default() },
+                ],
+                ..Default::default()
+            };
+            let records = [
+                record("2026-10-11 22:00:00", "Entrée"),
+                record("2026-10-12 06:00:00", "Sortie"),
+                record("2026-10-14 09:00:00", "Entrée"),
+                record("2026-10-14 17:30:00", "Sortie"),
+                record("2026-10-15 09:00:00", "Entrée"),
+            ];
+            let timesheet = Timesheet::build(&records, &config, parse_week("2026-W42").unwrap());
+            assert_eq!(timesheet.days, [6 * 3600, 0, 8 * 3600 + 1800, 0, 0, 0, 0]);
+            assert_eq!(timesheet.total(), 14 * 3600 + 1800);
+            assert_eq!(timesheet.sessions.len(), 3);
+            assert_eq!(timesheet.forgotten().count(), 1);
+        }
+    }
+}
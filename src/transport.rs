@@ -0,0 +1,240 @@
+/// Module `transport` définit le canal par lequel les APDU sont échangées avec le lecteur.
+pub mod transport {
+    use pcsc::{Card, Error, MAX_BUFFER_SIZE};
+    use std::collections::VecDeque;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use crate::utils::utils::hex_to_bytes;
+
+    /// Erreurs PC/SC reconnues à la relecture d'une trace, identifiées par leur nom.
+    const REPLAYABLE_ERRORS: [Error; 12] = [
+        Error::Timeout,
+        Error::RemovedCard,
+        Error::ResetCard,
+        Error::NoSmartcard,
+        Error::UnresponsiveCard,
+        Error::UnpoweredCard,
+        Error::CommError,
+        Error::UnknownReader,
+        Error::ReaderUnavailable,
+        Error::NoReadersAvailable,
+        Error::InvalidValue,
+        Error::UnknownError,
+    ];
+
+    /// Commande enregistrée et réponse obtenue lors d'un échange.
+    type Exchange = (Vec<u8>, Result<Vec<u8>, Error>);
+
+    /// Trait `Transport` envoie une APDU de commande et renvoie l'APDU de réponse.
+    ///
+    /// `CardManager` et la couche PN532 reposent sur ce trait, ce qui permet de remplacer
+    /// la connexion PC/SC par une autre implémentation.
+    pub trait Transport {
+        /// Transmet une APDU et renvoie la réponse complète, mot de statut inclus.
+        ///
+        /// # Arguments
+        ///
+        /// * `apdu` - L'APDU de commande.
+        ///
+        /// # Retourne
+        ///
+        /// * `Result<Vec<u8>, Error>` - L'APDU de réponse.
+        fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, Error>;
+    }
+
+    impl Transport for Card {
+        fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+            let mut rapdu = [0; MAX_BUFFER_SIZE];
+            let response = self.transmit(apdu, &mut rapdu)?;
+            Ok(response.to_vec())
+        }
+    }
+
+    /// Ouvre (ou crée) un fichier de trace en ajout, à partager entre plusieurs `TracingTransport`.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// let log = open_trace("apdu.trace")?;
+    /// ```
+    pub fn open_trace(path: &str) -> io::Result<Arc<Mutex<File>>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Arc::new(Mutex::new(file)))
+    }
+
+    /// Structure `TracingTransport` enregistre chaque APDU de commande et de réponse dans un fichier
+    /// de trace, horodatée à la milliseconde, avant de la relayer au transport sous-jacent.
+    ///
+    /// Chaque ligne du fichier a la forme `<horodatage ms>\t<lecteur>\t<sens>\t<données>`, le sens valant
+    /// `>` pour une commande, `<` pour une réponse et `!` pour une erreur PC/SC. Sans fichier, les APDU
+    /// sont simplement relayées.
+    pub struct TracingTransport<T: Transport> {
+        inner: T,
+        reader: String,
+        log: Option<Arc<Mutex<File>>>,
+    }
+
+    impl<T: Transport> TracingTransport<T> {
+        /// Enveloppe un transport en journalisant ses échanges.
+        ///
+        /// # Arguments
+        ///
+        /// * `inner` - Le transport à tracer.
+        /// * `reader` - Le nom du lecteur, repris sur chaque ligne de la trace.
+        /// * `log` - Le fichier de trace partagé, ou `None` pour désactiver la trace.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let log = open_trace("apdu.trace")?;
+        /// let card = CardManager{card: TracingTransport::new(card, &reader.name, Some(log))};
+        /// ```
+        pub fn new(inner: T, reader: &str, log: Option<Arc<Mutex<File>>>) -> TracingTransport<T> {
+            TracingTransport { inner, reader: reader.to_string(), log }
+        }
+
+        fn record(&self, direction: char, data: &str) {
+            if let Some(log) = &self.log {
+                let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|t| t.as_millis()).unwrap_or_default();
+                if let Ok(mut file) = log.lock() {
+                    let _ = writeln!(file, "{}\t{}\t{}\t{}", timestamp, self.reader, direction, data);
+                }
+            }
+        }
+    }
+
+    impl<T: Transport> Transport for TracingTransport<T> {
+        fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+            self.record('>', &to_hex(apdu));
+            let response = self.inner.exchange(apdu);
+            match &response {
+                Ok(rapdu) => self.record('<', &to_hex(rapdu)),
+                Err(e) => self.record('!', &format!("{:?}", e)),
+            }
+            response
+        }
+    }
+
+    /// Structure `ReplayTransport` rejoue une trace enregistrée par `TracingTransport`.
+    ///
+    /// Les réponses sont renvoyées dans l'ordre de la trace, quel que soit le moment de l'appel ;
+    /// une commande différente de celle enregistrée fait échouer l'échange avec `Error::InvalidValue`.
+    pub struct ReplayTransport {
+        exchanges: Mutex<VecDeque<Exchange>>,
+    }
+
+    impl ReplayTransport {
+        /// Charge une trace depuis un fichier.
+        ///
+        /// # Exemples
+        ///
+        /// ```
+        /// let card = CardManager{card: ReplayTransport::load("traces/badge_client.trace")?};
+        /// ```
+        pub fn load(path: &str) -> io::Result<ReplayTransport> {
+            ReplayTransport::parse(&fs::read_to_string(path)?)
+        }
+
+        /// Analyse le contenu d'une trace. Les lignes vides et celles commençant par `#` sont ignorées.
+        pub fn parse(trace: &str) -> io::Result<ReplayTransport> {
+            let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Ligne de trace invalide: {}", line));
+            let mut exchanges = VecDeque::new();
+            let mut command: Option<Vec<u8>> = None;
+
+            for line in trace.lines().map(|line| line.trim_end_matches('\r')).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+                let fields: Vec<&str> = line.splitn(4, '\t').collect();
+                let [_, _, direction, data] = fields[..] else {
+                    return Err(invalid(line));
+                };
+                match direction {
+                    ">" => command = Some(hex_to_bytes(data).ok_or_else(|| invalid(line))?),
+                    "<" | "!" => {
+                        let sent = command.take().ok_or_else(|| invalid(line))?;
+                        let response = if direction == "<" {
+                            Ok(hex_to_bytes(data).ok_or_else(|| invalid(line))?)
+                        } else {
+                            Err(REPLAYABLE_ERRORS.into_iter().find(|e| format!("{:?}", e) == data).unwrap_or(Error::UnknownError))
+                        };
+                        exchanges.push_back((sent, response));
+                    }
+                    _ => return Err(invalid(line)),
+                }
+            }
+
+            Ok(ReplayTransport { exchanges: Mutex::new(exchanges) })
+        }
+
+        /// Nombre d'échanges de la trace qui n'ont pas encore été rejoués.
+        pub fn remaining(&self) -> usize {
+            self.exchanges.lock().map(|exchanges| exchanges.len()).unwrap_or_default()
+        }
+    }
+
+    impl Transport for ReplayTransport {
+        fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+            let mut exchanges = self.exchanges.lock().map_err(|_| Error::InternalError)?;
+            match exchanges.pop_front() {
+                Some((expected, response)) if expected == apdu => response,
+                Some((expected, _)) => {
+                    eprintln!("Commande inattendue: {} au lieu de {}", to_hex(apdu), to_hex(&expected));
+                    Err(Error::InvalidValue)
+                }
+                None => {
+                    eprintln!("Trace épuisée, commande: {}", to_hex(apdu));
+                    Err(Error::InvalidValue)
+                }
+            }
+        }
+    }
+
+    /// Formate des octets en hexadécimal majuscule, deux caractères par octet.
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Transport de test qui renvoie la commande reçue suivie de `90 00`.
+        struct EchoTransport;
+
+        impl Transport for EchoTransport {
+            fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+                if apdu.is_empty() {
+                    return Err(Error::RemovedCard);
+                }
+                Ok([apdu, &[0x90, 0x00]].concat())
+            }
+        }
+
+        #[test]
+        fn test_trace_and_replay() {
+            let path = "trace_test.trace";
+            let _ = fs::remove_file(path);
+
+            let tracing = TracingTransport::new(EchoTransport, "Lecteur 00", Some(open_trace(path).unwrap()));
+            assert_eq!(tracing.exchange(&[0xFF, 0xCA]).unwrap(), [0xFF, 0xCA, 0x90, 0x00]);
+            assert_eq!(tracing.exchange(&[]), Err(Error::RemovedCard));
+            drop(tracing);
+
+            let trace = fs::read_to_string(path).unwrap();
+            fs::remove_file(path).unwrap();
+            assert!(trace.lines().next().unwrap().ends_with("\tLecteur 00\t>\tFFCA"));
+
+            let replay = ReplayTransport::parse(&trace).unwrap();
+            assert_eq!(replay.remaining(), 2);
+            assert_eq!(replay.exchange(&[0xFF, 0xCA]).unwrap(), [0xFF, 0xCA, 0x90, 0x00]);
+            assert_eq!(replay.exchange(&[]), Err(Error::RemovedCard));
+            assert_eq!(replay.exchange(&[0xFF]), Err(Error::InvalidValue));
+        }
+
+        #[test]
+        fn test_replay_unexpected_command() {
+            let replay = ReplayTransport::parse("1\tLecteur\t>\tFF00\n2\tLecteur\t<\t9000\n").unwrap();
+            assert_eq!(replay.exchange(&[0xFF, 0x01]), Err(Error::InvalidValue));
+        }
+    }
+}
@@ -43,13 +43,11 @@ pub mod utils {
     ///
     pub fn _hexa_to_tableau(hexa: String) -> [u8; 16] {
         let mut tableau = [0u8; 16];
-        let mut index = 0;
 
-        for chunk in hexa.as_bytes().chunks_exact(2) {
+        for (index, chunk) in hexa.as_bytes().chunks_exact(2).enumerate() {
             let hex_str = std::str::from_utf8(chunk).unwrap();
             let byte = u8::from_str_radix(hex_str, 16).unwrap();
             tableau[index] = byte;
-            index += 1;
         }
 
         tableau
@@ -116,6 +114,31 @@ pub mod utils {
     decimals.iter().map(|&num| format!("{:X}", num)).collect::<Vec<String>>().join("")
     }
 
+    /// Convertit une chaîne hexadécimale de longueur quelconque en vecteur d'octets.
+    ///
+    /// # Arguments
+    ///
+    /// * `hexa` - Chaîne hexadécimale, d'un nombre pair de caractères.
+    ///
+    /// # Retourne
+    ///
+    /// * `Option<Vec<u8>>` - Les octets, ou `None` si la chaîne n'est pas un hexadécimal valide.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// assert_eq!(utils::hex_to_bytes("30A4"), Some(vec![0x30, 0xA4]));
+    /// ```
+    pub fn hex_to_bytes(hexa: &str) -> Option<Vec<u8>> {
+        if !hexa.len().is_multiple_of(2) {
+            return None;
+        }
+        hexa.as_bytes()
+            .chunks_exact(2)
+            .map(|chunk| u8::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok())
+            .collect()
+    }
+
     /// Génère un token aléatoire de 16 octets et le retourne sous forme de chaîne hexadécimale.
     ///
     /// # Retourne
@@ -125,10 +148,10 @@ pub mod utils {
     /// # Exemples
     ///
     /// ```
-    /// let token = utils::_rngtoken();
+    /// let token = utils::rngtoken();
     /// assert_eq!(token.len(), 32); // Un token de 16 octets donne une chaîne hexadécimale de 32 caractères.
     /// ```
-    pub fn _rngtoken() -> String {
+    pub fn rngtoken() -> String {
         let mut rng = rand::thread_rng();
         let uid: [u8; 16] = rng.gen();
         let uid_hex: String = uid.iter().map(|byte| format!("{:02x}", byte)).collect();
@@ -139,9 +162,9 @@ pub mod utils {
         use super::*;
         #[test]
         fn test_rngtoken() {
-            let result = _rngtoken();
+            let result = rngtoken();
             assert_eq!(result.len(), 32);
-            assert!(result.chars().all(|c| c.is_digit(16)));
+            assert!(result.chars().all(|c| c.is_ascii_hexdigit()));
         }
         #[test]
         fn test_hexa_to_tableau() {
@@ -160,6 +183,12 @@ pub mod utils {
             assert_eq!(result, expected_output);
         }
         #[test]
+        fn test_hex_to_bytes() {
+            assert_eq!(hex_to_bytes("30a4FF"), Some(vec![0x30, 0xA4, 0xFF]));
+            assert_eq!(hex_to_bytes("30A"), None);
+            assert_eq!(hex_to_bytes("ZZ"), None);
+        }
+        #[test]
         fn test_decimals_to_hex() {
             let input = vec![10, 15, 20];
             let expected_output = "AF14";